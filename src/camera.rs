@@ -1,5 +1,8 @@
+use nalgebra::{Unit, UnitQuaternion, Vector3};
 use nalgebra_glm::{rotate_vec3, Vec3};
+use serde::Deserialize;
 use std::f32::consts::PI;
+use std::{fs, io};
 
 pub struct Camera {
     pub eye: Vec3,
@@ -48,6 +51,35 @@ impl Camera {
         self.has_changed = true;
     }
 
+    // Base de la cámara: adelante (hacia `center`), derecha y arriba, ortonormales
+    fn basis(&self) -> (Vec3, Vec3, Vec3) {
+        let forward = (self.center - self.eye).normalize();
+        let right = forward.cross(&self.up).normalize();
+        (forward, right, right.cross(&forward))
+    }
+
+    // Traslada `eye` y `center` juntos, así la cámara se mueve sin cambiar hacia dónde mira
+    fn translate(&mut self, offset: Vec3) {
+        self.eye += offset;
+        self.center += offset;
+        self.has_changed = true;
+    }
+
+    pub fn move_forward(&mut self, distance: f32) {
+        let (forward, _, _) = self.basis();
+        self.translate(forward * distance);
+    }
+
+    pub fn move_right(&mut self, distance: f32) {
+        let (_, right, _) = self.basis();
+        self.translate(right * distance);
+    }
+
+    pub fn move_up(&mut self, distance: f32) {
+        let (_, _, up) = self.basis();
+        self.translate(up * distance);
+    }
+
     pub fn move_center(&mut self, direction: Vec3) {
         let radius_vector = self.center - self.eye;
         let radius = radius_vector.magnitude();
@@ -64,3 +96,254 @@ impl Camera {
         self.has_changed = true;
     }
 }
+
+// Órbita automática alrededor del Sol para tomas cinematográficas
+pub struct CinematicOrbit {
+    pub radius: f32,
+    pub height: f32,
+    pub speed: f32,
+    pub angle: f32,
+}
+
+impl CinematicOrbit {
+    pub fn new(radius: f32, height: f32, speed: f32) -> Self {
+        CinematicOrbit {
+            radius,
+            height,
+            speed,
+            angle: 0.0,
+        }
+    }
+
+    pub fn update(&mut self, camera: &mut Camera, delta_time: f32) {
+        self.angle = (self.angle + self.speed * delta_time) % (2.0 * PI);
+
+        // El plano eclíptico es XY, así que la altura se mide sobre el eje Z
+        camera.eye = Vec3::new(
+            self.radius * self.angle.cos(),
+            self.radius * self.angle.sin(),
+            self.height,
+        );
+        camera.center = Vec3::new(0.0, 0.0, 0.0);
+        camera.up = Vec3::new(0.0, 0.0, 1.0);
+        camera.has_changed = true;
+    }
+}
+
+// Vuelo suave de la cámara hacia un cuerpo. El destino (eye, center) se pasa en cada frame,
+// así la cámara sigue al planeta mientras este avanza en su órbita durante el trayecto
+pub struct CameraTransition {
+    from: (Vec3, Vec3), // eye y center al empezar
+    pub to: usize,      // Índice del cuerpo de destino
+    start_time: f32,
+    duration: f32, // Segundos
+}
+
+impl CameraTransition {
+    pub fn start(camera: &Camera, to: usize, start_time: f32, duration: f32) -> Self {
+        CameraTransition {
+            from: (camera.eye, camera.center),
+            to,
+            start_time,
+            duration: duration.max(1e-3),
+        }
+    }
+
+    // Lleva la cámara al punto del trayecto en `time`, con aceleración y frenado suaves
+    // (smoothstep); devuelve true cuando ya llegó a `target`
+    pub fn apply(&self, camera: &mut Camera, target: (Vec3, Vec3), time: f32) -> bool {
+        let t = ((time - self.start_time) / self.duration).clamp(0.0, 1.0);
+        let eased = t * t * (3.0 - 2.0 * t);
+
+        camera.eye = self.from.0 + (target.0 - self.from.0) * eased;
+        camera.center = self.from.1 + (target.1 - self.from.1) * eased;
+        camera.has_changed = true;
+        t >= 1.0
+    }
+}
+
+// Fotograma clave de un recorrido de cámara, tal como se escribe en el JSON
+#[derive(Deserialize)]
+struct CameraKeyframe {
+    time: f32, // Segundos desde que empieza la reproducción
+    eye: [f32; 3],
+    center: [f32; 3],
+    up: [f32; 3],
+}
+
+#[derive(Deserialize)]
+struct CameraPathFile {
+    keyframes: Vec<CameraKeyframe>,
+}
+
+// Recorrido de cámara por fotogramas clave cargado de un JSON. Entre dos claves se interpola
+// con Catmull-Rom: la tangente de cada clave se calcula con sus vecinas y se comparte entre
+// los dos tramos que la tocan, así la velocidad no salta al pasar por ella aunque las claves
+// no estén espaciadas por igual en el tiempo.
+pub struct CameraPath {
+    times: Vec<f32>,
+    eyes: Vec<Vec3>,
+    centers: Vec<Vec3>,
+    ups: Vec<Vec3>,
+}
+
+impl CameraPath {
+    pub fn load(path: &str) -> io::Result<Self> {
+        let file: CameraPathFile = serde_json::from_str(&fs::read_to_string(path)?)?;
+        let keyframes = file.keyframes;
+
+        if keyframes.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "el recorrido no tiene fotogramas clave",
+            ));
+        }
+        if keyframes
+            .windows(2)
+            .any(|pair| pair[1].time <= pair[0].time)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "los tiempos de los fotogramas clave deben ser crecientes",
+            ));
+        }
+
+        let vector = |v: [f32; 3]| Vec3::new(v[0], v[1], v[2]);
+        Ok(CameraPath {
+            times: keyframes.iter().map(|key| key.time).collect(),
+            eyes: keyframes.iter().map(|key| vector(key.eye)).collect(),
+            centers: keyframes.iter().map(|key| vector(key.center)).collect(),
+            ups: keyframes.iter().map(|key| vector(key.up)).collect(),
+        })
+    }
+
+    // Tiempo de la última clave; después el recorrido se queda en ella
+    pub fn duration(&self) -> f32 {
+        self.times[self.times.len() - 1]
+    }
+
+    // (eye, center, up) en el tiempo `time`; antes de la primera clave y después de la
+    // última se mantiene la clave del extremo
+    pub fn sample(&self, time: f32) -> (Vec3, Vec3, Vec3) {
+        if self.times.len() == 1 {
+            return (self.eyes[0], self.centers[0], self.ups[0]);
+        }
+
+        let time = time.clamp(self.times[0], self.duration());
+        let segment = self
+            .times
+            .partition_point(|&key_time| key_time <= time)
+            .saturating_sub(1)
+            .min(self.times.len() - 2);
+
+        let up = self.catmull_rom(&self.ups, segment, time);
+        let up = if up.norm() > 1e-6 {
+            up.normalize()
+        } else {
+            self.ups[segment]
+        };
+
+        (
+            self.catmull_rom(&self.eyes, segment, time),
+            self.catmull_rom(&self.centers, segment, time),
+            up,
+        )
+    }
+
+    pub fn apply(&self, camera: &mut Camera, time: f32) {
+        (camera.eye, camera.center, camera.up) = self.sample(time);
+        camera.has_changed = true;
+    }
+
+    // Hermite cúbico entre las claves `i` e `i + 1`. Las tangentes están en unidades por
+    // segundo y se escalan por la duración del tramo; en los extremos se usa la diferencia
+    // hacia el único vecino.
+    fn catmull_rom(&self, points: &[Vec3], i: usize, time: f32) -> Vec3 {
+        let last = points.len() - 1;
+        let tangent = |k: usize| {
+            let previous = k.saturating_sub(1);
+            let next = (k + 1).min(last);
+            (points[next] - points[previous]) / (self.times[next] - self.times[previous])
+        };
+
+        let duration = self.times[i + 1] - self.times[i];
+        let s = (time - self.times[i]) / duration;
+        let s2 = s * s;
+        let s3 = s2 * s;
+
+        points[i] * (2.0 * s3 - 3.0 * s2 + 1.0)
+            + tangent(i) * duration * (s3 - 2.0 * s2 + s)
+            + points[i + 1] * (-2.0 * s3 + 3.0 * s2)
+            + tangent(i + 1) * duration * (s3 - s2)
+    }
+}
+
+// Orientación almacenada como cuaternión unitario: evita el bloqueo de cardán y la
+// deriva de aplicar rotaciones con matrices una y otra vez. En el espacio local la
+// nave mira hacia -Z con +Y como "arriba".
+pub struct Orientation {
+    rotation: UnitQuaternion<f32>,
+}
+
+impl Orientation {
+    pub fn identity() -> Self {
+        Orientation {
+            rotation: UnitQuaternion::identity(),
+        }
+    }
+
+    pub fn quaternion(&self) -> UnitQuaternion<f32> {
+        self.rotation
+    }
+
+    pub fn direction(&self) -> Vec3 {
+        self.rotation * Vec3::new(0.0, 0.0, -1.0)
+    }
+
+    pub fn up(&self) -> Vec3 {
+        self.rotation * Vec3::new(0.0, 1.0, 0.0)
+    }
+
+    // Giro alrededor del eje "arriba" local
+    pub fn yaw(&mut self, angle: f32) {
+        self.rotate_local(&Vector3::y_axis(), angle);
+    }
+
+    // Giro alrededor del eje "derecha" local
+    pub fn pitch(&mut self, angle: f32) {
+        self.rotate_local(&Vector3::x_axis(), angle);
+    }
+
+    // Giro alrededor del eje de avance local
+    pub fn roll(&mut self, angle: f32) {
+        self.rotate_local(&-Vector3::z_axis(), angle);
+    }
+
+    fn rotate_local(&mut self, axis: &Unit<Vector3<f32>>, angle: f32) {
+        self.rotation *= UnitQuaternion::from_axis_angle(axis, angle);
+        self.rotation.renormalize();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn orientation_stays_orthonormal_after_many_rotations() {
+        let mut orientation = Orientation::identity();
+        for step in 0..10_000 {
+            let angle = 0.01 + (step % 7) as f32 * 0.003;
+            match step % 3 {
+                0 => orientation.yaw(angle),
+                1 => orientation.pitch(-angle),
+                _ => orientation.roll(angle * 2.0),
+            }
+        }
+
+        let (direction, up) = (orientation.direction(), orientation.up());
+        assert!((direction.magnitude() - 1.0).abs() < 1e-4);
+        assert!((up.magnitude() - 1.0).abs() < 1e-4);
+        assert!(direction.dot(&up).abs() < 1e-4);
+    }
+}
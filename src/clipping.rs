@@ -0,0 +1,102 @@
+use crate::vertex::Vertex;
+use nalgebra_glm::{Mat4, Vec3, Vec4};
+
+// Planos del frustum en espacio de clip (convención de OpenGL: -w <= x, y, z <= w).
+// Cada función devuelve la distancia con signo al plano; negativa = fuera.
+const FRUSTUM_PLANES: [fn(&Vec4) -> f32; 6] = [
+    |p| p.w + p.x, // Izquierda
+    |p| p.w - p.x, // Derecha
+    |p| p.w + p.y, // Abajo
+    |p| p.w - p.y, // Arriba
+    |p| p.w + p.z, // Cerca
+    |p| p.w - p.z, // Lejos
+];
+
+// Recorta un triángulo (ya procesado por el vertex shader) contra los seis planos del
+// frustum y devuelve los triángulos resultantes. Los vértices nuevos se interpolan en
+// espacio de clip y se proyectan otra vez con `viewport_matrix`.
+pub fn clip_triangle(triangle: &[Vertex; 3], viewport_matrix: &Mat4) -> Vec<[Vertex; 3]> {
+    let inside_all = |plane: &fn(&Vec4) -> f32| {
+        triangle
+            .iter()
+            .all(|vertex| plane(&vertex.clip_position) >= 0.0)
+    };
+    let outside_any = |plane: &fn(&Vec4) -> f32| {
+        triangle
+            .iter()
+            .all(|vertex| plane(&vertex.clip_position) < 0.0)
+    };
+
+    // Casos comunes: completamente dentro o completamente fuera de algún plano
+    if FRUSTUM_PLANES.iter().all(inside_all) {
+        return vec![triangle.clone()];
+    }
+    if FRUSTUM_PLANES.iter().any(outside_any) {
+        return Vec::new();
+    }
+
+    // Sutherland-Hodgman: el polígono se recorta plano por plano
+    let mut polygon: Vec<Vertex> = triangle.to_vec();
+    for plane in &FRUSTUM_PLANES {
+        let mut clipped = Vec::with_capacity(polygon.len() + 2);
+
+        for (i, current) in polygon.iter().enumerate() {
+            let next = &polygon[(i + 1) % polygon.len()];
+            let current_distance = plane(&current.clip_position);
+            let next_distance = plane(&next.clip_position);
+
+            if current_distance >= 0.0 {
+                clipped.push(current.clone());
+            }
+            if (current_distance >= 0.0) != (next_distance >= 0.0) {
+                let t = current_distance / (current_distance - next_distance);
+                clipped.push(interpolate(current, next, t, viewport_matrix));
+            }
+        }
+
+        polygon = clipped;
+        if polygon.len() < 3 {
+            return Vec::new();
+        }
+    }
+
+    // El polígono recortado es convexo: se triangula en abanico
+    (1..polygon.len() - 1)
+        .map(|i| {
+            [
+                polygon[0].clone(),
+                polygon[i].clone(),
+                polygon[i + 1].clone(),
+            ]
+        })
+        .collect()
+}
+
+fn interpolate(a: &Vertex, b: &Vertex, t: f32, viewport_matrix: &Mat4) -> Vertex {
+    let clip_position = a.clip_position + (b.clip_position - a.clip_position) * t;
+    let ndc_position = Vec4::new(
+        clip_position.x / clip_position.w,
+        clip_position.y / clip_position.w,
+        clip_position.z / clip_position.w,
+        1.0,
+    );
+    let screen_position = viewport_matrix * ndc_position;
+
+    Vertex {
+        position: a.position + (b.position - a.position) * t,
+        normal: a.normal + (b.normal - a.normal) * t,
+        tex_coords: a.tex_coords + (b.tex_coords - a.tex_coords) * t,
+        color: a.color.lerp(&b.color, t),
+        material: a.material,
+        tangent: a.tangent + (b.tangent - a.tangent) * t,
+        bitangent: a.bitangent + (b.bitangent - a.bitangent) * t,
+        transformed_position: Vec3::new(screen_position.x, screen_position.y, screen_position.z),
+        transformed_normal: a.transformed_normal
+            + (b.transformed_normal - a.transformed_normal) * t,
+        transformed_tangent: a.transformed_tangent
+            + (b.transformed_tangent - a.transformed_tangent) * t,
+        transformed_bitangent: a.transformed_bitangent
+            + (b.transformed_bitangent - a.transformed_bitangent) * t,
+        clip_position,
+    }
+}
@@ -1,17 +1,22 @@
 use std::fmt;
-use std::ops::{Add, Mul};
+use std::ops::{Add, Div, Mul, Sub};
 
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub struct Color {
     pub r: u8,
     pub g: u8,
     pub b: u8,
+    pub a: u8, // Opacity: 255 is fully opaque, 0 fully transparent
 }
 
 impl Color {
-    // Constructor to initialize the color using r, g, b values as u8
+    // Constructor to initialize the color using r, g, b values as u8 (fully opaque)
     pub fn new(r: u8, g: u8, b: u8) -> Self {
-        Color { r, g, b }
+        Color::new_rgba(r, g, b, 255)
+    }
+
+    pub fn new_rgba(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Color { r, g, b, a }
     }
 
     fn clamp(value: i32) -> u8 {
@@ -28,16 +33,29 @@ impl Color {
         Color::new(0, 0, 0)
     }
 
+    // 0xRRGGBB, as the framebuffer uses it; the color comes out opaque
     pub fn from_hex(hex: u32) -> Color {
+        Color::from_hex_rgba(0xFF00_0000 | (hex & 0xFF_FFFF))
+    }
+
+    // 0xRRGGBB, dropping the alpha
+    pub fn to_hex(&self) -> u32 {
+        ((self.r as u32) << 16) | ((self.g as u32) << 8) | (self.b as u32)
+    }
+
+    // 0xAARRGGBB
+    pub fn from_hex_rgba(hex: u32) -> Color {
         Color {
             r: ((hex >> 16) & 0xFF) as u8,
             g: ((hex >> 8) & 0xFF) as u8,
             b: (hex & 0xFF) as u8,
+            a: (hex >> 24) as u8,
         }
     }
 
-    pub fn to_hex(&self) -> u32 {
-        ((self.r as u32) << 16) | ((self.g as u32) << 8) | (self.b as u32)
+    #[allow(dead_code)] // The framebuffer and the exports work in opaque 0xRRGGBB
+    pub fn to_hex_rgba(self) -> u32 {
+        ((self.a as u32) << 24) | self.to_hex()
     }
 
     // New constructor to initialize the color using r, g, b values as f32 (0.0 to 1.0)
@@ -46,19 +64,92 @@ impl Color {
             r: (r.clamp(0.0, 1.0) * 255.0) as u8,
             g: (g.clamp(0.0, 1.0) * 255.0) as u8,
             b: (b.clamp(0.0, 1.0) * 255.0) as u8,
+            a: 255,
         }
     }
 
-    // Linear interpolation between two colors
+    // Constructor from hue (degrees, wraps around), saturation and value (0.0 to 1.0)
+    pub fn from_hsv(hue: f32, saturation: f32, value: f32) -> Self {
+        let hue = hue.rem_euclid(360.0);
+        let saturation = saturation.clamp(0.0, 1.0);
+        let value = value.clamp(0.0, 1.0);
+
+        let chroma = value * saturation;
+        let x = chroma * (1.0 - ((hue / 60.0) % 2.0 - 1.0).abs());
+        let m = value - chroma;
+
+        let (r, g, b) = match (hue / 60.0) as u32 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+
+        Color::from_float(r + m, g + m, b + m)
+    }
+
+    // Inverse of `from_hsv`: hue in degrees [0, 360), saturation and value from 0.0 to 1.0.
+    // Grays have no hue and return 0
+    pub fn to_hsv(self) -> (f32, f32, f32) {
+        let r = self.r as f32 / 255.0;
+        let g = self.g as f32 / 255.0;
+        let b = self.b as f32 / 255.0;
+
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let chroma = max - min;
+
+        let hue = if chroma == 0.0 {
+            0.0
+        } else if max == r {
+            60.0 * ((g - b) / chroma).rem_euclid(6.0)
+        } else if max == g {
+            60.0 * ((b - r) / chroma + 2.0)
+        } else {
+            60.0 * ((r - g) / chroma + 4.0)
+        };
+        let saturation = if max == 0.0 { 0.0 } else { chroma / max };
+
+        (hue.rem_euclid(360.0), saturation, max)
+    }
+
+    // Linear interpolation between two colors (alpha included)
     pub fn lerp(&self, other: &Color, t: f32) -> Self {
         let t = t.clamp(0.0, 1.0);
+        let mix = |from: u8, to: u8| (from as f32 + (to as f32 - from as f32) * t).round() as u8;
+        Color {
+            r: mix(self.r, other.r),
+            g: mix(self.g, other.g),
+            b: mix(self.b, other.b),
+            a: mix(self.a, other.a),
+        }
+    }
+
+    // Source-over compositing: `over` is drawn on top of `self` with its own alpha
+    pub fn blend_alpha(&self, over: &Color) -> Color {
+        let alpha = over.a as f32 / 255.0;
+        let mix = |below: u8, above: u8| {
+            (above as f32 * alpha + below as f32 * (1.0 - alpha)).round() as u8
+        };
         Color {
-            r: (self.r as f32 + (other.r as f32 - self.r as f32) * t).round() as u8,
-            g: (self.g as f32 + (other.g as f32 - self.g as f32) * t).round() as u8,
-            b: (self.b as f32 + (other.b as f32 - self.b as f32) * t).round() as u8,
+            r: mix(self.r, over.r),
+            g: mix(self.g, over.g),
+            b: mix(self.b, over.b),
+            a: (over.a as f32 + self.a as f32 * (1.0 - alpha)).round() as u8,
         }
     }
 
+    // Compares each channel (alpha included) within `tolerance`, so rounding differences don't
+    // break comparisons
+    pub fn approx_eq(&self, other: &Color, tolerance: u8) -> bool {
+        self.r.abs_diff(other.r) <= tolerance
+            && self.g.abs_diff(other.g) <= tolerance
+            && self.b.abs_diff(other.b) <= tolerance
+            && self.a.abs_diff(other.a) <= tolerance
+    }
+
     pub fn is_black(&self) -> bool {
         self.r == 0 && self.g == 0 && self.b == 0
     }
@@ -72,19 +163,22 @@ impl Color {
         }
     }
 
+    // The blend modes below work on the color channels and keep `self`'s alpha
     pub fn blend_multiply(&self, blend: &Color) -> Color {
-        Color::new(
+        Color::new_rgba(
             ((self.r as f32 * blend.r as f32) / 255.0) as u8,
             ((self.g as f32 * blend.g as f32) / 255.0) as u8,
             ((self.b as f32 * blend.b as f32) / 255.0) as u8,
+            self.a,
         )
     }
 
     pub fn blend_add(&self, blend: &Color) -> Color {
-        Color::new(
+        Color::new_rgba(
             (self.r as u16 + blend.r as u16).min(255) as u8,
             (self.g as u16 + blend.g as u16).min(255) as u8,
             (self.b as u16 + blend.b as u16).min(255) as u8,
+            self.a,
         )
     }
 
@@ -93,14 +187,69 @@ impl Color {
         let g = (self.g as i16 - blend.g as i16).max(0).min(255) as u8;
         let b = (self.b as i16 - blend.b as i16).max(0).min(255) as u8;
 
-        Color::new(r, g, b)
+        Color::new_rgba(r, g, b, self.a)
     }
 
     pub fn blend_screen(&self, blend: &Color) -> Color {
-        Color::new(
+        Color::new_rgba(
             255 - ((255 - self.r as u16) * (255 - blend.r as u16) / 255) as u8,
             255 - ((255 - self.g as u16) * (255 - blend.g as u16) / 255) as u8,
             255 - ((255 - self.b as u16) * (255 - blend.b as u16) / 255) as u8,
+            self.a,
+        )
+    }
+
+    // Multiply where `self` is dark and screen where it is light, so the base keeps its contrast
+    pub fn blend_overlay(&self, blend: &Color) -> Color {
+        self.blend_channels(blend, |base, blend| {
+            if base < 0.5 {
+                2.0 * base * blend
+            } else {
+                1.0 - 2.0 * (1.0 - base) * (1.0 - blend)
+            }
+        })
+    }
+
+    pub fn blend_lighten(&self, blend: &Color) -> Color {
+        Color::new_rgba(
+            self.r.max(blend.r),
+            self.g.max(blend.g),
+            self.b.max(blend.b),
+            self.a,
+        )
+    }
+
+    pub fn blend_darken(&self, blend: &Color) -> Color {
+        Color::new_rgba(
+            self.r.min(blend.r),
+            self.g.min(blend.g),
+            self.b.min(blend.b),
+            self.a,
+        )
+    }
+
+    // Brightens `self` by dividing it by the inverse of `blend`; a white blend saturates to white
+    #[allow(dead_code)] // Only `combined_blend_shader` offers it for now
+    pub fn blend_color_dodge(&self, blend: &Color) -> Color {
+        self.blend_channels(blend, |base, blend| {
+            if blend >= 1.0 {
+                1.0
+            } else {
+                (base / (1.0 - blend)).min(1.0)
+            }
+        })
+    }
+
+    // Applies `mode` to each color channel as 0.0 - 1.0 values, keeping `self`'s alpha
+    fn blend_channels(&self, blend: &Color, mode: impl Fn(f32, f32) -> f32) -> Color {
+        let channel = |base: u8, blend: u8| {
+            (mode(base as f32 / 255.0, blend as f32 / 255.0).clamp(0.0, 1.0) * 255.0).round() as u8
+        };
+        Color::new_rgba(
+            channel(self.r, blend.r),
+            channel(self.g, blend.g),
+            channel(self.b, blend.b),
+            self.a,
         )
     }
 }
@@ -119,6 +268,20 @@ impl Add for Color {
             r: self.r.saturating_add(other.r),
             g: self.g.saturating_add(other.g),
             b: self.b.saturating_add(other.b),
+            a: self.a.saturating_add(other.a),
+        }
+    }
+}
+
+impl Sub for Color {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self {
+            r: self.r.saturating_sub(other.r),
+            g: self.g.saturating_sub(other.g),
+            b: self.b.saturating_sub(other.b),
+            a: self.a, // Subtracting light doesn't make the color see-through
         }
     }
 }
@@ -131,12 +294,162 @@ impl Mul<f32> for Color {
             r: ((self.r as f32 * scalar).clamp(0.0, 255.0)) as u8,
             g: ((self.g as f32 * scalar).clamp(0.0, 255.0)) as u8,
             b: ((self.b as f32 * scalar).clamp(0.0, 255.0)) as u8,
+            a: ((self.a as f32 * scalar).clamp(0.0, 255.0)) as u8,
+        }
+    }
+}
+
+// Component-wise multiply, same as blend_multiply
+impl Mul<Color> for Color {
+    type Output = Self;
+
+    fn mul(self, other: Color) -> Self {
+        self.blend_multiply(&other)
+    }
+}
+
+impl Div<f32> for Color {
+    type Output = Self;
+
+    fn div(self, scalar: f32) -> Self {
+        let scaled = if scalar == 0.0 {
+            Color::black()
+        } else {
+            self * (1.0 / scalar)
+        };
+        // Dividing dims the color without making it see-through, like `Sub`
+        Color {
+            a: self.a,
+            ..scaled
         }
     }
 }
 
 impl fmt::Display for Color {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Color(r: {}, g: {}, b: {})", self.r, self.g, self.b)
+        write!(
+            f,
+            "Color(r: {}, g: {}, b: {}, a: {})",
+            self.r, self.g, self.b, self.a
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blend_alpha_opaque_replaces_the_base() {
+        let base = Color::new(10, 20, 30);
+        let over = Color::new(200, 100, 50);
+        assert_eq!(base.blend_alpha(&over), over);
+    }
+
+    #[test]
+    fn blend_alpha_transparent_keeps_the_base() {
+        let base = Color::new(10, 20, 30);
+        let over = Color::new_rgba(200, 100, 50, 0);
+        assert_eq!(base.blend_alpha(&over), base);
+    }
+
+    #[test]
+    fn blend_alpha_half_mixes_both() {
+        let base = Color::new(0, 100, 200);
+        let over = Color::new_rgba(200, 100, 0, 128);
+        assert!(base
+            .blend_alpha(&over)
+            .approx_eq(&Color::new(100, 100, 100), 1));
+    }
+
+    #[test]
+    fn hex_rgba_round_trips() {
+        let color = Color::new_rgba(0x12, 0x34, 0x56, 0x78);
+        assert_eq!(color.to_hex_rgba(), 0x7812_3456);
+        assert_eq!(Color::from_hex_rgba(color.to_hex_rgba()), color);
+        assert_eq!(Color::from_hex_rgba(0x7812_3456), color);
+        assert_eq!(Color::from_hex(0x7812_3456), Color::new(0x12, 0x34, 0x56));
+    }
+
+    #[test]
+    fn approx_eq_compares_alpha() {
+        let color = Color::new(50, 50, 50);
+        assert!(color.approx_eq(&Color::new_rgba(51, 49, 50, 254), 1));
+        assert!(!color.approx_eq(&Color::new_rgba(50, 50, 50, 128), 1));
+    }
+
+    #[test]
+    fn mid_gray_is_within_tolerance() {
+        let gray = Color::black().lerp(&Color::new(255, 255, 255), 0.5);
+        assert!(gray.approx_eq(&Color::new(127, 127, 127), 2));
+        assert!(gray.approx_eq(&Color::new(129, 129, 129), 2));
+        assert!(!gray.approx_eq(&Color::new(124, 128, 128), 2));
+    }
+
+    #[test]
+    fn hsv_round_trips_primaries_and_gray() {
+        let cases = [
+            (Color::new(255, 0, 0), (0.0, 1.0, 1.0)),
+            (Color::new(0, 255, 0), (120.0, 1.0, 1.0)),
+            (Color::new(0, 0, 255), (240.0, 1.0, 1.0)),
+            (Color::new(128, 128, 128), (0.0, 0.0, 128.0 / 255.0)),
+        ];
+        for (color, hsv) in cases {
+            assert_eq!(color.to_hsv(), hsv);
+            assert!(Color::from_hsv(hsv.0, hsv.1, hsv.2).approx_eq(&color, 1));
+        }
+    }
+
+    #[test]
+    fn overlay_darkens_darks_and_lightens_lights() {
+        let base = Color::new_rgba(64, 192, 128, 200);
+        let blend = Color::new(64, 64, 255);
+        assert_eq!(
+            base.blend_overlay(&blend),
+            Color::new_rgba(32, 161, 255, 200)
+        );
+    }
+
+    #[test]
+    fn lighten_and_darken_pick_each_channel() {
+        let base = Color::new(10, 200, 128);
+        let blend = Color::new(100, 50, 128);
+        assert_eq!(base.blend_lighten(&blend), Color::new(100, 200, 128));
+        assert_eq!(base.blend_darken(&blend), Color::new(10, 50, 128));
+    }
+
+    #[test]
+    fn color_dodge_brightens_and_saturates() {
+        let base = Color::new(64, 200, 10);
+        let blend = Color::new(128, 128, 255);
+        assert_eq!(base.blend_color_dodge(&blend), Color::new(129, 255, 255));
+        assert_eq!(base.blend_color_dodge(&Color::black()), base);
+    }
+
+    #[test]
+    fn sub_saturates_at_zero_and_keeps_alpha() {
+        let color = Color::new_rgba(100, 20, 255, 200) - Color::new(50, 40, 255);
+        assert_eq!(color, Color::new_rgba(50, 0, 0, 200));
+    }
+
+    #[test]
+    fn div_scales_and_clamps() {
+        assert_eq!(Color::new(200, 100, 50) / 2.0, Color::new(100, 50, 25));
+        assert_eq!(Color::new(200, 100, 50) / 0.5, Color::new(255, 200, 100));
+        assert_eq!(Color::new(200, 100, 50) / 0.0, Color::black());
+        assert_eq!(
+            Color::new_rgba(200, 100, 50, 128) / 2.0,
+            Color::new_rgba(100, 50, 25, 128)
+        );
+
+        // Averaging two opaque colors stays opaque
+        let average = (Color::new(100, 20, 0) + Color::new(50, 40, 10)) / 2.0;
+        assert_eq!(average, Color::new(75, 30, 5));
+    }
+
+    #[test]
+    fn mul_color_is_component_wise() {
+        let color = Color::new(255, 128, 0) * Color::new(128, 255, 255);
+        assert_eq!(color, Color::new(128, 128, 0));
     }
 }
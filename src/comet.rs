@@ -0,0 +1,56 @@
+use nalgebra_glm::Vec3;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::f32::consts::PI;
+
+// Cometa con una trayectoria elíptica propia alrededor del Sol
+pub struct Comet {
+    pub radius_x: f32,    // Radio en X antes de rotar la órbita
+    pub radius_y: f32,    // Radio en Y antes de rotar la órbita
+    pub speed: f32,       // Velocidad angular (rad/s)
+    pub phase: f32,       // Desfase inicial en la órbita
+    pub tilt: f32,        // Rotación de la elipse dentro del plano eclíptico
+    pub inclination: f32, // Amplitud de la oscilación fuera del plano (eje Z)
+    pub size: f32,
+}
+
+impl Comet {
+    // Genera `count` cometas con parámetros aleatorios a partir de una semilla fija
+    pub fn spawn(count: usize, seed: u64) -> Vec<Comet> {
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        (0..count)
+            .map(|_| Comet {
+                radius_x: rng.gen_range(4.0..24.0),
+                radius_y: rng.gen_range(2.0..12.0),
+                speed: rng.gen_range(0.3..1.0),
+                phase: rng.gen_range(0.0..2.0 * PI),
+                tilt: rng.gen_range(0.0..2.0 * PI),
+                inclination: rng.gen_range(0.0..2.0),
+                size: rng.gen_range(0.15..0.25),
+            })
+            .collect()
+    }
+
+    pub fn position_at(&self, time: f32) -> Vec3 {
+        let angle = time * self.speed + self.phase;
+        let x = angle.sin() * self.radius_x;
+        let y = angle.cos() * self.radius_y;
+
+        Vec3::new(
+            x * self.tilt.cos() - y * self.tilt.sin(),
+            x * self.tilt.sin() + y * self.tilt.cos(),
+            angle.sin() * self.inclination,
+        )
+    }
+
+    // La cola siempre apunta en dirección contraria al Sol (en el origen)
+    pub fn tail_direction(&self, time: f32) -> Vec3 {
+        let position = self.position_at(time);
+        if position.norm() < 1e-4 {
+            Vec3::new(1.0, 0.0, 0.0)
+        } else {
+            position.normalize()
+        }
+    }
+}
@@ -0,0 +1,275 @@
+use minifb::Key;
+
+// Teclas que activan/desactivan opciones. Los manejadores en `main` usan estas constantes,
+// así la ayuda (F1) siempre muestra las mismas teclas que realmente responden.
+// Las descripciones van sin tildes porque la fuente de mapa de bits solo tiene ASCII.
+pub struct KeyBinding {
+    pub key: Key,
+    pub label: &'static str,
+    pub description: &'static str,
+}
+
+pub const HELP: KeyBinding = KeyBinding {
+    key: Key::F1,
+    label: "F1",
+    description: "Mostrar/ocultar esta ayuda",
+};
+
+pub const CAMERA_MODE: KeyBinding = KeyBinding {
+    key: Key::C,
+    label: "C",
+    description: "Camara: nave / vuelo libre / cinematica",
+};
+
+pub const PAINTER: KeyBinding = KeyBinding {
+    key: Key::P,
+    label: "P",
+    description: "Z-buffer / algoritmo del pintor",
+};
+
+pub const CLIPPING: KeyBinding = KeyBinding {
+    key: Key::K,
+    label: "K",
+    description: "Recorte de triangulos contra el frustum",
+};
+
+pub const GOD_RAYS: KeyBinding = KeyBinding {
+    key: Key::G,
+    label: "G",
+    description: "Rayos de luz del Sol",
+};
+
+pub const DEPTH_VIEW: KeyBinding = KeyBinding {
+    key: Key::Z,
+    label: "Z",
+    description: "Z-buffer: recuadro / pantalla completa",
+};
+
+pub const TRAILS: KeyBinding = KeyBinding {
+    key: Key::T,
+    label: "T",
+    description: "Orbitas / estelas",
+};
+
+pub const WATER: KeyBinding = KeyBinding {
+    key: Key::R,
+    label: "R",
+    description: "Plano de agua reflejante",
+};
+
+pub const SUN_HALO: KeyBinding = KeyBinding {
+    key: Key::H,
+    label: "H",
+    description: "Halo del Sol",
+};
+
+pub const TARGET_FPS: KeyBinding = KeyBinding {
+    key: Key::F,
+    label: "F",
+    description: "FPS objetivo (30 / 60 / 120)",
+};
+
+pub const DUMP_STATE: KeyBinding = KeyBinding {
+    key: Key::F2,
+    label: "F2",
+    description: "Imprimir camara y cuerpo enfocado en consola",
+};
+
+pub const EXPORT_SCENE: KeyBinding = KeyBinding {
+    key: Key::J,
+    label: "J",
+    description: "Exportar la escena a scene.json",
+};
+
+pub const LOCK_UP: KeyBinding = KeyBinding {
+    key: Key::L,
+    label: "L",
+    description: "Horizonte fijo al seguir la nave",
+};
+
+pub const GRID: KeyBinding = KeyBinding {
+    key: Key::B,
+    label: "B",
+    description: "Cuadricula de la ecliptica",
+};
+
+pub const TEXTURE: KeyBinding = KeyBinding {
+    key: Key::M,
+    label: "M",
+    description: "Textura del planeta enfocado",
+};
+
+pub const NORMAL_MAP: KeyBinding = KeyBinding {
+    key: Key::N,
+    label: "N",
+    description: "Mapa normal del planeta enfocado",
+};
+
+pub const NOISE_TYPE: KeyBinding = KeyBinding {
+    key: Key::I,
+    label: "I",
+    description: "Cambiar el tipo de ruido del planeta enfocado",
+};
+
+pub const THUMBNAILS: KeyBinding = KeyBinding {
+    key: Key::V,
+    label: "V",
+    description: "Miniaturas de los planetas",
+};
+
+pub const PHOTO_MODE: KeyBinding = KeyBinding {
+    key: Key::F10,
+    label: "F10",
+    description: "Modo foto (solo los cuerpos celestes)",
+};
+
+pub const CHROMATIC_ABERRATION: KeyBinding = KeyBinding {
+    key: Key::X,
+    label: "X",
+    description: "Aberracion cromatica",
+};
+
+pub const FOCUS_ANTIALIASING: KeyBinding = KeyBinding {
+    key: Key::U,
+    label: "U",
+    description: "Antialiasing solo del planeta enfocado",
+};
+
+pub const DEPTH_PEELING: KeyBinding = KeyBinding {
+    key: Key::O,
+    label: "O",
+    description: "Depth peeling de las atmosferas",
+};
+
+pub const ALBEDO: KeyBinding = KeyBinding {
+    key: Key::Y,
+    label: "Y",
+    description: "Solo color base (sin luz ni efectos)",
+};
+
+pub const BOUNDS: KeyBinding = KeyBinding {
+    key: Key::F3,
+    label: "F3",
+    description: "Cajas envolventes (verde = visible)",
+};
+
+pub const SCREENSHOT: KeyBinding = KeyBinding {
+    key: Key::F9,
+    label: "F9",
+    description: "Guardar captura PNG",
+};
+
+pub const BACKFACE_CULLING: KeyBinding = KeyBinding {
+    key: Key::F8,
+    label: "F8",
+    description: "Descartar caras traseras",
+};
+
+pub const OVERDRAW: KeyBinding = KeyBinding {
+    key: Key::F7,
+    label: "F7",
+    description: "Mapa de sobredibujo (azul a rojo)",
+};
+
+pub const CAMERA_PATH: KeyBinding = KeyBinding {
+    key: Key::F4,
+    label: "F4",
+    description: "Reproducir el recorrido de camara",
+};
+
+pub const RETRO_PALETTE: KeyBinding = KeyBinding {
+    key: Key::F5,
+    label: "F5",
+    description: "Paleta retro de 16 colores con tramado",
+};
+
+pub const SCAN_TRANSITION: KeyBinding = KeyBinding {
+    key: Key::F6,
+    label: "F6",
+    description: "Transicion de barrido al cambiar de planeta",
+};
+
+pub const PAUSE: KeyBinding = KeyBinding {
+    key: Key::Space,
+    label: "ESPACIO",
+    description: "Pausar/reanudar la simulacion",
+};
+
+pub const TOGGLES: [&KeyBinding; 31] = [
+    &HELP,
+    &PAUSE,
+    &PHOTO_MODE,
+    &CAMERA_MODE,
+    &CAMERA_PATH,
+    &LOCK_UP,
+    &PAINTER,
+    &CLIPPING,
+    &BACKFACE_CULLING,
+    &FOCUS_ANTIALIASING,
+    &DEPTH_PEELING,
+    &ALBEDO,
+    &GOD_RAYS,
+    &DEPTH_VIEW,
+    &BOUNDS,
+    &OVERDRAW,
+    &TRAILS,
+    &WATER,
+    &SUN_HALO,
+    &CHROMATIC_ABERRATION,
+    &RETRO_PALETTE,
+    &GRID,
+    &TEXTURE,
+    &NORMAL_MAP,
+    &NOISE_TYPE,
+    &THUMBNAILS,
+    &SCAN_TRANSITION,
+    &TARGET_FPS,
+    &DUMP_STATE,
+    &SCREENSHOT,
+    &EXPORT_SCENE,
+];
+
+// Teclas que ajustan un valor en pasos (se repiten al mantenerlas presionadas)
+pub const ORBIT_SPEED_UP: KeyBinding = KeyBinding {
+    key: Key::Equal,
+    label: "+",
+    description: "Acelerar las orbitas",
+};
+
+pub const ORBIT_SPEED_DOWN: KeyBinding = KeyBinding {
+    key: Key::Minus,
+    label: "-",
+    description: "Frenar las orbitas",
+};
+
+pub const TIME_FASTER: KeyBinding = KeyBinding {
+    key: Key::Period,
+    label: ".",
+    description: "Acelerar el tiempo de la simulacion",
+};
+
+pub const TIME_SLOWER: KeyBinding = KeyBinding {
+    key: Key::Comma,
+    label: ",",
+    description: "Frenar el tiempo de la simulacion",
+};
+
+pub const ADJUSTMENTS: [&KeyBinding; 4] = [
+    &ORBIT_SPEED_UP,
+    &ORBIT_SPEED_DOWN,
+    &TIME_FASTER,
+    &TIME_SLOWER,
+];
+
+// Controles que se mantienen presionados (teclas fijas en los manejadores de la nave)
+pub const CONTINUOUS: [(&str, &str); 9] = [
+    ("ESC", "Salir"),
+    ("1-7", "Enfocar un planeta"),
+    ("W / S", "Rotar la nave arriba/abajo"),
+    ("A / D", "Rotar la nave izquierda/derecha"),
+    ("Q / E", "Alabear la nave"),
+    ("FLECHAS", "Avanzar / retroceder"),
+    ("SHIFT", "Mover la nave al 10% (mantener)"),
+    ("CLIC DER.", "Orientar la nave con el mouse"),
+    ("SCROLL", "Zoom de la camara"),
+];
@@ -7,7 +7,19 @@ pub struct Fragment {
     pub depth: f32,
     pub normal: Vec3,
     pub intensity: f32,
-    pub vertex_position: Vec3,
+    // Object-space position (before the model matrix), interpolated across the triangle.
+    // Shaders should sample patterns with it so they stay locked to the body no matter
+    // how it is translated, rotated or scaled.
+    pub object_position: Vec3,
+    // Mesh UVs, interpolated perspective-correctly so textures don't swim on triangles seen
+    // at a steep angle.
+    pub tex_coords: Vec2,
+    // Interpolated world-space directions of +U and +V on the surface; zero when the mesh
+    // has no usable UVs. Together with `normal` they span the tangent space.
+    pub tangent: Vec3,
+    pub bitangent: Vec3,
+    // Index of the face's material in the mesh's .mtl list (0 = default white)
+    pub material: usize,
 }
 
 impl Fragment {
@@ -17,7 +29,7 @@ impl Fragment {
         depth: f32,
         normal: Vec3,
         intensity: f32,
-        vertex_position: Vec3,
+        object_position: Vec3,
     ) -> Self {
         Fragment {
             position,
@@ -25,7 +37,125 @@ impl Fragment {
             depth,
             normal,
             intensity,
-            vertex_position,
+            object_position,
+            tex_coords: Vec2::zeros(),
+            tangent: Vec3::zeros(),
+            bitangent: Vec3::zeros(),
+            material: 0,
+        }
+    }
+
+    pub fn with_tex_coords(mut self, tex_coords: Vec2) -> Self {
+        self.tex_coords = tex_coords;
+        self
+    }
+
+    pub fn with_material(mut self, material: usize) -> Self {
+        self.material = material;
+        self
+    }
+
+    pub fn with_tangents(mut self, tangent: Vec3, bitangent: Vec3) -> Self {
+        self.tangent = tangent;
+        self.bitangent = bitangent;
+        self
+    }
+
+    // Orthonormal (tangent, bitangent, normal) basis at this fragment. Interpolation skews
+    // the vertex tangents, so they are re-orthogonalized against the normal (Gram-Schmidt);
+    // the bitangent keeps the side the UVs put it on. None without a normal or tangents.
+    pub fn tangent_frame(&self) -> Option<(Vec3, Vec3, Vec3)> {
+        let normal = self.normal.try_normalize(f32::EPSILON)?;
+        let tangent = self.tangent - normal * normal.dot(&self.tangent);
+        if tangent.magnitude() < 1e-6 {
+            return None;
+        }
+        let tangent = tangent.normalize();
+
+        let mut bitangent = normal.cross(&tangent);
+        if bitangent.dot(&self.bitangent) < 0.0 {
+            bitangent = -bitangent;
+        }
+
+        Some((tangent, bitangent, normal))
+    }
+
+    // Takes a tangent-space direction (e.g. a normal map texel) to world space. Falls back to
+    // the interpolated normal when the fragment has no tangent frame.
+    pub fn tangent_to_world(&self, direction: &Vec3) -> Vec3 {
+        match self.tangent_frame() {
+            Some((tangent, bitangent, normal)) => {
+                (tangent * direction.x + bitangent * direction.y + normal * direction.z).normalize()
+            }
+            None => self.normal,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::compute_tangents;
+    use crate::triangle::triangle;
+    use crate::vertex::Vertex;
+
+    // Quad in the XY plane with U along +X and V along +Y, drawn head-on on a 100x100 screen.
+    // The corner normals lean different ways, so the interpolated tangents are not
+    // perpendicular to the interpolated normal until `tangent_frame` fixes them
+    fn rasterized_quad() -> Vec<Fragment> {
+        let corner = |x: f32, y: f32, lean: Vec3| {
+            let normal = (Vec3::new(0.0, 0.0, 1.0) + lean).normalize();
+            Vertex::new(Vec3::new(x, y, 0.0), normal, Vec2::new(x, y))
+        };
+        let a = corner(0.0, 0.0, Vec3::new(-0.2, -0.1, 0.0));
+        let b = corner(1.0, 0.0, Vec3::new(0.3, 0.0, 0.0));
+        let c = corner(1.0, 1.0, Vec3::new(0.1, 0.2, 0.0));
+        let d = corner(0.0, 1.0, Vec3::new(-0.1, 0.3, 0.0));
+        let mut vertices = vec![a.clone(), b, c.clone(), a, c, d];
+        compute_tangents(&mut vertices);
+
+        for vertex in &mut vertices {
+            let p = vertex.position;
+            vertex.transformed_position = Vec3::new(10.0 + 80.0 * p.x, 90.0 - 80.0 * p.y, 0.5);
+            vertex.transformed_tangent = vertex.tangent;
+            vertex.transformed_bitangent = vertex.bitangent;
+        }
+
+        vertices
+            .chunks_exact(3)
+            .flat_map(|tri| triangle(&tri[0], &tri[1], &tri[2], 100, 100))
+            .collect()
+    }
+
+    #[test]
+    fn interpolated_tangent_frame_is_orthonormal() {
+        let fragments = rasterized_quad();
+        assert!(!fragments.is_empty());
+
+        for fragment in &fragments {
+            let (tangent, bitangent, normal) = fragment.tangent_frame().unwrap();
+            for axis in [tangent, bitangent, normal] {
+                assert!((axis.magnitude() - 1.0).abs() < 1e-4);
+            }
+            assert!(tangent.dot(&bitangent).abs() < 1e-4);
+            assert!(tangent.dot(&normal).abs() < 1e-4);
+            assert!(bitangent.dot(&normal).abs() < 1e-4);
+            // The UVs still decide the sides: +U toward +X and +V toward +Y
+            assert!(tangent.x > 0.5 && bitangent.y > 0.5);
+        }
+    }
+
+    #[test]
+    fn tilted_normal_map_texel_follows_the_light() {
+        // A texel leaning toward +U, lit from the +X side and then from the -X side
+        let texel = Vec3::new(0.5, 0.0, 0.75).normalize();
+        let toward = Vec3::new(1.0, 0.0, 1.0).normalize();
+        let away = Vec3::new(-1.0, 0.0, 1.0).normalize();
+
+        for fragment in &rasterized_quad() {
+            let normal = fragment.tangent_to_world(&texel);
+            let diffuse = |light: &Vec3| normal.dot(light).max(0.0);
+            assert!(diffuse(&toward) > diffuse(&away) + 0.3);
         }
     }
 }
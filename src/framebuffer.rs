@@ -1,4 +1,15 @@
-use crate::{color::Color, texture::Texture};
+use crate::color::Color;
+use crate::font::{glyph, GLYPH_WIDTH};
+use crate::texture::{Cubemap, Texture};
+use image::{Rgb, RgbImage};
+use nalgebra_glm::{Mat4, Vec2, Vec3, Vec4};
+
+// Cómo se escribe un fragmento que pasa la prueba de profundidad
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlendMode {
+    Opaque,   // Reemplaza el color y la profundidad del píxel
+    Additive, // Suma su color al del píxel sin escribir la profundidad (brillos, colas)
+}
 
 pub struct Framebuffer {
     pub width: usize,
@@ -7,8 +18,17 @@ pub struct Framebuffer {
     pub zbuffer: Vec<f32>, // Asegúrate de incluir el Z-buffer
     pub background_color: Color,
     pub current_color: Color,
+    pub depth_test: bool, // Si es falso se pinta en orden (algoritmo del pintor)
+    pub frustum_clipping: bool, // Recortar los triángulos contra el frustum antes de rasterizar
+    pub overdraw: Option<Vec<u32>>, // Veces que se sombreó cada píxel en el frame (None = no se cuenta)
+    gamma_table: Option<[u8; 256]>, // Codificación de gamma al presentar (None = sin corregir)
+    // Muestras por lado de cada píxel mostrado; `width` y `height` ya están multiplicados
+    samples: usize,
 }
 
+// Sombreados por píxel que ya se ven en rojo puro en el mapa de sobredibujo
+const OVERDRAW_RAMP_MAX: u32 = 8;
+
 impl Framebuffer {
     pub fn new(width: usize, height: usize) -> Self {
         let buffer = vec![Color::new(0, 0, 0); width * height];
@@ -20,19 +40,96 @@ impl Framebuffer {
             height,
             background_color: Color::new(0, 0, 0),
             current_color: Color::new(255, 255, 255),
+            depth_test: true,
+            frustum_clipping: true,
+            overdraw: None,
+            gamma_table: None,
+            samples: 1,
+        }
+    }
+
+    // Supersampling: todo se dibuja (z-buffer, líneas y texto incluidos) a `scale` veces la
+    // resolución en cada eje y `resolve` promedia cada bloque de scale x scale al presentar
+    pub fn new_with_scale(width: usize, height: usize, scale: usize) -> Self {
+        let scale = scale.max(1);
+        let mut framebuffer = Self::new(width * scale, height * scale);
+        framebuffer.samples = scale;
+        framebuffer
+    }
+
+    // Tamaño de la imagen que se muestra, después de `resolve`
+    pub fn output_width(&self) -> usize {
+        self.width / self.samples
+    }
+
+    pub fn output_height(&self) -> usize {
+        self.height / self.samples
+    }
+
+    // Imagen a la resolución de salida: promedio (filtro de caja) de las muestras de cada píxel
+    pub fn resolve(&self) -> Vec<Color> {
+        self.resolve_pixels(&self.buffer)
+    }
+
+    // `resolve` para cualquier imagen con el tamaño interno del framebuffer
+    fn resolve_pixels(&self, pixels: &[Color]) -> Vec<Color> {
+        if self.samples == 1 {
+            return pixels.to_vec();
+        }
+
+        let (width, height) = (self.output_width(), self.output_height());
+        let count = (self.samples * self.samples) as u32;
+        let mut resolved = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let mut sum = [0u32; 3];
+                for sy in 0..self.samples {
+                    let row = (y * self.samples + sy) * self.width + x * self.samples;
+                    for color in &pixels[row..row + self.samples] {
+                        sum[0] += color.r as u32;
+                        sum[1] += color.g as u32;
+                        sum[2] += color.b as u32;
+                    }
+                }
+                resolved.push(Color::new(
+                    ((sum[0] + count / 2) / count) as u8,
+                    ((sum[1] + count / 2) / count) as u8,
+                    ((sum[2] + count / 2) / count) as u8,
+                ));
+            }
         }
+        resolved
     }
 
     pub fn point(&mut self, x: usize, y: usize, depth: f32) {
         if x < self.width && y < self.height {
             let index = y * self.width + x;
-            if depth < self.zbuffer[index] {
+            if !self.depth_test || depth < self.zbuffer[index] {
                 self.buffer[index] = self.current_color;
                 self.zbuffer[index] = depth; // Actualiza el Z-buffer
             }
         }
     }
 
+    // Fragmento con `BlendMode::Additive`: lo de atrás sigue visible a través del brillo, y al
+    // no escribir la profundidad otros brillos pueden sumarse encima sin ordenarlos
+    pub fn add_point(&mut self, x: usize, y: usize, depth: f32, color: Color) {
+        if x < self.width && y < self.height {
+            let index = y * self.width + x;
+            if !self.depth_test || depth < self.zbuffer[index] {
+                self.buffer[index] = self.buffer[index].blend_add(&color);
+            }
+        }
+    }
+
+    pub fn depth_at(&self, x: usize, y: usize) -> f32 {
+        if x < self.width && y < self.height {
+            self.zbuffer[y * self.width + x]
+        } else {
+            f32::INFINITY
+        }
+    }
+
     pub fn point_with_color(&mut self, x: usize, y: usize, color: Color) {
         if x < self.width && y < self.height {
             self.buffer[y * self.width + x] = color;
@@ -69,47 +166,384 @@ impl Framebuffer {
         for depth in &mut self.zbuffer {
             *depth = f32::INFINITY; // Restablecer el Z-buffer
         }
+        if let Some(counts) = &mut self.overdraw {
+            counts.fill(0);
+        }
+    }
+
+    // Activa o desactiva el conteo de fragmentos sombreados por píxel
+    pub fn set_overdraw_counting(&mut self, enabled: bool) {
+        self.overdraw = enabled.then(|| vec![0; self.width * self.height]);
+    }
+
+    // Cuenta un fragmento sombreado en el píxel `index`, pase o no la prueba de profundidad
+    pub fn count_shade(&mut self, index: usize) {
+        if let Some(count) = self
+            .overdraw
+            .as_mut()
+            .and_then(|counts| counts.get_mut(index))
+        {
+            *count += 1;
+        }
+    }
+
+    // Reemplaza la imagen por el conteo de sombreados: negro donde no se sombreó nada y de
+    // azul (una vez) a rojo (`OVERDRAW_RAMP_MAX` o más) pasando por cian, verde y amarillo
+    pub fn show_overdraw(&mut self) {
+        let Some(counts) = &self.overdraw else {
+            return;
+        };
+        let ramp = [
+            Color::new(0, 0, 255),
+            Color::new(0, 255, 255),
+            Color::new(0, 255, 0),
+            Color::new(255, 255, 0),
+            Color::new(255, 0, 0),
+        ];
+
+        for (pixel, &count) in self.buffer.iter_mut().zip(counts) {
+            if count == 0 {
+                *pixel = Color::black();
+                continue;
+            }
+            let t = (count - 1).min(OVERDRAW_RAMP_MAX - 1) as f32 / (OVERDRAW_RAMP_MAX - 1) as f32;
+            let position = t * (ramp.len() - 1) as f32;
+            let stop = (position as usize).min(ramp.len() - 2);
+            *pixel = ramp[stop].lerp(&ramp[stop + 1], position - stop as f32);
+        }
     }
 
     pub fn is_point_set(&self, x: usize, y: usize) -> bool {
         if x < self.width && y < self.height {
-            self.buffer[y * self.width + x] == Color::from_hex(0xFFFFFF)
+            self.buffer[y * self.width + x].to_hex() == 0xFFFFFF
         } else {
             false
         }
     }
 
+    // Guarda la imagen como PNG tal como se ve en la ventana: la fila 0 del buffer es la de
+    // arriba, igual que en la imagen
+    pub fn save_png(&self, path: &str) -> Result<(), image::ImageError> {
+        let pixels = self.to_u32_buffer();
+        let width = self.output_width();
+        let image = RgbImage::from_fn(width as u32, self.output_height() as u32, |x, y| {
+            let [_, r, g, b] = pixels[y as usize * width + x as usize].to_be_bytes();
+            Rgb([r, g, b])
+        });
+        image.save(path)
+    }
+
+    // Con Some(gamma) el buffer se trata como color lineal y cada canal se codifica como
+    // c^(1/gamma) al presentarlo (ventana, PNG y GIF); los shaders y las mezclas no cambian.
+    // 2.2 se aproxima a sRGB
+    pub fn set_gamma(&mut self, gamma: Option<f32>) {
+        self.gamma_table = gamma.filter(|gamma| *gamma > 0.0).map(|gamma| {
+            let mut table = [0u8; 256];
+            for (value, encoded) in table.iter_mut().enumerate() {
+                let linear = value as f32 / 255.0;
+                *encoded = (linear.powf(1.0 / gamma) * 255.0).round() as u8;
+            }
+            table
+        });
+    }
+
+    // Si el buffer guarda color lineal (hay gamma que aplicar al presentar)
+    pub fn is_linear(&self) -> bool {
+        self.gamma_table.is_some()
+    }
+
+    // Colores listos para mostrar (0xRRGGBB) a la resolución de salida, con la gamma
+    // aplicada si está activa
     pub fn to_u32_buffer(&self) -> Vec<u32> {
-        self.buffer.iter().map(|color| color.to_hex()).collect()
+        let resolved = self.resolve();
+        match &self.gamma_table {
+            Some(table) => resolved
+                .iter()
+                .map(|color| {
+                    Color::new(
+                        table[color.r as usize],
+                        table[color.g as usize],
+                        table[color.b as usize],
+                    )
+                    .to_hex()
+                })
+                .collect(),
+            None => resolved.iter().map(|color| color.to_hex()).collect(),
+        }
+    }
+
+    // El z-buffer en escala de grises, listo para mostrar como `to_u32_buffer`. Cada valor se
+    // lleva de profundidad NDC a distancia lineal a la cámara con los planos `near` y `far` de
+    // la proyección, y esas distancias se estiran entre la más cercana (blanco) y la más
+    // lejana (gris oscuro) del frame; los píxeles sin escribir quedan en negro
+    pub fn to_u32_buffer_depth(&self, near: f32, far: f32) -> Vec<u32> {
+        let linearize = |depth: f32| 2.0 * near * far / (far + near - depth * (far - near));
+        let distances: Vec<Option<f32>> = self
+            .zbuffer
+            .iter()
+            .map(|&depth| depth.is_finite().then(|| linearize(depth)))
+            .collect();
+
+        let (closest, farthest) = distances.iter().flatten().fold(
+            (f32::INFINITY, f32::NEG_INFINITY),
+            |(min, max), &distance| (min.min(distance), max.max(distance)),
+        );
+        let range = (farthest - closest).max(1e-6);
+
+        let gray: Vec<Color> = distances
+            .iter()
+            .map(|distance| match distance {
+                Some(distance) => {
+                    let level = 1.0 - 0.85 * (distance - closest) / range;
+                    let value = (level * 255.0).round() as u8;
+                    Color::new(value, value, value)
+                }
+                None => Color::black(),
+            })
+            .collect();
+
+        self.resolve_pixels(&gray)
+            .iter()
+            .map(|color| color.to_hex())
+            .collect()
     }
 
     pub fn draw_line(&mut self, x0: usize, y0: usize, x1: usize, y1: usize, color: u32) {
-        let mut x0 = x0 as isize;
-        let mut y0 = y0 as isize;
-        let x1 = x1 as isize;
-        let y1 = y1 as isize;
+        for (x, y) in bresenham(x0 as isize, y0 as isize, x1 as isize, y1 as isize) {
+            if x >= 0 && x < self.width as isize && y >= 0 && y < self.height as isize {
+                self.point_with_color(x as usize, y as usize, Color::from_hex(color));
+            }
+        }
+    }
+
+    // Segmentos conectados entre puntos consecutivos; con `closed` también une el último con
+    // el primero. Un solo punto dibuja un píxel.
+    pub fn draw_polyline(&mut self, points: &[(usize, usize)], color: u32, closed: bool) {
+        if let [(x, y)] = points {
+            self.draw_line(*x, *y, *x, *y, color);
+            return;
+        }
+
+        for segment in points.windows(2) {
+            let ((x0, y0), (x1, y1)) = (segment[0], segment[1]);
+            self.draw_line(x0, y0, x1, y1, color);
+        }
+
+        if closed && points.len() > 2 {
+            let ((x0, y0), (x1, y1)) = (points[points.len() - 1], points[0]);
+            self.draw_line(x0, y0, x1, y1, color);
+        }
+    }
+
+    // Línea semitransparente con prueba de profundidad: `start` y `end` son (x, y) en píxeles y
+    // z en profundidad NDC. Solo se pinta donde no hay nada más cerca; no escribe el z-buffer.
+    pub fn draw_line_depth(&mut self, start: Vec3, end: Vec3, color: Color, alpha: f32) {
+        let pixels = bresenham(
+            start.x as isize,
+            start.y as isize,
+            end.x as isize,
+            end.y as isize,
+        );
+        let last = (pixels.len() - 1).max(1) as f32;
+
+        for (i, (x, y)) in pixels.into_iter().enumerate() {
+            if x < 0 || y < 0 || x >= self.width as isize || y >= self.height as isize {
+                continue;
+            }
+
+            // La profundidad NDC varía linealmente en el espacio de pantalla
+            let depth = start.z + (end.z - start.z) * (i as f32 / last);
+            let index = y as usize * self.width + x as usize;
+            if depth <= self.zbuffer[index] {
+                self.buffer[index] = self.buffer[index].lerp(&color, alpha);
+            }
+        }
+    }
+
+    // Versión aditiva de `draw_line_depth` (estelas de luz): suma `color` con `add_point` en
+    // cada píxel que pasa la prueba de profundidad
+    pub fn add_line(&mut self, start: Vec3, end: Vec3, color: Color) {
+        let pixels = bresenham(
+            start.x as isize,
+            start.y as isize,
+            end.x as isize,
+            end.y as isize,
+        );
+        let last = (pixels.len() - 1).max(1) as f32;
+
+        for (i, (x, y)) in pixels.into_iter().enumerate() {
+            if x < 0 || y < 0 {
+                continue;
+            }
+            let depth = start.z + (end.z - start.z) * (i as f32 / last);
+            self.add_point(x as usize, y as usize, depth, color);
+        }
+    }
+
+    // Suma `color` a cada píxel de la línea (efectos de luz); acepta extremos fuera de pantalla
+    pub fn draw_line_additive(&mut self, x0: isize, y0: isize, x1: isize, y1: isize, color: Color) {
+        for (x, y) in bresenham(x0, y0, x1, y1) {
+            if x >= 0 && x < self.width as isize && y >= 0 && y < self.height as isize {
+                let index = y as usize * self.width + x as usize;
+                self.buffer[index] = self.buffer[index].blend_add(&color);
+            }
+        }
+    }
 
-        let dx = (x1 - x0).abs();
-        let dy = (y1 - y0).abs();
-        let sx = if x0 < x1 { 1 } else { -1 };
-        let sy = if y0 < y1 { 1 } else { -1 };
-        let mut err = if dx > dy { dx } else { -dy } / 2;
+    // Mezcla `color` con lo que ya hay en el píxel según `alpha` (0 = invisible, 1 = opaco)
+    pub fn blend_pixel(&mut self, x: usize, y: usize, color: Color, alpha: f32) {
+        if x < self.width && y < self.height {
+            let index = y * self.width + x;
+            self.buffer[index] = self.buffer[index].lerp(&color, alpha);
+        }
+    }
+
+    // Dibuja un sprite RGBA centrado en (center_x, center_y) con un lado de `size` píxeles.
+    // Los texeles casi transparentes se descartan y el resto se mezcla con su opacidad.
+    pub fn draw_sprite(
+        &mut self,
+        center_x: f32,
+        center_y: f32,
+        size: f32,
+        texture: &Texture,
+        tint: Color,
+        alpha: f32,
+    ) {
+        const ALPHA_CUTOFF: f32 = 0.02;
+
+        let half = size * 0.5;
+        let min_x = (center_x - half).floor().max(0.0) as usize;
+        let min_y = (center_y - half).floor().max(0.0) as usize;
+        let max_x = ((center_x + half).ceil().max(0.0) as usize).min(self.width);
+        let max_y = ((center_y + half).ceil().max(0.0) as usize).min(self.height);
+
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let u = (x as f32 + 0.5 - (center_x - half)) / size;
+                let v = (y as f32 + 0.5 - (center_y - half)) / size;
+                if !(0.0..=1.0).contains(&u) || !(0.0..=1.0).contains(&v) {
+                    continue;
+                }
+
+                let (texel, texel_alpha) = texture.sample_rgba(u, v);
+                let coverage = texel_alpha * alpha;
+                if coverage < ALPHA_CUTOFF {
+                    continue;
+                }
+                self.blend_pixel(x, y, texel * tint, coverage);
+            }
+        }
+    }
 
-        loop {
-            if x0 >= 0 && x0 < self.width as isize && y0 >= 0 && y0 < self.height as isize {
-                self.point_with_color(x0 as usize, y0 as usize, Color::from_hex(color));
+    // Halo radial sumado a la imagen: intensidad máxima en el centro y cero en `radius`
+    pub fn draw_halo(&mut self, center: Vec2, radius: f32, color: Color, intensity: f32) {
+        if radius <= 0.0 {
+            return;
+        }
+
+        let min_x = (center.x - radius).floor().max(0.0) as usize;
+        let min_y = (center.y - radius).floor().max(0.0) as usize;
+        let max_x = ((center.x + radius).ceil().max(0.0) as usize).min(self.width);
+        let max_y = ((center.y + radius).ceil().max(0.0) as usize).min(self.height);
+
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let dx = x as f32 + 0.5 - center.x;
+                let dy = y as f32 + 0.5 - center.y;
+                let falloff = 1.0 - (dx * dx + dy * dy).sqrt() / radius;
+                if falloff <= 0.0 {
+                    continue;
+                }
+
+                let index = y * self.width + x;
+                let glow = color * (falloff * falloff * intensity);
+                self.buffer[index] = self.buffer[index].blend_add(&glow);
             }
-            if x0 == x1 && y0 == y1 {
-                break;
+        }
+    }
+
+    // Disco suavizado (antialiasing): cada píxel se mezcla según qué fracción de él cubre el
+    // disco, estimada por la distancia de su centro al borde. Un disco de menos de medio
+    // píxel de radio se dibuja como uno de medio píxel más tenue, con la misma área de luz.
+    pub fn draw_disc(&mut self, center: Vec2, radius: f32, color: Color, alpha: f32) {
+        if radius <= 0.0 {
+            return;
+        }
+        let drawn_radius = radius.max(0.5);
+        let alpha = alpha * (radius / drawn_radius).powi(2);
+
+        let reach = drawn_radius + 0.5;
+        let min_x = (center.x - reach).floor().max(0.0) as usize;
+        let min_y = (center.y - reach).floor().max(0.0) as usize;
+        let max_x = ((center.x + reach).ceil().max(0.0) as usize).min(self.width);
+        let max_y = ((center.y + reach).ceil().max(0.0) as usize).min(self.height);
+
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let dx = x as f32 + 0.5 - center.x;
+                let dy = y as f32 + 0.5 - center.y;
+                let coverage = (reach - (dx * dx + dy * dy).sqrt()).clamp(0.0, 1.0);
+                if coverage > 0.0 {
+                    self.blend_pixel(x, y, color, coverage * alpha);
+                }
+            }
+        }
+    }
+
+    pub fn fill_rect_alpha(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        color: Color,
+        alpha: f32,
+    ) {
+        for py in y..(y + height).min(self.height) {
+            for px in x..(x + width).min(self.width) {
+                self.blend_pixel(px, py, color, alpha);
             }
-            let e2 = err;
-            if e2 > -dx {
-                err -= dy;
-                x0 += sx;
+        }
+    }
+
+    // Escribe `text` con la fuente de mapa de bits; cada píxel del glifo ocupa `scale` x `scale`.
+    // No usa el z-buffer: pensado para HUD y overlays.
+    pub fn draw_text(&mut self, x: usize, y: usize, text: &str, color: Color, scale: usize) {
+        for (i, c) in text.chars().enumerate() {
+            let glyph_x = x + i * (GLYPH_WIDTH + 1) * scale;
+            for (row, bits) in glyph(c).iter().enumerate() {
+                for column in 0..GLYPH_WIDTH {
+                    if bits & (1 << (GLYPH_WIDTH - 1 - column)) == 0 {
+                        continue;
+                    }
+                    for sy in 0..scale {
+                        for sx in 0..scale {
+                            let px = glyph_x + column * scale + sx;
+                            let py = y + row * scale + sy;
+                            if px < self.width && py < self.height {
+                                self.buffer[py * self.width + px] = color;
+                            }
+                        }
+                    }
+                }
             }
-            if e2 < dy {
-                err += dx;
-                y0 += sy;
+        }
+    }
+
+    // Mezcla `color` con opacidad `alpha` en cada píxel de la línea; acepta extremos fuera de
+    // pantalla y dibuja solo el tramo visible
+    pub fn draw_line_alpha(
+        &mut self,
+        x0: isize,
+        y0: isize,
+        x1: isize,
+        y1: isize,
+        color: Color,
+        alpha: f32,
+    ) {
+        for (x, y) in bresenham(x0, y0, x1, y1) {
+            if x >= 0 && y >= 0 {
+                self.blend_pixel(x as usize, y as usize, color, alpha);
             }
         }
     }
@@ -129,4 +563,339 @@ impl Framebuffer {
             }
         }
     }
+
+    // Fondo con un cubemap: por cada píxel se reconstruye el rayo de vista en el mundo y se
+    // muestrea el cubemap en esa dirección. Se usa solo la rotación de la vista (el fondo está
+    // infinitamente lejos), así el fondo gira con la cámara pero no se acerca al moverse
+    pub fn draw_skybox_cubemap(
+        &mut self,
+        cubemap: &Cubemap,
+        view_matrix: &Mat4,
+        projection_matrix: &Mat4,
+    ) {
+        let mut rotation = *view_matrix;
+        rotation.fixed_view_mut::<3, 1>(0, 3).fill(0.0);
+        let Some(inverse) = (projection_matrix * rotation).try_inverse() else {
+            return;
+        };
+
+        for y in 0..self.height {
+            // Centro del píxel en coordenadas normalizadas (NDC), con +Y hacia arriba
+            let ndc_y = 1.0 - 2.0 * (y as f32 + 0.5) / self.height as f32;
+            for x in 0..self.width {
+                let ndc_x = 2.0 * (x as f32 + 0.5) / self.width as f32 - 1.0;
+                let near = inverse * Vec4::new(ndc_x, ndc_y, -1.0, 1.0);
+                let far = inverse * Vec4::new(ndc_x, ndc_y, 1.0, 1.0);
+                let direction = far.xyz() / far.w - near.xyz() / near.w;
+
+                // Los cubemaps se arman con +Y hacia arriba y la escena usa +Z (la normal
+                // de la eclíptica): la cara `py` queda sobre el sistema y `pz` hacia -Y
+                let cubemap_direction = Vec3::new(direction.x, direction.z, -direction.y);
+                self.buffer[y * self.width + x] = cubemap.sample(&cubemap_direction);
+            }
+        }
+    }
+
+    // Rayos de luz en espacio de pantalla: desenfoque radial de los píxeles brillantes hacia el Sol
+    pub fn apply_god_rays(&mut self, sun_screen_pos: Vec2, decay: f32, density: f32) {
+        const SAMPLES: usize = 32;
+        const THRESHOLD: f32 = 0.85; // Luminancia mínima para emitir rayos
+        const EXPOSURE: f32 = 0.4;
+
+        let bright: Vec<[f32; 3]> = self
+            .buffer
+            .iter()
+            .map(|color| {
+                let rgb = [
+                    color.r as f32 / 255.0,
+                    color.g as f32 / 255.0,
+                    color.b as f32 / 255.0,
+                ];
+                let luminance = 0.2126 * rgb[0] + 0.7152 * rgb[1] + 0.0722 * rgb[2];
+                if luminance > THRESHOLD {
+                    rgb
+                } else {
+                    [0.0; 3]
+                }
+            })
+            .collect();
+
+        let mut rays = vec![Color::black(); self.buffer.len()];
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let mut position = Vec2::new(x as f32, y as f32);
+                let step = (sun_screen_pos - position) * (density / SAMPLES as f32);
+
+                let mut illumination_decay = 1.0;
+                let mut sum = [0.0f32; 3];
+                for _ in 0..SAMPLES {
+                    position += step;
+                    let sx = position.x as usize;
+                    let sy = position.y as usize;
+                    if position.x >= 0.0 && position.y >= 0.0 && sx < self.width && sy < self.height
+                    {
+                        let sample = bright[sy * self.width + sx];
+                        for c in 0..3 {
+                            sum[c] += sample[c] * illumination_decay;
+                        }
+                    }
+                    illumination_decay *= decay;
+                }
+
+                let scale = EXPOSURE / SAMPLES as f32;
+                rays[y * self.width + x] =
+                    Color::from_float(sum[0] * scale, sum[1] * scale, sum[2] * scale);
+            }
+        }
+
+        // Se compone de forma aditiva sobre la imagen final
+        for (pixel, ray) in self.buffer.iter_mut().zip(rays.iter()) {
+            *pixel = pixel.blend_add(ray);
+        }
+    }
+
+    // Separa los canales hacia los bordes: el rojo se desplaza hacia afuera `strength` píxeles
+    // en las esquinas y el azul la mitad, así los dos se abren y el verde queda en su lugar.
+    // En el centro no se mueve nada
+    pub fn apply_chromatic_aberration(&mut self, strength: f32) {
+        if strength <= 0.0 {
+            return;
+        }
+
+        let source = self.buffer.clone();
+        let center_x = self.width as f32 * 0.5;
+        let center_y = self.height as f32 * 0.5;
+        let half_diagonal = (center_x * center_x + center_y * center_y).sqrt();
+        let max_x = self.width as f32 - 1.0;
+        let max_y = self.height as f32 - 1.0;
+
+        let sample = |x: f32, y: f32| {
+            let x = x.round().clamp(0.0, max_x) as usize;
+            let y = y.round().clamp(0.0, max_y) as usize;
+            source[y * self.width + x]
+        };
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let offset_x = (x as f32 - center_x) / half_diagonal * strength;
+                let offset_y = (y as f32 - center_y) / half_diagonal * strength;
+
+                let red = sample(x as f32 - offset_x, y as f32 - offset_y).r;
+                let blue = sample(x as f32 - offset_x * 0.5, y as f32 - offset_y * 0.5).b;
+                let pixel = &mut self.buffer[y * self.width + x];
+                pixel.r = red;
+                pixel.b = blue;
+            }
+        }
+    }
+
+    // Reduce la imagen a los colores de `palette` con tramado ordenado (matriz de Bayer 4x4):
+    // antes de buscar el color más cercano se suma a cada píxel un umbral que depende solo de
+    // su posición, así los tonos intermedios se aproximan mezclando dos colores vecinos y el
+    // patrón no parpadea entre frames. La distancia es euclidiana ponderada ("redmean"), que
+    // se acerca más a cómo percibimos las diferencias de color que la RGB simple.
+    pub fn quantize_to_palette(&mut self, palette: &[Color]) {
+        const BAYER: [[f32; 4]; 4] = [
+            [0.0, 8.0, 2.0, 10.0],
+            [12.0, 4.0, 14.0, 6.0],
+            [3.0, 11.0, 1.0, 9.0],
+            [15.0, 7.0, 13.0, 5.0],
+        ];
+        // Amplitud del tramado: aproximadamente la separación entre colores de la paleta
+        let spread = 255.0 / (palette.len().max(1) as f32).cbrt();
+
+        let distance = |r: f32, g: f32, b: f32, color: &Color| {
+            let mean_r = (r + color.r as f32) * 0.5;
+            let dr = r - color.r as f32;
+            let dg = g - color.g as f32;
+            let db = b - color.b as f32;
+            (2.0 + mean_r / 256.0) * dr * dr + 4.0 * dg * dg + (3.0 - mean_r / 256.0) * db * db
+        };
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let threshold = ((BAYER[y % 4][x % 4] + 0.5) / 16.0 - 0.5) * spread;
+                let pixel = &mut self.buffer[y * self.width + x];
+                let r = pixel.r as f32 + threshold;
+                let g = pixel.g as f32 + threshold;
+                let b = pixel.b as f32 + threshold;
+
+                let nearest = palette.iter().min_by(|first, second| {
+                    distance(r, g, b, first).total_cmp(&distance(r, g, b, second))
+                });
+                if let Some(nearest) = nearest {
+                    *pixel = *nearest;
+                }
+            }
+        }
+    }
+
+    // Mapa de calor del z-buffer reducido a `width` x `height` (cerca = rojo, lejos = azul)
+    pub fn depth_heatmap(&self, width: usize, height: usize) -> Vec<Color> {
+        let (min_depth, max_depth) = self
+            .zbuffer
+            .iter()
+            .filter(|depth| depth.is_finite())
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &depth| {
+                (min.min(depth), max.max(depth))
+            });
+        let range = (max_depth - min_depth).max(1e-6);
+
+        let near_color = Color::new(255, 0, 0);
+        let mid_color = Color::new(255, 255, 0);
+        let far_color = Color::new(0, 0, 255);
+
+        let mut heatmap = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let source_x = x * self.width / width;
+                let source_y = y * self.height / height;
+                let depth = self.zbuffer[source_y * self.width + source_x];
+
+                let color = if depth.is_finite() {
+                    let t = (depth - min_depth) / range;
+                    if t < 0.5 {
+                        near_color.lerp(&mid_color, t * 2.0)
+                    } else {
+                        mid_color.lerp(&far_color, (t - 0.5) * 2.0)
+                    }
+                } else {
+                    Color::black()
+                };
+                heatmap.push(color);
+            }
+        }
+
+        heatmap
+    }
+
+    // Copia un bloque de píxeles al framebuffer en la posición (x, y)
+    pub fn blit(&mut self, x: usize, y: usize, width: usize, height: usize, pixels: &[Color]) {
+        for j in 0..height {
+            for i in 0..width {
+                self.point_with_color(x + i, y + j, pixels[j * width + i]);
+            }
+        }
+    }
+}
+
+fn bresenham(x0: isize, y0: isize, x1: isize, y1: isize) -> Vec<(isize, isize)> {
+    let mut points = Vec::new();
+    let mut x0 = x0;
+    let mut y0 = y0;
+
+    let dx = (x1 - x0).abs();
+    let dy = (y1 - y0).abs();
+    let sx = if x0 < x1 { 1 } else { -1 };
+    let sy = if y0 < y1 { 1 } else { -1 };
+    let mut err = if dx > dy { dx } else { -dy } / 2;
+
+    loop {
+        points.push((x0, y0));
+        if x0 == x1 && y0 == y1 {
+            break;
+        }
+        let e2 = err;
+        if e2 > -dx {
+            err -= dy;
+            x0 += sx;
+        }
+        if e2 < dy {
+            err += dx;
+            y0 += sy;
+        }
+    }
+
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gamma_brightens_linear_mid_gray() {
+        let mut framebuffer = Framebuffer::new(1, 1);
+        framebuffer.point_with_color(0, 0, Color::new(128, 128, 128));
+        assert_eq!(framebuffer.to_u32_buffer(), vec![0x808080]);
+
+        // (128 / 255)^(1 / 2.2) * 255 = 186.4
+        framebuffer.set_gamma(Some(2.2));
+        assert_eq!(framebuffer.to_u32_buffer(), vec![0xBABABA]);
+
+        // Los extremos no cambian
+        framebuffer.point_with_color(0, 0, Color::new(0, 255, 0));
+        assert_eq!(framebuffer.to_u32_buffer(), vec![0x00FF00]);
+    }
+
+    // Píxeles de un framebuffer negro de 8x8 que pinta la polilínea
+    fn polyline_pixels(points: &[(usize, usize)], closed: bool) -> usize {
+        let mut framebuffer = Framebuffer::new(8, 8);
+        framebuffer.draw_polyline(points, 0xFFFFFF, closed);
+        framebuffer
+            .buffer
+            .iter()
+            .filter(|pixel| pixel.to_hex() == 0xFFFFFF)
+            .count()
+    }
+
+    #[test]
+    fn polyline_with_one_point_draws_a_pixel() {
+        assert_eq!(polyline_pixels(&[(3, 3)], false), 1);
+        assert_eq!(polyline_pixels(&[(3, 3)], true), 1);
+    }
+
+    #[test]
+    fn polyline_with_two_points_draws_one_segment() {
+        assert_eq!(polyline_pixels(&[(0, 0), (4, 0)], false), 5);
+        // Cerrarla no agrega nada: el segmento de vuelta es el mismo
+        assert_eq!(polyline_pixels(&[(0, 0), (4, 0)], true), 5);
+    }
+
+    #[test]
+    fn closed_polyline_joins_the_last_point_to_the_first() {
+        let triangle = [(0, 0), (4, 0), (0, 4)];
+        // Dos lados de 5 píxeles que comparten una esquina
+        assert_eq!(polyline_pixels(&triangle, false), 9);
+        // Tres lados de 5 píxeles que comparten las tres esquinas
+        assert_eq!(polyline_pixels(&triangle, true), 12);
+    }
+
+    // Una fila con el rojo y el azul creciendo de izquierda a derecha
+    fn gradient_row(width: usize) -> Framebuffer {
+        let mut framebuffer = Framebuffer::new(width, 1);
+        for x in 0..width {
+            let value = (x * 10) as u8;
+            framebuffer.point_with_color(x, 0, Color::new(value, 100, value));
+        }
+        framebuffer
+    }
+
+    #[test]
+    fn chromatic_aberration_at_zero_strength_is_a_no_op() {
+        let mut framebuffer = gradient_row(7);
+        let original = framebuffer.buffer.clone();
+        framebuffer.apply_chromatic_aberration(0.0);
+        assert_eq!(framebuffer.buffer, original);
+    }
+
+    #[test]
+    fn chromatic_aberration_moves_red_and_blue_outward() {
+        let mut framebuffer = gradient_row(21);
+        framebuffer.apply_chromatic_aberration(6.0);
+
+        // A la derecha del centro ambos canales vienen de más adentro (valores menores), el
+        // azul de menos lejos que el rojo; el verde no cambia
+        let pixel = framebuffer.buffer[20];
+        assert_eq!((pixel.r, pixel.b), (150, 170));
+        assert_eq!(pixel.g, 100);
+    }
+
+    #[test]
+    fn chromatic_aberration_clamps_samples_at_the_border() {
+        // Con una intensidad enorme el borde izquierdo muestrea más allá del borde derecho
+        let mut framebuffer = gradient_row(4);
+        framebuffer.apply_chromatic_aberration(1000.0);
+        assert_eq!(framebuffer.buffer[0], Color::new(30, 100, 30));
+    }
 }
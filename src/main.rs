@@ -1,46 +1,150 @@
 use fragment::Fragment;
-use minifb::{Key, Window, WindowOptions};
-use nalgebra_glm::{look_at, perspective, Mat4, Vec3, Vec4};
+use minifb::{Key, KeyRepeat, ScaleMode, Window, WindowOptions};
+use nalgebra_glm::{look_at, perspective, Mat4, Vec2, Vec3, Vec4};
+use std::collections::HashMap;
 use std::f32::consts::PI;
-use std::time::{Duration, Instant};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
 mod camera;
+mod clipping;
 mod color;
+mod comet;
+mod controls;
+mod font;
 mod fragment;
 mod framebuffer;
+mod mesh;
 mod obj;
+mod pacing;
+mod particles;
+mod recording;
+mod scene;
 mod shaders;
+mod starfield;
+mod stats;
 mod texture;
+mod trail;
+mod transition;
 mod triangle;
 mod vertex;
+mod warp;
 
-use camera::Camera;
+use camera::{Camera, CameraPath, CameraTransition, CinematicOrbit, Orientation};
+use clipping::clip_triangle;
 use fastnoise_lite::{FastNoiseLite, FractalType, NoiseType};
-use framebuffer::Framebuffer;
+use framebuffer::{BlendMode, Framebuffer};
 use image::{GenericImageView, RgbaImage};
-use obj::Obj;
+use mesh::{Aabb, AABB_EDGES};
+use obj::{Obj, ObjOptions};
 use once_cell::sync::Lazy;
+use pacing::FramePacer;
+use particles::ParticleSystem;
+use rayon::prelude::*;
+use recording::GifRecorder;
+use scene::{
+    Material, Meshes, Scene, SceneConfig, Shader, TransparentShader, DEFAULT_MODEL, ORBIT_SPACING,
+    SUN,
+};
 use shaders::{
-    cellular_shader, cloud_shader, combined_shader, comet_shader, dalmata_shader, earth,
-    fragment_shader, lava_shader, luna_shader, moving_circles_shader, neon_light_shader,
-    neon_normal_map_shader, static_pattern_shader, sun_shader, vertex_shader,
+    atmosphere_shader, comet_shader, earth, luna_shader, ship_shader, sun_shader, vertex_shader,
+    Atmosphere, HeightMap, MaterialTextures, NoiseDisplacement,
 };
+use starfield::Starfield;
+use stats::SessionStats;
 use std::sync::{Arc, Mutex};
-use texture::Texture;
-use triangle::triangle;
+use texture::{ColorSpace, Cubemap, Texture, WrapMode};
+use trail::OrbitTrail;
+use transition::ScanTransition;
+use triangle::{triangle, Winding};
 use vertex::Vertex;
+use warp::WarpIntro;
+
+static FONDO: Lazy<Arc<Texture>> = Lazy::new(|| {
+    let mut texture = Texture::new("assets/images/Galaxy.jpg");
+    // También es el mapa de entorno de la nave: la longitud da la vuelta completa
+    texture.set_wrap_mode(WrapMode::Repeat);
+    Arc::new(texture)
+});
+static GLOW: Lazy<Arc<Texture>> =
+    Lazy::new(|| Arc::new(Texture::new_rgba("assets/images/glow.png")));
+// Luz ambiental tomada del fondo de estrellas (se calcula una sola vez)
+static SKY_AMBIENT: Lazy<color::Color> = Lazy::new(|| sky_ambient(&FONDO));
+
+// Brillo del canal más fuerte de la luz ambiental (0.0 - 1.0)
+const AMBIENT_LEVEL: f32 = 0.18;
+
+// Color promedio del fondo reescalado para que su canal más brillante valga AMBIENT_LEVEL:
+// conserva el tono del cielo (frío en la Vía Láctea) con un brillo fijo y tenue
+fn sky_ambient(sky: &Texture) -> color::Color {
+    let average = sky.average_color(64);
+    let max_channel = average.r.max(average.g).max(average.b).max(1) as f32 / 255.0;
+    let scale = AMBIENT_LEVEL / max_channel / 255.0;
+    color::Color::from_float(
+        average.r as f32 * scale,
+        average.g as f32 * scale,
+        average.b as f32 * scale,
+    )
+}
 
-static FONDO: Lazy<Arc<Texture>> = Lazy::new(|| Arc::new(Texture::new("assets/images/Galaxy.jpg")));
+// Mapa de normales de los paneles del planeta neón; sin él el planeta se dibuja sin relieve
+static NEON_NORMAL: Lazy<Option<Arc<Texture>>> = Lazy::new(|| {
+    match Texture::load_as("assets/images/neon_normal.png", ColorSpace::Linear) {
+        Ok(mut texture) => {
+            // La longitud da la vuelta completa a la esfera
+            texture.set_wrap_mode(WrapMode::Repeat);
+            Some(Arc::new(texture))
+        }
+        Err(err) => {
+            eprintln!(
+                "No se pudo cargar el mapa de normales del planeta neón: {}",
+                err
+            );
+            None
+        }
+    }
+});
+
+// Mapa de alturas de la Luna; si no se puede cargar la Luna queda lisa
+static MOON_HEIGHT: Lazy<Option<Arc<Texture>>> =
+    Lazy::new(
+        || match Texture::load_as("assets/images/moon_height.png", ColorSpace::Linear) {
+            Ok(texture) => Some(Arc::new(texture)),
+            Err(err) => {
+                eprintln!("No se pudo cargar el relieve de la Luna: {}", err);
+                None
+            }
+        },
+    );
 
 pub struct Uniforms {
     model_matrix: Mat4,
     view_matrix: Mat4,
     projection_matrix: Mat4,
     viewport_matrix: Mat4,
-    time: u32,
-    noise: FastNoiseLite,
+    camera_position: Vec3,
+    sun_position: Vec3, // Los cuerpos se iluminan desde aquí (ver `shaders::phong`)
+    material: Material,
+    // Semilla global (`--seed`) que mezclan los shaders aleatorios; ninguna escena los usa
+    // todavía
+    #[allow(dead_code)]
+    seed: u64,
+    time: f32, // Segundos de simulación desde el inicio, continuos para animar sin saltos
+    ambient: color::Color, // Luz del entorno que reciben las caras sin sol
+    noise: Arc<FastNoiseLite>,
+    displacement: Option<NoiseDisplacement>,
+    height_map: Option<HeightMap>,
+    normal_map: Option<Arc<Texture>>, // Normales en espacio tangente (solo el planeta neón)
+    material_textures: Option<MaterialTextures>, // Texturas del .mtl de la malla (`material_shader`)
+    environment: Option<Arc<Texture>>,           // Mapa de entorno para los reflejos (solo la nave)
+    atmosphere: Option<Atmosphere>, // Capa de gas que se dibuja en la pasada transparente
+    albedo: bool,                   // Color base sin iluminación (modo de depuración)
+    linear_textures: bool,          // Decodificar las texturas sRGB antes de iluminarlas
+    cull_backfaces: bool,           // Descartar los triángulos que dan la espalda a la cámara
+    front_face: Winding,            // Orden de los vértices de las caras frontales de la malla
 }
 
+// Ruido inicial de cada cuerpo (7 = Luna y cometas); la escena los guarda y los reconstruye
+// solo cuando cambian
 fn create_noise_for_planet(index: usize) -> FastNoiseLite {
     match index {
         0 => create_lava_noise(),
@@ -50,7 +154,7 @@ fn create_noise_for_planet(index: usize) -> FastNoiseLite {
         4 => create_cloud_noise(),
         5 => create_combined_noise(),
         6 => create_cloud_noise(),
-        7 => create_cloud_noise(), // noise para la luna y cometa
+        7 => create_cloud_noise(), // noise para la luna y los cometas
         _ => create_noise(),       // Por defecto
     }
 }
@@ -163,12 +267,148 @@ fn create_model_matrix(translation: Vec3, scale: f32, rotation: Vec3) -> Mat4 {
     translation_matrix * rotation_matrix * scale_matrix
 }
 
-fn render(
-    framebuffer: &mut Framebuffer,
+// Matriz para una esfera estirada a lo largo de `direction`, empezando en `origin`
+fn create_tail_model_matrix(origin: Vec3, direction: Vec3, length: f32, width: f32) -> Mat4 {
+    let half_length = length * 0.5;
+    let rotation = nalgebra_glm::quat_to_mat4(&nalgebra_glm::quat_rotation(
+        &Vec3::new(1.0, 0.0, 0.0),
+        &direction,
+    ));
+    nalgebra_glm::translation(&(origin + direction * half_length))
+        * rotation
+        * nalgebra_glm::scaling(&Vec3::new(half_length, width, width))
+}
+
+// Semilla global de la aleatoriedad (`--seed N`); la misma semilla reproduce la misma escena
+const DEFAULT_SEED: u64 = 2024;
+
+// Cometas que se generan al iniciar (`--comets N`)
+const DEFAULT_COMET_COUNT: usize = 3;
+
+// Pasos de simulación por frame (`--substeps N`); más pasos suavizan el movimiento cuando el
+// tiempo entre frames es grande
+const DEFAULT_SUBSTEPS: u32 = 1;
+
+// Relación ancho/alto de cada píxel físico (`--pixel-aspect X`); 1.0 para píxeles cuadrados
+const DEFAULT_PIXEL_ASPECT: f32 = 1.0;
+
+// Radio en píxeles de las estrellas más brillantes del fondo (`--star-size X`)
+const DEFAULT_STAR_SIZE: f32 = 1.5;
+
+// Fracción del tamaño de la ventana a la que se renderiza (`--render-scale X`)
+const DEFAULT_RENDER_SCALE: f32 = 1.0;
+
+// FPS objetivo por defecto (`--target-fps N`) y los valores que se recorren con la tecla
+const DEFAULT_TARGET_FPS: u32 = 60;
+const TARGET_FPS_OPTIONS: [u32; 3] = [30, 60, 120];
+
+// Paleta EGA de 16 colores para el modo retro (F5); `--palette` carga otra
+const EGA_PALETTE: [u32; 16] = [
+    0x000000, 0x0000AA, 0x00AA00, 0x00AAAA, 0xAA0000, 0xAA00AA, 0xAA5500, 0xAAAAAA, 0x555555,
+    0x5555FF, 0x55FF55, 0x55FFFF, 0xFF5555, 0xFF55FF, 0xFFFF55, 0xFFFFFF,
+];
+
+// Paleta desde un JSON con una lista de colores "#RRGGBB"
+fn load_palette(path: &str) -> std::io::Result<Vec<color::Color>> {
+    let entries: Vec<String> = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    let invalid = |message: String| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+
+    let palette = entries
+        .iter()
+        .map(|entry| {
+            u32::from_str_radix(entry.trim_start_matches('#'), 16)
+                .map(color::Color::from_hex)
+                .map_err(|_| invalid(format!("color inválido: {}", entry)))
+        })
+        .collect::<std::io::Result<Vec<_>>>()?;
+    if palette.is_empty() {
+        return Err(invalid("la paleta no tiene colores".to_string()));
+    }
+    Ok(palette)
+}
+
+// Recorrido de cámara que se reproduce con F4 (`--camera-path` carga otro)
+const DEFAULT_CAMERA_PATH: &str = "assets/camera_path.json";
+
+// Valores por defecto de `--frames` y `--fps` al grabar un GIF (20 fps = 5 centésimas por
+// frame, la unidad de los retardos de GIF)
+const DEFAULT_GIF_FRAMES: u32 = 120;
+const DEFAULT_GIF_FPS: u32 = 20;
+
+// Valor de `--name valor` en la línea de comandos; None si no se pasó. Un valor inválido
+// termina el programa con un mensaje que describe lo que se esperaba.
+fn parse_arg<T: std::str::FromStr>(name: &str, expected: &str) -> Option<T> {
+    let args: Vec<String> = std::env::args().collect();
+    let index = args.iter().position(|arg| arg == name)?;
+
+    match args.get(index + 1).map(|value| value.parse::<T>()) {
+        Some(Ok(value)) => Some(value),
+        _ => {
+            eprintln!("{} necesita {}", name, expected);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn load_obj(path: &str, options: ObjOptions) -> Obj {
+    match Obj::load_with_options(path, options) {
+        Ok(obj) => obj,
+        Err(err) => {
+            eprintln!("No se pudo cargar {}: {}", path, err);
+            std::process::exit(1);
+        }
+    }
+}
+
+// Modelos propios del proyecto, que ya traen sus normales
+fn load_vertex_array(path: &str) -> Vec<Vertex> {
+    load_obj(path, ObjOptions::default()).get_vertex_array()
+}
+
+// Malla y texturas de sus materiales de un modelo de la escena; una textura que no se puede
+// abrir se omite y el material queda solo con su color difuso. Los OBJ de la escena pueden
+// venir de cualquier exportador: si no traen `vn` reciben normales suaves
+fn load_model(path: &str) -> (Vec<Vertex>, MaterialTextures) {
+    let options = ObjOptions {
+        smooth_normals: true,
+    };
+    let obj = load_obj(path, options);
+    let textures = obj
+        .materials()
+        .iter()
+        .map(|material| {
+            let texture_path = material.diffuse_texture.as_ref()?;
+            match Texture::load(texture_path) {
+                Ok(mut texture) => {
+                    texture.set_wrap_mode(WrapMode::Repeat);
+                    Some(Arc::new(texture))
+                }
+                Err(err) => {
+                    eprintln!("No se pudo cargar la textura {}: {}", texture_path, err);
+                    None
+                }
+            }
+        })
+        .collect();
+    (obj.get_vertex_array(), textures)
+}
+
+// Fondo del frame: el cubemap gira con la cámara; la imagen plana queda fija en la pantalla
+fn draw_background(framebuffer: &mut Framebuffer, skybox: Option<&Cubemap>, frame: &FrameContext) {
+    match skybox {
+        Some(cubemap) => {
+            framebuffer.draw_skybox_cubemap(cubemap, &frame.view_matrix, &frame.projection_matrix)
+        }
+        None => framebuffer.draw_skybox(&FONDO),
+    }
+}
+
+// Vertex shader + ensamblado de primitivas (con recorte opcional contra el frustum)
+fn assemble_triangles(
+    framebuffer: &Framebuffer,
     uniforms: &Uniforms,
     vertex_array: &[Vertex],
-    shader: fn(&Fragment, &Uniforms) -> color::Color,
-) {
+) -> Vec<[Vertex; 3]> {
     // Vertex Shader Stage
     let mut transformed_vertices = Vec::with_capacity(vertex_array.len());
     for vertex in vertex_array {
@@ -176,32 +416,287 @@ fn render(
         transformed_vertices.push(transformed);
     }
 
+    // Una matriz que refleja el espacio (p. ej. la del agua) invierte el orden de los vértices
+    let model_view = uniforms.view_matrix * uniforms.model_matrix;
+    let front_face = if model_view.determinant() < 0.0 {
+        uniforms.front_face.flipped()
+    } else {
+        uniforms.front_face
+    };
+
     // Primitive Assembly Stage
     let mut triangles = Vec::new();
     for i in (0..transformed_vertices.len()).step_by(3) {
         if i + 2 < transformed_vertices.len() {
-            triangles.push([
+            let triangle = [
                 transformed_vertices[i].clone(),
                 transformed_vertices[i + 1].clone(),
                 transformed_vertices[i + 2].clone(),
-            ]);
+            ];
+
+            // Con el recorte solo se rasterizan las partes dentro del frustum; sin él se
+            // depende de descartar píxeles fuera de pantalla uno por uno, y los triángulos
+            // con un vértice detrás de la cámara se descartan enteros
+            let mut assembled = Vec::new();
+            if framebuffer.frustum_clipping {
+                assembled.extend(clip_triangle(&triangle, &uniforms.viewport_matrix));
+            } else if triangle.iter().all(Vertex::is_projectable) {
+                assembled.push(triangle);
+            }
+
+            // Las caras traseras se descartan después del recorte, cuando las posiciones en
+            // pantalla ya son válidas (el recorte conserva el orden de los vértices)
+            triangles.extend(assembled.into_iter().filter(|[v1, v2, v3]| {
+                !uniforms.cull_backfaces || front_face.is_front_facing(v1, v2, v3)
+            }));
         }
     }
 
+    stats::count_triangles(triangles.len());
+    triangles
+}
+
+// Orden en que se dibujan las mallas centradas en `centers`. Sin z-buffer van de atrás hacia
+// adelante según la profundidad de su centro en espacio de vista, así una más lejana no tapa
+// a otra más cercana (dentro de cada malla ordena `render`); con z-buffer, en orden
+fn draw_order(framebuffer: &Framebuffer, view_matrix: &Mat4, centers: &[Vec3]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..centers.len()).collect();
+    if !framebuffer.depth_test {
+        let view_depth = |i: &usize| (view_matrix * centers[*i].push(1.0)).z;
+        order.sort_by(|a, b| view_depth(a).total_cmp(&view_depth(b)));
+    }
+    order
+}
+
+// Mínimo de fragmentos por tarea al sombrear en paralelo; con menos, repartirlos entre
+// hilos cuesta más que sombrearlos
+const SHADING_CHUNK: usize = 256;
+
+fn render(
+    framebuffer: &mut Framebuffer,
+    uniforms: &Uniforms,
+    vertex_array: &[Vertex],
+    shader: fn(&Fragment, &Uniforms) -> color::Color,
+    blend_mode: BlendMode,
+) {
+    let mut triangles = assemble_triangles(framebuffer, uniforms, vertex_array);
+
+    // Sin z-buffer se ordenan los triángulos de atrás hacia adelante (algoritmo del pintor)
+    if !framebuffer.depth_test {
+        let model_view = uniforms.view_matrix * uniforms.model_matrix;
+        let view_depth = |tri: &[Vertex; 3]| {
+            tri.iter()
+                .map(|v| (model_view * Vec4::new(v.position.x, v.position.y, v.position.z, 1.0)).z)
+                .sum::<f32>()
+                / 3.0
+        };
+        // En espacio de vista la cámara mira hacia -Z: lo más lejano tiene la Z más negativa
+        triangles.sort_by(|a, b| view_depth(a).total_cmp(&view_depth(b)));
+    }
+
     // Rasterization Stage
     let mut fragments = Vec::new();
     for tri in &triangles {
-        fragments.extend(triangle(&tri[0], &tri[1], &tri[2]));
+        fragments.extend(triangle(
+            &tri[0],
+            &tri[1],
+            &tri[2],
+            framebuffer.width,
+            framebuffer.height,
+        ));
+    }
+
+    // Modo albedo: luz plena para que el shader deje su color base tal cual
+    if uniforms.albedo {
+        for fragment in &mut fragments {
+            fragment.intensity = 1.0;
+        }
     }
 
-    // Fragment Processing Stage
-    for fragment in fragments {
+    // Fragment Processing Stage: `triangle` ya descarta lo que cae fuera de la pantalla. Los
+    // fragmentos se sombrean en paralelo y después se escriben en orden, así el z-buffer
+    // queda igual que en serie
+    let colors: Vec<u32> = fragments
+        .par_iter()
+        .with_min_len(SHADING_CHUNK)
+        .map(|fragment| shader(fragment, uniforms).to_hex())
+        .collect();
+
+    for (fragment, color) in fragments.iter().zip(colors) {
         let x = fragment.position.x as usize;
         let y = fragment.position.y as usize;
-        if x < framebuffer.width && y < framebuffer.height {
-            let shaded_color = shader(&fragment, uniforms);
-            let color = shaded_color.to_hex();
-            framebuffer.set_current_color(color);
+        framebuffer.count_shade(y * framebuffer.width + x);
+        match blend_mode {
+            BlendMode::Opaque => {
+                framebuffer.set_current_color(color);
+                framebuffer.point(x, y, fragment.depth);
+            }
+            BlendMode::Additive => {
+                framebuffer.add_point(x, y, fragment.depth, color::Color::from_hex(color))
+            }
+        }
+    }
+}
+
+// Objeto semitransparente a dibujar en la pasada transparente
+struct TransparentDraw<'a> {
+    uniforms: Uniforms,
+    vertices: &'a [Vertex],
+    shader: TransparentShader,
+}
+
+// Fragmentos transparentes visibles (delante de lo opaco) de todos los objetos, con su índice
+// de píxel. No escribe el z-buffer.
+fn transparent_fragments<'a>(
+    framebuffer: &'a Framebuffer,
+    draws: &'a [TransparentDraw],
+) -> impl Iterator<Item = (usize, Fragment, &'a TransparentDraw<'a>)> + 'a {
+    draws.iter().flat_map(move |draw| {
+        assemble_triangles(framebuffer, &draw.uniforms, draw.vertices)
+            .into_iter()
+            .flat_map(|tri| {
+                triangle(
+                    &tri[0],
+                    &tri[1],
+                    &tri[2],
+                    framebuffer.width,
+                    framebuffer.height,
+                )
+            })
+            .filter_map(move |fragment| {
+                let x = fragment.position.x as usize;
+                let y = fragment.position.y as usize;
+                let index = y * framebuffer.width + x;
+                (fragment.depth < framebuffer.depth_at(x, y)).then_some((index, fragment, draw))
+            })
+    })
+}
+
+// Dibuja objetos semitransparentes. Con `depth_peeling` se separan las dos capas más cercanas
+// de cada píxel (dos pasadas de rasterización) y se componen de atrás hacia adelante, así el
+// resultado no depende del orden de `draws`. Sin él se mezcla cada fragmento en orden de dibujo.
+fn render_transparent(
+    framebuffer: &mut Framebuffer,
+    draws: &[TransparentDraw],
+    depth_peeling: bool,
+) {
+    if !depth_peeling {
+        let blended: Vec<(usize, color::Color, f32)> = transparent_fragments(framebuffer, draws)
+            .map(|(index, fragment, draw)| {
+                let (color, alpha) = (draw.shader)(&fragment, &draw.uniforms);
+                (index, color, alpha)
+            })
+            .collect();
+        for (index, color, alpha) in blended {
+            framebuffer.count_shade(index);
+            framebuffer.buffer[index] = framebuffer.buffer[index].lerp(&color, alpha);
+        }
+        return;
+    }
+
+    let pixel_count = framebuffer.width * framebuffer.height;
+    let transparent = (color::Color::black(), 0.0);
+
+    // Píxeles de cada fragmento sombreado, para el mapa de sobredibujo
+    let mut shaded = Vec::new();
+
+    // Primera capa: el fragmento transparente más cercano de cada píxel
+    let mut front_depth = vec![f32::INFINITY; pixel_count];
+    let mut front_color = vec![transparent; pixel_count];
+    for (index, fragment, draw) in transparent_fragments(framebuffer, draws) {
+        if fragment.depth < front_depth[index] {
+            front_depth[index] = fragment.depth;
+            front_color[index] = (draw.shader)(&fragment, &draw.uniforms);
+            shaded.push(index);
+        }
+    }
+
+    // Segunda capa: el más cercano que quede detrás de la primera
+    let mut back_depth = vec![f32::INFINITY; pixel_count];
+    let mut back_color = vec![transparent; pixel_count];
+    for (index, fragment, draw) in transparent_fragments(framebuffer, draws) {
+        if fragment.depth > front_depth[index] + 1e-6 && fragment.depth < back_depth[index] {
+            back_depth[index] = fragment.depth;
+            back_color[index] = (draw.shader)(&fragment, &draw.uniforms);
+            shaded.push(index);
+        }
+    }
+
+    if framebuffer.overdraw.is_some() {
+        for index in shaded {
+            framebuffer.count_shade(index);
+        }
+    }
+
+    for index in 0..pixel_count {
+        let (back, back_alpha) = back_color[index];
+        let (front, front_alpha) = front_color[index];
+        let pixel = framebuffer.buffer[index].lerp(&back, back_alpha);
+        framebuffer.buffer[index] = pixel.lerp(&front, front_alpha);
+    }
+}
+
+// Matriz que refleja el espacio respecto al plano horizontal z = height
+fn create_reflection_matrix(height: f32) -> Mat4 {
+    nalgebra_glm::translation(&Vec3::new(0.0, 0.0, height))
+        * nalgebra_glm::scaling(&Vec3::new(1.0, 1.0, -1.0))
+        * nalgebra_glm::translation(&Vec3::new(0.0, 0.0, -height))
+}
+
+// Cuadrado de lado 2 * half_size sobre el plano z = height (dos triángulos)
+fn create_plane_vertices(height: f32, half_size: f32) -> Vec<Vertex> {
+    let normal = Vec3::new(0.0, 0.0, 1.0);
+    let corner = |x: f32, y: f32| {
+        Vertex::new(
+            Vec3::new(x * half_size, y * half_size, height),
+            normal,
+            Vec2::new((x + 1.0) * 0.5, (y + 1.0) * 0.5),
+        )
+    };
+
+    vec![
+        corner(-1.0, -1.0),
+        corner(1.0, -1.0),
+        corner(1.0, 1.0),
+        corner(-1.0, -1.0),
+        corner(1.0, 1.0),
+        corner(-1.0, 1.0),
+    ]
+}
+
+// Dibuja el plano mezclando su color base con la reflexión ya renderizada en `reflection`.
+// La reflexión solo aparece en los píxeles que cubre el plano.
+fn render_reflective_plane(
+    framebuffer: &mut Framebuffer,
+    uniforms: &Uniforms,
+    plane_vertices: &[Vertex],
+    reflection: &Framebuffer,
+    base_color: color::Color,
+    reflectivity: f32,
+) {
+    let transformed: Vec<Vertex> = plane_vertices
+        .iter()
+        .map(|vertex| vertex_shader(vertex, uniforms))
+        .collect();
+
+    for tri in transformed.chunks_exact(3) {
+        for fragment in triangle(
+            &tri[0],
+            &tri[1],
+            &tri[2],
+            framebuffer.width,
+            framebuffer.height,
+        ) {
+            let x = fragment.position.x as usize;
+            let y = fragment.position.y as usize;
+            let index = y * reflection.width + x;
+            let color = if reflection.zbuffer[index].is_finite() {
+                base_color.lerp(&reflection.buffer[index], reflectivity)
+            } else {
+                base_color
+            };
+
+            framebuffer.set_current_color(color.to_hex());
             framebuffer.point(x, y, fragment.depth);
         }
     }
@@ -211,13 +706,17 @@ fn create_view_matrix(eye: Vec3, center: Vec3, up: Vec3) -> Mat4 {
     look_at(&eye, &center, &up)
 }
 
-fn create_perspective_matrix(window_width: f32, window_height: f32) -> Mat4 {
-    let fov = 45.0 * PI / 180.0;
-    let aspect_ratio = window_width / window_height;
-    let near = 0.1;
-    let far = 1000.0;
-
-    perspective(fov, aspect_ratio, near, far)
+const NEAR_PLANE: f32 = 0.1;
+const FAR_PLANE: f32 = 1000.0;
+const FIELD_OF_VIEW: f32 = 45.0 * PI / 180.0; // Vertical, en radianes
+
+// Recibe el tamaño del framebuffer (no el de la ventana): la imagen se calcula a esa
+// resolución y al presentarla se escala a la ventana conservando la proporción.
+// `pixel_aspect` corrige pantallas con píxeles no cuadrados: el ancho físico de la imagen es
+// width * pixel_aspect. Todo (culling, proyección de puntos) usa esta misma matriz.
+fn create_perspective_matrix(width: f32, height: f32, pixel_aspect: f32) -> Mat4 {
+    let aspect_ratio = width * pixel_aspect / height;
+    perspective(FIELD_OF_VIEW, aspect_ratio, NEAR_PLANE, FAR_PLANE)
 }
 
 fn create_viewport_matrix(width: f32, height: f32) -> Mat4 {
@@ -241,19 +740,72 @@ fn create_viewport_matrix(width: f32, height: f32) -> Mat4 {
     )
 }
 
-fn is_visible(position: &Vec3, view_matrix: &Mat4, projection_matrix: &Mat4) -> bool {
-    // Transformar la posición del objeto al espacio de clip
-    let clip_space_position =
-        projection_matrix * view_matrix * Vec4::new(position.x, position.y, position.z, 1.0);
-    let w = clip_space_position.w;
+// Prueba de la esfera envolvente (centro y radio en el mundo) contra los seis planos del
+// frustum, extraídos de las filas de la matriz de proyección por vista. Así un cuerpo grande
+// con el centro justo fuera de la pantalla sigue dibujándose mientras se vea una parte.
+fn is_visible(position: &Vec3, radius: f32, view_matrix: &Mat4, projection_matrix: &Mat4) -> bool {
+    let clip_matrix = projection_matrix * view_matrix;
+    let row = |i: usize| clip_matrix.row(i).transpose();
+    let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+    let center = Vec4::new(position.x, position.y, position.z, 1.0);
+
+    // Izquierdo, derecho, abajo, arriba, cercano y lejano: -w <= x, y, z <= w
+    [w + x, w - x, w + y, w - y, w + z, w - z]
+        .iter()
+        .all(|plane| {
+            let normal_length = plane.xyz().magnitude();
+            normal_length <= f32::EPSILON || plane.dot(&center) / normal_length >= -radius
+        })
+}
+
+// Proyecta un punto del mundo a píxeles (x, y) junto con su profundidad NDC (z), la misma
+// que guarda el z-buffer. Devuelve None si el punto está detrás de la cámara.
+fn project_point(
+    position: &Vec3,
+    view_matrix: &Mat4,
+    projection_matrix: &Mat4,
+    width: f32,
+    height: f32,
+) -> Option<Vec3> {
+    let clip = projection_matrix * view_matrix * Vec4::new(position.x, position.y, position.z, 1.0);
+    if clip.w <= 0.0 {
+        return None;
+    }
+
+    let x_ndc = clip.x / clip.w;
+    let y_ndc = clip.y / clip.w;
+    Some(Vec3::new(
+        (x_ndc + 1.0) * width * 0.5,
+        (1.0 - y_ndc) * height * 0.5,
+        clip.z / clip.w,
+    ))
+}
+
+fn project_to_screen(
+    position: &Vec3,
+    view_matrix: &Mat4,
+    projection_matrix: &Mat4,
+    width: f32,
+    height: f32,
+) -> Option<Vec2> {
+    let screen = project_point(position, view_matrix, projection_matrix, width, height)?;
+    if !(0.0..=width).contains(&screen.x) || !(0.0..=height).contains(&screen.y) {
+        return None;
+    }
 
-    // Normalizar las coordenadas (clip space -> NDC)
-    let x_ndc = clip_space_position.x / w;
-    let y_ndc = clip_space_position.y / w;
-    let z_ndc = clip_space_position.z / w;
+    Some(Vec2::new(screen.x, screen.y))
+}
 
-    // Verificar si está dentro del rango [-1, 1]
-    x_ndc >= -1.0 && x_ndc <= 1.0 && y_ndc >= -1.0 && y_ndc <= 1.0 && z_ndc >= -1.0 && z_ndc <= 1.0
+// "Arriba" de la cámara alineado con la normal de la eclíptica (+Z) y perpendicular a la
+// dirección de vista. Si se mira justo a lo largo de +Z se conserva `previous_up`.
+fn level_up_vector(view_direction: &Vec3, previous_up: &Vec3) -> Vec3 {
+    let world_up = Vec3::new(0.0, 0.0, 1.0);
+    let up = world_up - view_direction * view_direction.dot(&world_up);
+    if up.norm() < 1e-3 {
+        *previous_up
+    } else {
+        up.normalize()
+    }
 }
 
 fn check_collision(position: &Vec3, planet_position: &Vec3, planet_radius: f32) -> bool {
@@ -269,476 +821,2349 @@ fn render_orbit(
     view_matrix: &Mat4,
     projection_matrix: &Mat4,
 ) {
-    let mut points = Vec::new();
-    for i in 0..segments {
-        let angle = 2.0 * PI * i as f32 / segments as f32;
-        let x = center.x + radius * angle.cos();
-        let y = center.y + radius * angle.sin();
-        points.push(Vec3::new(x, y, center.z));
-    }
-
-    for i in 0..segments {
-        let start = points[i];
-        let end = points[(i + 1) % segments];
-        let start_ndc = projection_matrix * view_matrix * Vec4::new(start.x, start.y, start.z, 1.0);
-        let end_ndc = projection_matrix * view_matrix * Vec4::new(end.x, end.y, end.z, 1.0);
-
-        let start_ndc = start_ndc / start_ndc.w;
-        let end_ndc = end_ndc / end_ndc.w;
-
-        framebuffer.draw_line(
-            ((start_ndc.x + 1.0) * framebuffer.width as f32 * 0.5) as usize,
-            ((1.0 - start_ndc.y) * framebuffer.height as f32 * 0.5) as usize,
-            ((end_ndc.x + 1.0) * framebuffer.width as f32 * 0.5) as usize,
-            ((1.0 - end_ndc.y) * framebuffer.height as f32 * 0.5) as usize,
-            0xFFFFFF, // Color blanco para las órbitas
-        );
+    // None para los puntos detrás del plano cercano: al dividir entre w quedarían reflejados
+    let points: Vec<Option<(usize, usize)>> = (0..segments)
+        .map(|i| {
+            let angle = 2.0 * PI * i as f32 / segments as f32;
+            let x = center.x + radius * angle.cos();
+            let y = center.y + radius * angle.sin();
+
+            let clip = projection_matrix * view_matrix * Vec4::new(x, y, center.z, 1.0);
+            if clip.w <= NEAR_PLANE {
+                return None;
+            }
+
+            let ndc = clip / clip.w;
+            Some((
+                ((ndc.x + 1.0) * framebuffer.width as f32 * 0.5) as usize,
+                ((1.0 - ndc.y) * framebuffer.height as f32 * 0.5) as usize,
+            ))
+        })
+        .collect();
+
+    let color = 0xFFFFFF; // Color blanco para las órbitas
+    let Some(first_hidden) = points.iter().position(|point| point.is_none()) else {
+        let points: Vec<(usize, usize)> = points.into_iter().flatten().collect();
+        framebuffer.draw_polyline(&points, color, true);
+        return;
+    };
+
+    // Se recorre el anillo empezando después de un punto oculto y se dibuja cada tramo visible
+    let mut run = Vec::new();
+    for k in 1..=segments {
+        match points[(first_hidden + k) % segments] {
+            Some(point) => run.push(point),
+            None => {
+                if run.len() > 1 {
+                    framebuffer.draw_polyline(&run, color, false);
+                }
+                run.clear();
+            }
+        }
     }
 }
 
-fn main() {
-    let window_width = 1300;
-    let window_height = 600;
-    let framebuffer_width = 1300;
-    let framebuffer_height = 600;
-    let frame_delay = Duration::from_millis(16);
+// Cuadrícula sobre el plano de la eclíptica (z = 0), centrada en el Sol
+#[derive(Clone, Copy)]
+struct EclipticGrid {
+    spacing: f32,
+    extent: f32, // Distancia del centro al borde de la cuadrícula
+    color: color::Color,
+    alpha: f32,
+}
 
-    // Crear el framebuffer
-    let mut framebuffer = Framebuffer::new(framebuffer_width, framebuffer_height);
+fn render_ecliptic_grid(framebuffer: &mut Framebuffer, grid: &EclipticGrid, frame: &FrameContext) {
+    let width = framebuffer.width as f32;
+    let height = framebuffer.height as f32;
+    let lines = (grid.extent / grid.spacing).floor() as i32;
+    let extent = lines as f32 * grid.spacing;
+    // Tope en pantalla para no recorrer líneas enormes que caen muy lejos de la ventana
+    let max_coordinate = 4.0 * width.max(height);
+
+    // Cada línea se parte en tramos del tamaño de una celda para desvanecerla con la distancia
+    for line in -lines..=lines {
+        let offset = line as f32 * grid.spacing;
+        for step in -lines..lines {
+            let from = step as f32 * grid.spacing;
+            let to = from + grid.spacing;
+
+            for (start, end) in [
+                (Vec3::new(offset, from, 0.0), Vec3::new(offset, to, 0.0)),
+                (Vec3::new(from, offset, 0.0), Vec3::new(to, offset, 0.0)),
+            ] {
+                let midpoint = (start + end) * 0.5;
+                let fade = 1.0 - (midpoint.norm() / extent).min(1.0);
+                if fade <= 0.0 {
+                    continue;
+                }
 
-    let mut window = Window::new(
-        "Sistema Solar - Proyecto Final",
-        window_width,
-        window_height,
-        WindowOptions::default(),
-    )
-    .unwrap();
+                let project = |point: &Vec3| {
+                    project_point(
+                        point,
+                        &frame.view_matrix,
+                        &frame.projection_matrix,
+                        width,
+                        height,
+                    )
+                    .filter(|screen| {
+                        screen.x.abs() < max_coordinate && screen.y.abs() < max_coordinate
+                    })
+                };
+                if let (Some(start_screen), Some(end_screen)) = (project(&start), project(&end)) {
+                    framebuffer.draw_line_depth(
+                        start_screen,
+                        end_screen,
+                        grid.color,
+                        grid.alpha * fade * fade,
+                    );
+                }
+            }
+        }
+    }
+}
 
-    window.set_position(500, 500);
-    window.update();
+fn render_trail(
+    framebuffer: &mut Framebuffer,
+    trail: &OrbitTrail,
+    view_matrix: &Mat4,
+    projection_matrix: &Mat4,
+) {
+    let width = framebuffer.width as f32;
+    let height = framebuffer.height as f32;
+    let trail_color = color::Color::new(120, 200, 255);
+    // Los tramos que cruzan el borde se dibujan hasta él; el tope descarta solo los que caen
+    // muy lejos de la ventana, como en la estela de los cometas
+    let max_coordinate = 4.0 * width.max(height);
+
+    for (start, end, alpha) in trail.segments() {
+        let project = |point: &Vec3| {
+            project_point(point, view_matrix, projection_matrix, width, height)
+                .filter(|screen| screen.x.abs() < max_coordinate && screen.y.abs() < max_coordinate)
+        };
 
-    framebuffer.set_background_color(0x333355);
+        if let (Some(start_screen), Some(end_screen)) = (project(&start), project(&end)) {
+            framebuffer.draw_line_alpha(
+                start_screen.x as isize,
+                start_screen.y as isize,
+                end_screen.x as isize,
+                end_screen.y as isize,
+                trail_color,
+                alpha,
+            );
+        }
+    }
+}
 
-    // Posiciones iniciales en el plano eclíptico
-    let mut planet_orbits = vec![
-        4.0,  // Marte
-        6.0,  // Neon
-        8.0,  // Sol (solo referencia para mantener alineación)
-        10.0, // Dalmata
-        12.0, // Saturno
-        14.0, // Kepler-452b
-        16.0, // Tierra
-    ];
-
-    let mut translations = vec![
-        Vec3::new(2.0, 0.0, 0.0),  // Marte
-        Vec3::new(0.0, 0.0, 0.0),  // Neon
-        Vec3::new(-2.0, 0.0, 0.0), // Sol
-        Vec3::new(0.0, 2.0, 0.0),  // Dalmata
-        Vec3::new(0.0, 4.0, 0.0),  // Saturno
-        Vec3::new(1.0, 2.0, 0.0),  // Kepler-452b
-        Vec3::new(-1.0, 2.0, 0.0), // Tierra
-        Vec3::new(0.0, 0.0, 0.0),  // Cometa (posición inicial)
-    ];
-
-    let mut rotations = vec![Vec3::new(0.0, 0.0, 0.0); 8];
-    let scales = vec![1.0f32; 8];
-    let shaders = vec![
-        lava_shader,            // Marte
-        neon_normal_map_shader, // Neon
-        static_pattern_shader,  // Sol
-        dalmata_shader,         // Dalmata
-        combined_shader,        // Saturno
-        cellular_shader,        // Kepler-452b
-        earth,                  // Tierra
-        comet_shader,           // Cometa
-    ];
+// Exponente con el que se apaga la estela de los cometas: 1.0 se desvanece de forma lineal
+// hacia el punto más viejo, valores mayores dejan brillante solo el tramo cerca del núcleo
+const COMET_TRAIL_FADE: f32 = 2.0;
+
+// Estela de un cometa: segmentos aditivos con prueba de profundidad, más oscuros (y por lo
+// tanto más transparentes al sumarse) cuanto más viejos
+fn render_comet_trail(framebuffer: &mut Framebuffer, trail: &OrbitTrail, frame: &FrameContext) {
+    let width = framebuffer.width as f32;
+    let height = framebuffer.height as f32;
+    let trail_color = color::Color::new(150, 210, 255);
+    // Mismo tope que la cuadrícula para no recorrer tramos que caen muy lejos de la ventana
+    let max_coordinate = 4.0 * width.max(height);
+
+    for (start, end, alpha) in trail.segments() {
+        let project = |point: &Vec3| {
+            project_point(
+                point,
+                &frame.view_matrix,
+                &frame.projection_matrix,
+                width,
+                height,
+            )
+            .filter(|screen| screen.x.abs() < max_coordinate && screen.y.abs() < max_coordinate)
+        };
+        if let (Some(start_screen), Some(end_screen)) = (project(&start), project(&end)) {
+            framebuffer.add_line(
+                start_screen,
+                end_screen,
+                trail_color * alpha.powf(COMET_TRAIL_FADE),
+            );
+        }
+    }
+}
 
-    // OBJs
+fn render_particles(
+    framebuffer: &mut Framebuffer,
+    particles: &ParticleSystem,
+    sprite: &Texture,
+    view_matrix: &Mat4,
+    projection_matrix: &Mat4,
+) {
+    let width = framebuffer.width as f32;
+    let height = framebuffer.height as f32;
 
-    //Luego hacer un array de modelos para manejar planetas, estrellas, etc.
-    let obj = Obj::load("assets/models/sphere.obj").expect("Failed to load obj");
-    let vertex_arrays = obj.get_vertex_array();
+    for particle in &particles.particles {
+        let Some(screen) = project_to_screen(
+            &particle.position,
+            view_matrix,
+            projection_matrix,
+            width,
+            height,
+        ) else {
+            continue;
+        };
 
-    let obj_ring = Obj::load("assets/models/saturn.obj").expect("Failed to load obj_ring");
-    let vertex_arrays_ring = obj_ring.get_vertex_array();
+        // Cada partícula es un billboard de 6x6 píxeles teñido con el color de su edad
+        let (color, alpha) = particles.color_at(particle);
+        framebuffer.draw_sprite(screen.x, screen.y, 6.0, sprite, color, alpha);
+    }
+}
 
-    let obj_moon = Obj::load("assets/models/sphere.obj").expect("Failed to load obj_moon");
-    let vertex_arrays_moon = obj_moon.get_vertex_array();
+// Paso de tiempo máximo por frame (segundos); evita saltos enormes después de un bloqueo
+const MAX_FRAME_TIME: f32 = 0.1;
 
-    let obj_comet = Obj::load("assets/models/sphere.obj").expect("Failed to load obj_comet");
-    let vertex_arrays_comet = obj_comet.get_vertex_array();
+const SCENE_EXPORT_PATH: &str = "scene.json";
 
-    // OBJ de mi nave
-    let obj_tie_fighter =
-        Obj::load("assets/models/tiefighter.obj").expect("Failed to load tiefigther.obj");
-    let vertex_arrays_tie_fighter = obj_tie_fighter.get_vertex_array();
+// Fracción de la velocidad de la nave mientras se mantiene Shift
+const SHIP_PRECISION_FACTOR: f32 = 0.1;
 
-    // Variables para la nave
-    let mut tie_fighter_position = Vec3::new(0.0, 0.0, 7.0); // Posición inicial
-    let mut tie_fighter_direction = Vec3::new(0.0, 0.0, -1.0); // Dirección inicial
-    let mut tie_fighter_up = Vec3::new(0.0, 1.0, 0.0); // Vector "arriba"
+// Velocidad de la cámara en vuelo libre (unidades por segundo)
+const FREE_FLY_SPEED: f32 = 6.0;
 
-    let start_time = Instant::now(); // Tiempo inicial para controlar la rotación
-    let mut last_mouse_pos = (0.0, 0.0);
+// Duración del vuelo de la cámara hacia el planeta elegido con 1-7
+const CAMERA_TRANSITION_SECONDS: f32 = 0.5;
 
-    // Configuración de la cámara
-    let mut camera = Camera::new(
-        Vec3::new(0.0, 0.0, 5.0), // Posición inicial de la cámara
-        Vec3::new(0.0, 0.0, 0.0), // Centro (Sol)
-        Vec3::new(0.0, 1.0, 0.0), // Vector "arriba"
-    );
+// Vista de un planeta para las teclas 1-7: la cámara del lado opuesto al Sol, a dos radios
+// del planeta, mirando hacia el Sol
+fn planet_view(scene: &Scene, body: usize) -> (Vec3, Vec3) {
+    let planet_position = scene.translations[body];
+    let planet_radius = scene.scales[body] * 1.5;
 
-    let mut should_update_camera_target = false;
+    // Normalizar la dirección hacia el Sol
+    let direction_to_sun = nalgebra_glm::normalize(&(Vec3::new(0.0, 0.0, 0.0) - planet_position));
+    let mut eye = planet_position - direction_to_sun * (planet_radius * 2.0);
 
-    let mut current_camera_target = 0; // Índice del planeta seleccionado
+    // Validar la posición de la cámara
+    if !(1e-3..=1e6).contains(&eye.norm()) {
+        eye = Vec3::new(0.0, 0.0, 10.0); // Restablecer
+    }
+    (eye, Vec3::new(0.0, 0.0, 0.0))
+}
 
-    let mut zoom_factor = 3.0; // Zoom inicial
+// Distancia máxima (píxeles de la ventana) entre presionar y soltar el clic para contarlo
+// como selección y no como arrastre de la vista
+const CLICK_TOLERANCE: f32 = 4.0;
+
+// Punto de la ventana llevado a la imagen presentada (0.0 - 1.0 en cada eje). Con
+// `ScaleMode::AspectRatioStretch` la imagen se escala sin deformarse y se centra, así que
+// puede quedar con franjas a los lados o arriba y abajo; un punto sobre ellas da None
+fn window_to_image(
+    point: (f32, f32),
+    window_size: (usize, usize),
+    image_size: (usize, usize),
+) -> Option<(f32, f32)> {
+    let (window_width, window_height) = (window_size.0 as f32, window_size.1 as f32);
+    let (image_width, image_height) = (image_size.0 as f32, image_size.1 as f32);
+    let scale = (window_width / image_width).min(window_height / image_height);
+    let drawn_width = image_width * scale;
+    let drawn_height = image_height * scale;
+
+    let x = (point.0 - (window_width - drawn_width) * 0.5) / drawn_width;
+    let y = (point.1 - (window_height - drawn_height) * 0.5) / drawn_height;
+    ((0.0..=1.0).contains(&x) && (0.0..=1.0).contains(&y)).then_some((x, y))
+}
 
-    while window.is_open() {
-        if window.is_key_down(Key::Escape) {
-            break;
-        }
+// Planeta bajo el punto `screen` de la pantalla (0.0 - 1.0 en cada eje, origen arriba a la
+// izquierda): el rayo sale de la cámara atravesando ese punto y se prueba contra la esfera
+// envolvente de cada planeta. Devuelve el impacto más cercano, o None si no toca ninguno
+fn pick_body(
+    scene: &Scene,
+    meshes: &Meshes,
+    camera: &Camera,
+    projection_matrix: &Mat4,
+    screen: (f32, f32),
+) -> Option<usize> {
+    // Desproyectar el punto en los planos cercano y lejano para obtener la dirección del rayo
+    let view_matrix = create_view_matrix(camera.eye, camera.center, camera.up);
+    let inverse = (projection_matrix * view_matrix).try_inverse()?;
+    let ndc_x = screen.0 * 2.0 - 1.0;
+    let ndc_y = 1.0 - screen.1 * 2.0;
+    let unproject = |ndc_z: f32| {
+        let point = inverse * Vec4::new(ndc_x, ndc_y, ndc_z, 1.0);
+        point.xyz() / point.w
+    };
+    let direction = (unproject(1.0) - unproject(-1.0)).try_normalize(f32::EPSILON)?;
+
+    (0..scene.planet_orbits.len())
+        .filter_map(|body| {
+            let radius = body_bounding_radius(scene, meshes, body);
+            let to_center = scene.translations[body] - camera.eye;
+            let along_ray = to_center.dot(&direction);
+            let miss_squared = to_center.norm_squared() - along_ray * along_ray;
+            if miss_squared > radius * radius {
+                return None;
+            }
 
-        if let Some((_, scroll_y)) = window.get_scroll_wheel() {
-            let zoom_sensitivity = 0.1; // Ajusta la sensibilidad
-            zoom_factor -= scroll_y as f32 * zoom_sensitivity;
-            zoom_factor = zoom_factor.clamp(2.0, 50.0); // Limitar el zoom
-        }
+            // Primer punto de la esfera frente a la cámara (el de salida si está adentro)
+            let half_chord = (radius * radius - miss_squared).sqrt();
+            let distance = if along_ray - half_chord >= 0.0 {
+                along_ray - half_chord
+            } else {
+                along_ray + half_chord
+            };
+            (distance >= 0.0).then_some((body, distance))
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(body, _)| body)
+}
 
-        // Control de cámara con teclas numéricas
-        if window.is_key_down(Key::Key1) {
-            current_camera_target = 0; // Marte
-            should_update_camera_target = true;
-        } else if window.is_key_down(Key::Key2) {
-            current_camera_target = 1; // Neon
-            should_update_camera_target = true;
-        } else if window.is_key_down(Key::Key3) {
-            current_camera_target = 2; // Sol
-            should_update_camera_target = true;
-        } else if window.is_key_down(Key::Key4) {
-            current_camera_target = 3; // Dalmata
-            should_update_camera_target = true;
-        } else if window.is_key_down(Key::Key5) {
-            current_camera_target = 4; // Saturno
-            should_update_camera_target = true;
-        } else if window.is_key_down(Key::Key6) {
-            current_camera_target = 5; // Kepler-452b
-            should_update_camera_target = true;
-        } else if window.is_key_down(Key::Key7) {
-            current_camera_target = 6; // Tierra
-            should_update_camera_target = true;
-        }
+// Quién mueve la cámara del loop principal; `C` pasa de uno al siguiente. El recorrido de
+// `F4` tiene prioridad sobre cualquiera de ellos mientras se reproduce
+#[derive(Clone, Copy)]
+enum CameraMode {
+    ChaseShip, // Detrás de la nave, que responde al teclado y al mouse
+    FreeFly,   // Cámara suelta: WASD / Q-E para moverse y el mouse para mirar
+    Cinematic, // Órbita automática alrededor del Sol; la nave queda quieta
+}
 
-        // Asegúrate de que current_camera_target esté dentro del rango válido
-        if current_camera_target >= planet_orbits.len() {
-            current_camera_target = 0; // Regresar al valor por defecto (Marte)
-            should_update_camera_target = true;
+impl CameraMode {
+    fn next(self) -> Self {
+        match self {
+            CameraMode::ChaseShip => CameraMode::FreeFly,
+            CameraMode::FreeFly => CameraMode::Cinematic,
+            CameraMode::Cinematic => CameraMode::ChaseShip,
         }
+    }
+}
 
-        if should_update_camera_target {
-            let planet_position = translations[current_camera_target];
-            let planet_radius = scales[current_camera_target] * 1.5;
+// Factor por pulsación y límites del multiplicador de velocidad orbital
+const ORBIT_SPEED_STEP: f32 = 1.25;
+const ORBIT_SPEED_RANGE: (f32, f32) = (1.0 / 16.0, 64.0);
+
+// Lo mismo para la escala de tiempo de toda la simulación (`,` / `.`)
+const TIME_SCALE_STEP: f32 = 2.0;
+const TIME_SCALE_RANGE: (f32, f32) = (1.0 / 8.0, 8.0);
+
+// Imprime en consola todo lo necesario para reproducir el frame actual: cámara, proyección,
+// tiempo y la transformación del cuerpo enfocado
+fn print_render_state(
+    scene: &Scene,
+    camera: &Camera,
+    focused: usize,
+    elapsed_time: f32,
+    aspect_ratio: f32,
+) {
+    let fmt_vec = |v: &Vec3| format!("[{:.4}, {:.4}, {:.4}]", v.x, v.y, v.z);
+    let translation = scene.translations[focused];
+    let rotation = scene.rotations[focused];
+    let scale = scene.scale_animations[focused].scale_at(scene.scales[focused], elapsed_time);
+    let material = scene.materials[focused];
+
+    println!("===== Estado de render =====");
+    println!(
+        "Tiempo de simulación: {:.4} s (velocidad orbital x{:.2}, tiempo orbital {:.4} s)",
+        elapsed_time,
+        scene.orbit_speed,
+        scene.orbit_time()
+    );
+    println!("Cámara:");
+    println!("  eye:    {}", fmt_vec(&camera.eye));
+    println!("  center: {}", fmt_vec(&camera.center));
+    println!("  up:     {}", fmt_vec(&camera.up));
+    println!(
+        "Proyección: FOV {:.1}°, aspecto {:.4}, near {}, far {}",
+        FIELD_OF_VIEW.to_degrees(),
+        aspect_ratio,
+        NEAR_PLANE,
+        FAR_PLANE
+    );
+    println!("Cuerpo enfocado: {} ({})", focused, scene.names[focused]);
+    println!("  posición: {}", fmt_vec(&translation));
+    println!("  rotación: {}", fmt_vec(&rotation));
+    println!("  escala:   {:.4}", scale);
+    println!(
+        "  shader: {}, textura: {}, mapa normal: {}",
+        scene.shader_names[focused], material.use_texture, material.use_normal_map
+    );
+    print!(
+        "Matriz de vista:{}",
+        create_view_matrix(camera.eye, camera.center, camera.up)
+    );
+    print!(
+        "Matriz de modelo del cuerpo enfocado:{}",
+        create_model_matrix(translation, scale, rotation)
+    );
+}
 
-            // Normalizar la dirección hacia el Sol
-            let direction_to_sun =
-                nalgebra_glm::normalize(&(Vec3::new(0.0, 0.0, 0.0) - planet_position));
+// Guarda la escena actual en un archivo JSON; un error solo se reporta, no detiene el programa
+fn export_scene(scene: &Scene, camera: &Camera, path: &str) {
+    let result = scene
+        .to_json(camera)
+        .map_err(|err| err.to_string())
+        .and_then(|json| std::fs::write(path, json).map_err(|err| err.to_string()));
 
-            // Calcular la posición de la cámara
-            camera.eye = planet_position - direction_to_sun * (planet_radius * 2.0);
+    match result {
+        Ok(()) => println!("Escena exportada a {}", path),
+        Err(err) => eprintln!("No se pudo exportar la escena a {}: {}", path, err),
+    }
+}
 
-            // Validar la posición de la cámara
-            if camera.eye.norm() > 1e6 || camera.eye.norm() < 1e-3 {
-                println!(
-                    "Advertencia: Posición de la cámara fuera de rango: {:?}",
-                    camera.eye
-                );
-                camera.eye = Vec3::new(0.0, 0.0, 10.0); // Restablecer
-            }
+// Guarda el frame actual como screenshot_<segundos desde 1970>.png en la carpeta actual
+fn save_screenshot(framebuffer: &Framebuffer) {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    let path = format!("screenshot_{}.png", timestamp);
+
+    match framebuffer.save_png(&path) {
+        Ok(()) => println!("Captura guardada en {}", path),
+        Err(err) => eprintln!("No se pudo guardar la captura en {}: {}", path, err),
+    }
+}
 
-            println!("Posición de la cámara: {:?}", camera.eye);
-            camera.center = Vec3::new(0.0, 0.0, 0.0); // Mirar al Sol
-            should_update_camera_target = false; // Actualización completa
-        }
+// Datos de la cámara y del tiempo compartidos por todas las pasadas de un frame
+struct FrameContext {
+    camera_eye: Vec3,
+    sun_position: Vec3,
+    view_matrix: Mat4,
+    projection_matrix: Mat4,
+    viewport_matrix: Mat4,
+    elapsed_time: f32,
+    seed: u64,
+    albedo: bool, // Los shaders devuelven su color base, sin luz ni ambiente
+    cull_backfaces: bool,
+    linear_textures: bool, // El framebuffer aplica gamma al presentar (`--gamma`)
+}
 
-        //handle_input(&window, &mut camera, &mut last_mouse_pos);
+impl FrameContext {
+    fn uniforms(&self, model_matrix: Mat4, noise: Arc<FastNoiseLite>) -> Uniforms {
+        self.material_uniforms(model_matrix, noise, Material::default())
+    }
 
-        // Verificar colisiones para la nave
-        for (i, planet_position) in translations.iter().enumerate() {
-            let planet_radius = scales[i] + 0.5; // Aumentar ligeramente el radio para mayor seguridad
-            if check_collision(&tie_fighter_position, planet_position, planet_radius) {
-                // Ajustar la posición de la nave para evitar la colisión
-                let direction = nalgebra_glm::normalize(&(tie_fighter_position - planet_position));
-                tie_fighter_position = *planet_position + direction * (planet_radius + 0.05);
-                //println!(
-                //    "Colisión detectada con el planeta {}. Posición de la nave ajustada.",
-                //    i
-                //);
-            }
+    fn material_uniforms(
+        &self,
+        model_matrix: Mat4,
+        noise: Arc<FastNoiseLite>,
+        material: Material,
+    ) -> Uniforms {
+        Uniforms {
+            model_matrix,
+            view_matrix: self.view_matrix,
+            projection_matrix: self.projection_matrix,
+            viewport_matrix: self.viewport_matrix,
+            camera_position: self.camera_eye,
+            sun_position: self.sun_position,
+            material,
+            seed: self.seed,
+            time: self.elapsed_time,
+            ambient: *SKY_AMBIENT,
+            noise,
+            displacement: None,
+            height_map: None,
+            normal_map: None,
+            material_textures: None,
+            environment: None,
+            atmosphere: None,
+            albedo: self.albedo,
+            linear_textures: self.linear_textures,
+            cull_backfaces: self.cull_backfaces,
+            front_face: Winding::CounterClockwise,
         }
+    }
 
-        // Actualizar la posición y orientación de la cámara para seguir la nave
-        camera.eye =
-            tie_fighter_position - tie_fighter_direction * zoom_factor + tie_fighter_up * 2.0;
-        camera.center = tie_fighter_position;
-        camera.up = tie_fighter_up;
-
-        // Manejar los controles de la nave
-        handle_tie_fighter_input(
-            &window,
-            &mut tie_fighter_position,
-            &mut tie_fighter_direction,
-            &mut tie_fighter_up,
-            &mut camera,
-            &mut last_mouse_pos,
-        );
+    // `radius`: radio de la esfera envolvente en el mundo
+    fn is_visible(&self, position: &Vec3, radius: f32) -> bool {
+        is_visible(position, radius, &self.view_matrix, &self.projection_matrix)
+    }
+}
 
-        framebuffer.clear();
+// Plano de "agua" bajo la eclíptica que refleja la escena (segunda pasada con cámara reflejada)
+struct WaterPlane {
+    height: f32,
+    vertices: Vec<Vertex>,
+    color: color::Color,
+    reflectivity: f32,
+    reflection: Framebuffer,
+}
 
-        framebuffer.draw_skybox(&FONDO);
+// Opciones de render que se activan y desactivan con el teclado
+struct RenderSettings {
+    show_orbits: bool, // Anillos de las órbitas o estelas, según `show_trails`
+    show_ship: bool,
+    show_trails: bool,
+    show_water: bool,
+    god_rays_enabled: bool,
+    depth_view: DepthView,
+    show_bounds: bool, // Cajas envolventes de los cuerpos (verde = dibujado, rojo = descartado)
+    show_overdraw: bool, // Veces que se sombreó cada píxel (azul = una, rojo = muchas)
+    show_help: bool,
+    show_thumbnails: bool,
+    focus_antialiasing: Option<usize>, // Muestras por lado del supermuestreo del cuerpo enfocado
+    depth_peeling: bool,               // Atmósferas compuestas con dos capas de depth peeling
+    sun_halo: Option<SunHalo>,
+    ecliptic_grid: Option<EclipticGrid>,
+    chromatic_aberration: Option<f32>, // Desplazamiento máximo de los canales, en píxeles
+    albedo: bool, // Solo el color base de cada superficie: sin luz, atmósferas ni posproceso
+    cull_backfaces: bool, // Descartar las caras traseras al ensamblar los triángulos
+    retro_palette: Option<Vec<color::Color>>, // Imagen final tramada con estos colores
+    scan_transition: Option<f32>, // Duración del barrido al cambiar de planeta (None = corte)
+}
 
-        let view_matrix = create_view_matrix(camera.eye, camera.center, camera.up);
-        let projection_matrix =
-            create_perspective_matrix(window_width as f32, window_height as f32);
-        let viewport_matrix =
-            create_viewport_matrix(framebuffer_width as f32, framebuffer_height as f32);
+// Cómo se muestra el z-buffer; `Z` pasa de uno al siguiente
+#[derive(Clone, Copy, PartialEq)]
+enum DepthView {
+    Hidden,
+    Inset,      // Mapa de calor en la esquina superior derecha, sobre la imagen
+    FullScreen, // Toda la ventana en escala de grises, con la profundidad linealizada
+}
 
-        let elapsed_time = start_time.elapsed().as_secs_f32();
+impl DepthView {
+    fn next(self) -> Self {
+        match self {
+            DepthView::Hidden => DepthView::Inset,
+            DepthView::Inset => DepthView::FullScreen,
+            DepthView::FullScreen => DepthView::Hidden,
+        }
+    }
+}
 
-        let model_matrix_tie_fighter = nalgebra_glm::translation(&tie_fighter_position)
-            * nalgebra_glm::look_at(&Vec3::zeros(), &tie_fighter_direction, &tie_fighter_up)
-            * nalgebra_glm::scaling(&Vec3::new(0.1, 0.1, 0.1));
+// Lo que el modo foto ocultó, para devolverlo tal cual al salir
+struct PhotoModeBackup {
+    show_orbits: bool,
+    show_ship: bool,
+    depth_view: DepthView,
+    show_bounds: bool,
+    show_help: bool,
+    show_thumbnails: bool,
+    ecliptic_grid: Option<EclipticGrid>,
+}
 
-        let uniforms_tie_fighter = Uniforms {
-            model_matrix: model_matrix_tie_fighter,
-            view_matrix,
-            projection_matrix,
-            viewport_matrix,
-            time: elapsed_time as u32,
-            noise: create_noise(),
+impl RenderSettings {
+    // Modo foto: solo quedan los cuerpos celestes (y la nave si `hide_ship` es falso)
+    fn enter_photo_mode(&mut self, hide_ship: bool) -> PhotoModeBackup {
+        let backup = PhotoModeBackup {
+            show_orbits: self.show_orbits,
+            show_ship: self.show_ship,
+            depth_view: self.depth_view,
+            show_bounds: self.show_bounds,
+            show_help: self.show_help,
+            show_thumbnails: self.show_thumbnails,
+            ecliptic_grid: self.ecliptic_grid,
         };
 
-        // Renderizar la nave
-        render(
-            &mut framebuffer,
-            &uniforms_tie_fighter,
-            &vertex_arrays_tie_fighter,
-            |_, _| color::Color::new(165, 165, 165),
-        );
+        self.show_orbits = false;
+        self.show_ship = self.show_ship && !hide_ship;
+        self.depth_view = DepthView::Hidden;
+        self.show_bounds = false;
+        self.show_help = false;
+        self.show_thumbnails = false;
+        self.ecliptic_grid = None;
+        backup
+    }
 
-        for i in 0..translations.len() {
-            // Movimiento orbital
-            if i != 2
-                && i < planet_orbits.len()
-                && is_visible(&translations[i], &view_matrix, &projection_matrix)
-            {
-                let orbit_angle = elapsed_time * (0.1 + i as f32 * 0.05);
-                translations[i].x = planet_orbits[i] * 1.5 * orbit_angle.cos(); // Factor 1.5 para separarlos más
-                translations[i].y = planet_orbits[i] * 1.5 * orbit_angle.sin(); // Factor 1.5 para separarlos más
-
-                render_orbit(
-                    &mut framebuffer,
-                    Vec3::new(0.0, 0.0, 0.0), // Centro de la órbita (el Sol)
-                    planet_orbits[i] * 1.5,   // Radio de la órbita
-                    100,                      // Número de segmentos para el círculo
-                    &view_matrix,
-                    &projection_matrix,
-                );
-            }
+    fn exit_photo_mode(&mut self, backup: PhotoModeBackup) {
+        self.show_orbits = backup.show_orbits;
+        self.show_ship = backup.show_ship;
+        self.depth_view = backup.depth_view;
+        self.show_bounds = backup.show_bounds;
+        self.show_help = backup.show_help;
+        self.show_thumbnails = backup.show_thumbnails;
+        self.ecliptic_grid = backup.ecliptic_grid;
+    }
+}
 
-            rotations[i].y = elapsed_time * (0.1 + i as f32 * 0.05);
+// Halo alrededor del Sol: `scale` veces su radio en pantalla, sumado sobre la imagen
+#[derive(Clone, Copy)]
+struct SunHalo {
+    scale: f32,
+    color: color::Color,
+    intensity: f32,
+}
+
+// Orden fijo de las pasadas de cada frame. Los efectos de post-proceso se aplican
+// sobre la imagen 3D antes del overlay para que el HUD nunca quede afectado.
+#[derive(Clone, Copy)]
+enum RenderPass {
+    Opaque,
+    Transparent,
+    PostProcess,
+    Overlay,
+}
+
+const RENDER_PASSES: [RenderPass; 4] = [
+    RenderPass::Opaque,
+    RenderPass::Transparent,
+    RenderPass::PostProcess,
+    RenderPass::Overlay,
+];
+
+// Matriz de modelo de la malla principal del cuerpo (el anillo en el caso de Saturno)
+fn body_model_matrix(scene: &Scene, body: usize, elapsed_time: f32) -> Mat4 {
+    let translation = scene.translations[body];
+    let rotation = scene.rotations[body];
+    let scale = scene.scales[body];
+
+    match body {
+        // Anillo de Saturno: posición y rotación iguales al planeta, escala ajustada
+        4 => create_model_matrix(translation, scale * 0.7, rotation),
+        6 => create_model_matrix(translation, scale, rotation),
+        SUN => create_model_matrix(
+            Vec3::new(0.0, 0.0, 0.0),
+            scene.scale_animations[body].scale_at(scale * 1.5, elapsed_time),
+            Vec3::new(0.0, 0.0, 0.0),
+        ),
+        _ => create_model_matrix(
+            translation,
+            scene.scale_animations[body].scale_at(scale, elapsed_time),
+            rotation,
+        ),
+    }
+}
+
+// Matriz de modelo de la Luna, que orbita la Tierra (cuerpo 6)
+fn moon_model_matrix(scene: &Scene, elapsed_time: f32) -> Mat4 {
+    let translation = scene.translations[6];
+
+    // Calcular la órbita de la luna
+    let moon_orbit_radius = 0.7; // Radio de la órbita
+    let moon_speed = 0.5; // Velocidad de la órbita
+    let moon_angle = elapsed_time * moon_speed;
+
+    let moon_x = translation.x + moon_orbit_radius * moon_angle.cos();
+    let moon_y = translation.y + moon_orbit_radius * moon_angle.sin();
+
+    let moon_translation = Vec3::new(moon_x, moon_y, 0.0);
+    // uv_sphere tiene radio 1, el doble que sphere.obj
+    create_model_matrix(moon_translation, scene.scales[6] * 0.15, scene.rotations[6])
+}
+
+// Dibuja el cuerpo `body` (con su anillo o su luna si los tiene)
+fn render_body(
+    framebuffer: &mut Framebuffer,
+    scene: &Scene,
+    meshes: &Meshes,
+    frame: &FrameContext,
+    body: usize,
+) {
+    let elapsed_time = frame.elapsed_time;
+    let model_matrix = body_model_matrix(scene, body, elapsed_time);
+    let mut uniforms = frame.material_uniforms(
+        model_matrix,
+        Arc::clone(&scene.noises[body]),
+        scene.materials[body],
+    );
+    // Solo lo lee el shader de neón; los demás lo ignoran
+    uniforms.normal_map = NEON_NORMAL.clone();
+    uniforms.material_textures = Some(Arc::clone(&meshes.model_textures[body]));
 
-            if is_visible(&translations[i], &view_matrix, &projection_matrix) {
-                let model_matrix = create_model_matrix(translations[i], scales[i], rotations[i]);
-                let noise = create_noise_for_planet(i);
+    if body == 6 {
+        // Renderizar la Tierra
+        render(
+            framebuffer,
+            &uniforms,
+            &meshes.models[body],
+            earth,
+            BlendMode::Opaque,
+        );
+
+        // Renderizar la Luna, con relieve real de cráteres si hay mapa de alturas
+        let mut moon_uniforms = frame.uniforms(
+            moon_model_matrix(scene, elapsed_time),
+            Arc::clone(&scene.satellite_noise),
+        );
+        moon_uniforms.height_map = MOON_HEIGHT.as_ref().map(|texture| HeightMap {
+            texture: Arc::clone(texture),
+            scale: scene.moon_relief,
+        });
+        render(
+            framebuffer,
+            &moon_uniforms,
+            &meshes.moon,
+            luna_shader,
+            BlendMode::Opaque,
+        );
+    } else if body == SUN {
+        // Renderizar el Sol
+        render(
+            framebuffer,
+            &uniforms,
+            &meshes.models[body],
+            sun_shader,
+            BlendMode::Opaque,
+        );
+    } else {
+        // Renderizar los demás planetas con su propia malla (el anillo en el caso de Saturno)
+        render(
+            framebuffer,
+            &uniforms,
+            &meshes.models[body],
+            scene.shaders[body],
+            BlendMode::Opaque,
+        );
+    }
+}
+
+// Caja envolvente en el mundo de todo lo que dibuja `render_body` para el cuerpo
+fn body_bounds(scene: &Scene, meshes: &Meshes, body: usize, elapsed_time: f32) -> Aabb {
+    let model_matrix = body_model_matrix(scene, body, elapsed_time);
+    let bounds = meshes.model_bounds[body].transformed(&model_matrix);
+    if body != 6 {
+        return bounds;
+    }
+
+    // La Tierra incluye la Luna; el relieve puede sobresalir hasta la mitad de moon_relief
+    let relief = Vec3::repeat(scene.moon_relief * 0.5);
+    let moon_bounds = Aabb {
+        min: meshes.moon_bounds.min - relief,
+        max: meshes.moon_bounds.max + relief,
+    };
+    bounds.union(&moon_bounds.transformed(&moon_model_matrix(scene, elapsed_time)))
+}
+
+// Radio de una esfera que contiene todo lo que dibuja `render_body` para el cuerpo
+fn body_bounding_radius(scene: &Scene, meshes: &Meshes, body: usize) -> f32 {
+    let scale = scene.scales[body];
+    match body {
+        SUN => calculate_sphere_radius(&meshes.models[body]) * scale * 1.5,
+        4 => calculate_sphere_radius(&meshes.models[body]) * scale * 0.7,
+        // La Tierra incluye la órbita de la Luna, o su propia malla si es más grande
+        6 => (calculate_sphere_radius(&meshes.models[body]) * scale).max(0.7 + scale * 0.15),
+        _ => calculate_sphere_radius(&meshes.models[body]) * scale,
+    }
+}
+
+// Si el cuerpo se dibuja este frame: los de always_render nunca se descartan por visibilidad
+fn body_in_view(scene: &Scene, meshes: &Meshes, frame: &FrameContext, body: usize) -> bool {
+    scene.always_render[body]
+        || frame.is_visible(
+            &scene.translations[body],
+            body_bounding_radius(scene, meshes, body),
+        )
+}
+
+// Radio aproximado del cuerpo en pantalla (píxeles), o None si queda fuera del frustum.
+// Solo se usa para ordenar cuerpos por prioridad, así basta con la esfera envolvente.
+fn body_screen_radius(
+    scene: &Scene,
+    meshes: &Meshes,
+    frame: &FrameContext,
+    framebuffer: &Framebuffer,
+    body: usize,
+) -> Option<f32> {
+    let position = scene.translations[body];
+    let radius = body_bounding_radius(scene, meshes, body);
+    if !frame.is_visible(&position, radius) {
+        return None;
+    }
+
+    // En espacio de vista la cámara mira hacia -Z
+    let view_position = frame.view_matrix * Vec4::new(position.x, position.y, position.z, 1.0);
+    let depth = (-view_position.z).max(NEAR_PLANE);
+    let pixels_per_unit = (frame.projection_matrix[(0, 0)] * framebuffer.width as f32)
+        .max(frame.projection_matrix[(1, 1)] * framebuffer.height as f32)
+        * 0.5;
+    Some(radius * pixels_per_unit / depth)
+}
+
+// Aplica `max_visible_bodies` con los tamaños en pantalla del frame actual
+fn select_visible_bodies(
+    scene: &mut Scene,
+    meshes: &Meshes,
+    frame: &FrameContext,
+    framebuffer: &Framebuffer,
+    focused: Option<usize>,
+) {
+    if scene.max_visible_bodies.is_none() {
+        return;
+    }
+    let screen_sizes: Vec<Option<f32>> = (0..scene.translations.len())
+        .map(|body| body_screen_radius(scene, meshes, frame, framebuffer, body))
+        .collect();
+    scene.select_visible_bodies(&screen_sizes, focused);
+}
+
+// Límite de muestras del framebuffer auxiliar; si la huella del cuerpo es muy grande se
+// baja el factor de supermuestreo
+const MAX_SUPERSAMPLES: usize = 4 * 1024 * 1024;
+
+// Dibuja el cuerpo con `factor` x `factor` muestras por píxel solo dentro de su huella en
+// pantalla y compone el promedio sobre el framebuffer. La región auxiliar empieza en un píxel
+// entero y cada píxel corresponde exactamente a un bloque de muestras, así el resultado
+// queda alineado con el resto de la imagen. Devuelve false si no se puede delimitar la
+// huella (cámara dentro o muy cerca del cuerpo); entonces se dibuja sin supermuestreo.
+fn render_body_supersampled(
+    framebuffer: &mut Framebuffer,
+    scene: &Scene,
+    meshes: &Meshes,
+    frame: &FrameContext,
+    body: usize,
+    factor: usize,
+) -> bool {
+    let center = scene.translations[body];
+    // Margen para la animación de escala y el desplazamiento de los vértices
+    let radius = body_bounding_radius(scene, meshes, body) * 1.2;
+
+    let view_center = frame.view_matrix * Vec4::new(center.x, center.y, center.z, 1.0);
+    let distance = -view_center.z;
+    if distance - radius <= NEAR_PLANE {
+        return false;
+    }
+    let Some(screen_center) = project_point(
+        &center,
+        &frame.view_matrix,
+        &frame.projection_matrix,
+        framebuffer.width as f32,
+        framebuffer.height as f32,
+    ) else {
+        return false;
+    };
+
+    // Semiejes en píxeles de la esfera proyectada (cota superior para esferas fuera del eje);
+    // la escala horizontal y la vertical de la proyección no son iguales
+    let extent = radius / (distance - radius);
+    let radius_x = extent * frame.projection_matrix[(0, 0)] * framebuffer.width as f32 / 2.0 + 2.0;
+    let radius_y = extent * frame.projection_matrix[(1, 1)] * framebuffer.height as f32 / 2.0 + 2.0;
+
+    let min_x = (screen_center.x - radius_x).floor().max(0.0) as usize;
+    let min_y = (screen_center.y - radius_y).floor().max(0.0) as usize;
+    let max_x = ((screen_center.x + radius_x).ceil().max(0.0) as usize).min(framebuffer.width);
+    let max_y = ((screen_center.y + radius_y).ceil().max(0.0) as usize).min(framebuffer.height);
+    if min_x >= max_x || min_y >= max_y {
+        return true; // Fuera de pantalla: no hay nada que dibujar
+    }
+    let (width, height) = (max_x - min_x, max_y - min_y);
+
+    let mut factor = factor.max(1);
+    while factor > 1 && width * height * factor * factor > MAX_SUPERSAMPLES {
+        factor -= 1;
+    }
+    if factor == 1 {
+        return false;
+    }
+
+    // Misma cámara; el viewport se escala por `factor` y se desplaza al origen de la región
+    let sample_scale = factor as f32;
+    let region_viewport = nalgebra_glm::scaling(&Vec3::new(sample_scale, sample_scale, 1.0))
+        * nalgebra_glm::translation(&Vec3::new(-(min_x as f32), -(min_y as f32), 0.0))
+        * frame.viewport_matrix;
+    let region_frame = FrameContext {
+        viewport_matrix: region_viewport,
+        ..*frame
+    };
+
+    let mut samples = Framebuffer::new(width * factor, height * factor);
+    samples.depth_test = true; // La cobertura se detecta por el z-buffer de la región
+    samples.frustum_clipping = framebuffer.frustum_clipping;
+    render_body(&mut samples, scene, meshes, &region_frame, body);
+
+    // Resolver: promedio de las muestras cubiertas, mezclado según la cobertura
+    let samples_per_pixel = (factor * factor) as f32;
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = [0.0f32; 3];
+            let mut covered = 0;
+            let mut nearest = f32::INFINITY;
+
+            for sy in 0..factor {
+                for sx in 0..factor {
+                    let index = (y * factor + sy) * samples.width + x * factor + sx;
+                    let depth = samples.zbuffer[index];
+                    if depth.is_finite() {
+                        let color = samples.buffer[index];
+                        sum[0] += color.r as f32;
+                        sum[1] += color.g as f32;
+                        sum[2] += color.b as f32;
+                        covered += 1;
+                        nearest = nearest.min(depth);
+                    }
+                }
+            }
+
+            let index = (min_y + y) * framebuffer.width + min_x + x;
+            if covered == 0 || nearest >= framebuffer.zbuffer[index] {
+                continue;
+            }
+
+            let scale = 255.0 * covered as f32;
+            let average = color::Color::from_float(sum[0] / scale, sum[1] / scale, sum[2] / scale);
+            let coverage = covered as f32 / samples_per_pixel;
+            framebuffer.buffer[index] = framebuffer.buffer[index].lerp(&average, coverage);
+            if coverage >= 0.5 {
+                framebuffer.zbuffer[index] = nearest;
+            }
+        }
+    }
+
+    true
+}
+
+// Órbitas, nave, planetas, Sol, Luna, núcleos de los cometas y el plano de agua
+fn render_opaque_pass(
+    framebuffer: &mut Framebuffer,
+    scene: &Scene,
+    meshes: &Meshes,
+    frame: &FrameContext,
+    settings: &RenderSettings,
+    water: &mut WaterPlane,
+    focused: Option<usize>,
+) {
+    let elapsed_time = frame.elapsed_time;
+
+    // Las líneas de las órbitas no usan z-buffer, así que van primero y los cuerpos las tapan
+    for i in 0..scene.translations.len() {
+        if !settings.show_orbits || i == SUN || !(body_in_view(scene, meshes, frame, i)) {
+            continue;
+        }
+
+        if settings.show_trails {
+            render_trail(
+                framebuffer,
+                &scene.orbit_trails[i],
+                &frame.view_matrix,
+                &frame.projection_matrix,
+            );
+        } else {
+            render_orbit(
+                framebuffer,
+                Vec3::new(0.0, 0.0, 0.0), // Centro de la órbita (el Sol)
+                scene.orbit_radius(i),    // Radio de la órbita
+                100,                      // Número de segmentos para el círculo
+                &frame.view_matrix,
+                &frame.projection_matrix,
+            );
+        }
+    }
+
+    let model_matrix_tie_fighter = nalgebra_glm::translation(&scene.tie_fighter_position)
+        * nalgebra_glm::look_at(
+            &Vec3::zeros(),
+            &scene.tie_fighter_orientation.direction(),
+            &scene.tie_fighter_orientation.up(),
+        )
+        * nalgebra_glm::scaling(&Vec3::new(0.1, 0.1, 0.1));
+
+    // Renderizar la nave
+    if settings.show_ship {
+        // Metal que refleja el fondo de estrellas
+        let mut ship_uniforms = frame.uniforms(model_matrix_tie_fighter, Arc::new(create_noise()));
+        ship_uniforms.environment = Some(Arc::clone(&FONDO));
+        render(
+            framebuffer,
+            &ship_uniforms,
+            &meshes.tie_fighter,
+            ship_shader,
+            BlendMode::Opaque,
+        );
+    }
+
+    for i in draw_order(framebuffer, &frame.view_matrix, &scene.translations) {
+        // Los cuerpos marcados con always_render nunca se descartan por visibilidad
+        if !(body_in_view(scene, meshes, frame, i)) {
+            continue;
+        }
+
+        // Fuera del límite de cuerpos por frame (max_visible_bodies)
+        if !scene.is_selected(i) {
+            continue;
+        }
+
+        if focused == Some(i) && settings.focus_antialiasing.is_some() {
+            continue;
+        }
+        render_body(framebuffer, scene, meshes, frame, i);
+    }
+
+    // El cuerpo enfocado va después de los demás para que los bordes suavizados se mezclen
+    // con lo que ya está detrás
+    if let (Some(body), Some(factor)) = (focused, settings.focus_antialiasing) {
+        let visible = body_in_view(scene, meshes, frame, body);
+        if visible && !render_body_supersampled(framebuffer, scene, meshes, frame, body, factor) {
+            render_body(framebuffer, scene, meshes, frame, body);
+        }
+    }
+
+    // Núcleos de los cometas
+    for comet in &scene.comets {
+        let comet_translation = comet.position_at(elapsed_time);
+        let comet_size = scene
+            .comet_twinkle
+            .scale_at(comet.size, elapsed_time + comet.phase);
+        if !frame.is_visible(
+            &comet_translation,
+            calculate_sphere_radius(&meshes.comet) * comet_size,
+        ) {
+            continue;
+        }
+
+        let comet_model_matrix =
+            create_model_matrix(comet_translation, comet_size, Vec3::new(0.0, 0.0, 0.0));
+
+        // El núcleo ondula: sus vértices se desplazan con ruido animado
+        let mut comet_uniforms =
+            frame.uniforms(comet_model_matrix, Arc::clone(&scene.satellite_noise));
+        comet_uniforms.displacement = scene.comet_wobble;
+        render(
+            framebuffer,
+            &comet_uniforms,
+            &meshes.comet,
+            comet_shader,
+            BlendMode::Opaque,
+        );
+    }
+
+    // Después de los cuerpos para que la prueba de profundidad los deje tapar la cuadrícula
+    if let Some(grid) = settings.ecliptic_grid {
+        render_ecliptic_grid(framebuffer, &grid, frame);
+    }
+
+    if settings.show_water {
+        render_water(framebuffer, scene, meshes, frame, water);
+    }
+
+    if settings.show_bounds {
+        render_body_bounds(framebuffer, scene, meshes, frame);
+    }
+}
+
+// Aristas de la caja envolvente de cada cuerpo: verde si pasó el descarte por visibilidad
+// (y el límite de cuerpos), rojo si no se dibujó
+fn render_body_bounds(
+    framebuffer: &mut Framebuffer,
+    scene: &Scene,
+    meshes: &Meshes,
+    frame: &FrameContext,
+) {
+    let width = framebuffer.width as f32;
+    let height = framebuffer.height as f32;
+    // Mismo tope que la cuadrícula para no recorrer aristas que caen muy lejos de la ventana
+    let max_coordinate = 4.0 * width.max(height);
+
+    for body in 0..scene.translations.len() {
+        let drawn = (body_in_view(scene, meshes, frame, body)) && scene.is_selected(body);
+        let color = if drawn {
+            color::Color::new(60, 255, 90)
+        } else {
+            color::Color::new(255, 60, 60)
+        };
+
+        let corners = body_bounds(scene, meshes, body, frame.elapsed_time)
+            .corners()
+            .map(|corner| {
+                project_point(
+                    &corner,
+                    &frame.view_matrix,
+                    &frame.projection_matrix,
+                    width,
+                    height,
+                )
+                .filter(|screen| screen.x.abs() < max_coordinate && screen.y.abs() < max_coordinate)
+            });
+
+        for (start, end) in AABB_EDGES {
+            if let (Some(start), Some(end)) = (corners[start], corners[end]) {
+                framebuffer.draw_line_depth(start, end, color, 1.0);
+            }
+        }
+    }
+}
+
+fn render_water(
+    framebuffer: &mut Framebuffer,
+    scene: &Scene,
+    meshes: &Meshes,
+    frame: &FrameContext,
+    water: &mut WaterPlane,
+) {
+    let reflected_frame = FrameContext {
+        view_matrix: frame.view_matrix * create_reflection_matrix(water.height),
+        ..*frame
+    };
+    water.reflection.clear();
+
+    for i in 0..scene.translations.len() {
+        // Solo se refleja lo que queda por completo encima del plano (la Luna incluida)
+        if body_bounds(scene, meshes, i, frame.elapsed_time).min.z <= water.height {
+            continue;
+        }
+        render_body(&mut water.reflection, scene, meshes, &reflected_frame, i);
+    }
 
-                let uniforms = Uniforms {
-                    model_matrix,
-                    view_matrix,
-                    projection_matrix,
-                    viewport_matrix,
-                    time: elapsed_time as u32,
-                    noise,
+    render_reflective_plane(
+        framebuffer,
+        &frame.uniforms(Mat4::identity(), Arc::new(create_noise())),
+        &water.vertices,
+        &water.reflection,
+        water.color,
+        water.reflectivity,
+    );
+}
+
+// Colas de los cometas y partículas, después de todo lo opaco
+fn render_transparent_pass(
+    framebuffer: &mut Framebuffer,
+    scene: &Scene,
+    meshes: &Meshes,
+    frame: &FrameContext,
+    settings: &RenderSettings,
+) {
+    let elapsed_time = frame.elapsed_time;
+
+    // Atmósferas: capas translúcidas alrededor de algunos planetas
+    let atmospheres: Vec<TransparentDraw> = (0..scene.translations.len())
+        .filter(|&i| body_in_view(scene, meshes, frame, i))
+        .filter(|&i| scene.is_selected(i))
+        .filter_map(|i| {
+            let atmosphere = scene.atmospheres[i]?;
+            let model_matrix = create_model_matrix(
+                scene.translations[i],
+                scene.scales[i] * atmosphere.scale,
+                scene.rotations[i],
+            );
+            let mut uniforms = frame.uniforms(model_matrix, Arc::clone(&scene.noises[i]));
+            uniforms.atmosphere = Some(atmosphere);
+            Some(TransparentDraw {
+                uniforms,
+                vertices: &meshes.sphere,
+                shader: atmosphere_shader,
+            })
+        })
+        .collect();
+    render_transparent(framebuffer, &atmospheres, settings.depth_peeling);
+
+    for trail in &scene.comet_trails {
+        render_comet_trail(framebuffer, trail, frame);
+    }
+
+    for comet in &scene.comets {
+        let comet_translation = comet.position_at(elapsed_time);
+        // La cola sale del núcleo y mide `comet.size * 6.0`: la esfera que la contiene está
+        // centrada en el núcleo
+        if !frame.is_visible(
+            &comet_translation,
+            calculate_sphere_radius(&meshes.comet) * comet.size * 6.0,
+        ) {
+            continue;
+        }
+
+        // Cola: una esfera estirada que apunta en dirección contraria al Sol
+        let comet_size = scene
+            .comet_twinkle
+            .scale_at(comet.size, elapsed_time + comet.phase);
+        let tail_model_matrix = create_tail_model_matrix(
+            comet_translation,
+            comet.tail_direction(elapsed_time),
+            comet.size * 6.0,
+            comet_size * 0.6,
+        );
+
+        render(
+            framebuffer,
+            &frame.uniforms(tail_model_matrix, Arc::clone(&scene.satellite_noise)),
+            &meshes.comet,
+            comet_shader,
+            BlendMode::Additive,
+        );
+    }
+
+    render_particles(
+        framebuffer,
+        &scene.comet_particles,
+        &GLOW,
+        &frame.view_matrix,
+        &frame.projection_matrix,
+    );
+}
+
+fn render_post_process_pass(
+    framebuffer: &mut Framebuffer,
+    scene: &Scene,
+    frame: &FrameContext,
+    settings: &RenderSettings,
+) {
+    if settings.god_rays_enabled {
+        if let Some(sun_screen_pos) = project_to_screen(
+            &Vec3::new(0.0, 0.0, 0.0),
+            &frame.view_matrix,
+            &frame.projection_matrix,
+            framebuffer.width as f32,
+            framebuffer.height as f32,
+        ) {
+            framebuffer.apply_god_rays(sun_screen_pos, 0.95, 0.8);
+        }
+    }
+
+    // Después de los god rays para que el halo no alimente el umbral de brillo
+    if let Some(halo) = settings.sun_halo {
+        render_sun_halo(framebuffer, scene, frame, &halo);
+    }
+
+    // Efecto de lente: al final, sobre la imagen con todos los demás efectos
+    if let Some(strength) = settings.chromatic_aberration {
+        framebuffer.apply_chromatic_aberration(strength);
+    }
+
+    // La paleta retro va después de todo para que ningún efecto agregue colores fuera de ella
+    if let Some(palette) = &settings.retro_palette {
+        framebuffer.quantize_to_palette(palette);
+    }
+}
+
+fn render_sun_halo(
+    framebuffer: &mut Framebuffer,
+    scene: &Scene,
+    frame: &FrameContext,
+    halo: &SunHalo,
+) {
+    let width = framebuffer.width as f32;
+    let height = framebuffer.height as f32;
+    let sun_position = Vec3::new(0.0, 0.0, 0.0);
+    let sun_radius =
+        scene.scale_animations[SUN].scale_at(scene.scales[SUN] * 1.5, frame.elapsed_time);
+
+    let Some(center) = project_to_screen(
+        &sun_position,
+        &frame.view_matrix,
+        &frame.projection_matrix,
+        width,
+        height,
+    ) else {
+        return;
+    };
+
+    // Radio en pantalla: proyectar un punto del borde del Sol perpendicular a la vista
+    let to_camera = frame.camera_eye - sun_position;
+    if to_camera.norm() <= sun_radius {
+        return;
+    }
+    let to_camera = to_camera.normalize();
+    let side = nalgebra_glm::normalize(&to_camera.cross(&Vec3::new(0.0, 0.0, 1.0)));
+    let side = if side.x.is_finite() {
+        side
+    } else {
+        Vec3::new(1.0, 0.0, 0.0)
+    };
+    let Some(edge) = project_point(
+        &(sun_position + side * sun_radius),
+        &frame.view_matrix,
+        &frame.projection_matrix,
+        width,
+        height,
+    ) else {
+        return;
+    };
+    let screen_radius = nalgebra_glm::distance(&Vec2::new(edge.x, edge.y), &center);
+
+    // Si lo que hay en el z-buffer en el centro del Sol está claramente delante de su
+    // superficie, un planeta lo tapa y no se dibuja el halo
+    let Some(occlusion_limit) = project_point(
+        &(sun_position + to_camera * sun_radius * 1.1),
+        &frame.view_matrix,
+        &frame.projection_matrix,
+        width,
+        height,
+    ) else {
+        return;
+    };
+    if framebuffer.depth_at(center.x as usize, center.y as usize) < occlusion_limit.z {
+        return;
+    }
+
+    framebuffer.draw_halo(
+        center,
+        screen_radius * halo.scale,
+        halo.color,
+        halo.intensity,
+    );
+}
+
+const THUMBNAIL_SIZE: usize = 64;
+
+// Renderiza solo el cuerpo `body` en una imagen de `size` x `size`, con una cámara que lo
+// encuadra completo sin importar su escala. El fondo queda en `Color::black()` para que se
+// pueda tratar como transparente al dibujar la miniatura.
+fn render_thumbnail(scene: &Scene, meshes: &Meshes, body: usize, size: usize) -> Vec<color::Color> {
+    let mut framebuffer = Framebuffer::new(size, size);
+    framebuffer.set_background_color(color::Color::black());
+    framebuffer.clear();
+
+    let (vertices, scale, shader): (_, _, Shader) = match body {
+        SUN => (&meshes.models[body], scene.scales[body] * 1.5, sun_shader),
+        4 => (
+            &meshes.models[body],
+            scene.scales[body] * 0.7,
+            scene.shaders[body],
+        ),
+        _ => (
+            &meshes.models[body],
+            scene.scales[body],
+            scene.shaders[body],
+        ),
+    };
+
+    // Radio de la esfera que envuelve la malla ya escalada
+    let bounding_radius = vertices
+        .iter()
+        .map(|vertex| vertex.position.norm())
+        .fold(0.0, f32::max)
+        * scale;
+
+    // Distancia a la que la esfera cabe en el campo de visión (45°), con un pequeño margen
+    let distance = bounding_radius / (FIELD_OF_VIEW * 0.5).sin() * 1.1;
+    let center = scene.translations[body];
+    let eye = center + Vec3::new(0.0, -1.0, 0.5).normalize() * distance;
+
+    let frame = FrameContext {
+        camera_eye: eye,
+        sun_position: scene.translations[SUN],
+        view_matrix: create_view_matrix(eye, center, Vec3::new(0.0, 0.0, 1.0)),
+        projection_matrix: create_perspective_matrix(size as f32, size as f32, 1.0),
+        viewport_matrix: create_viewport_matrix(size as f32, size as f32),
+        elapsed_time: 0.0,
+        seed: DEFAULT_SEED,
+        albedo: false,
+        cull_backfaces: true,
+        linear_textures: false,
+    };
+
+    let model_matrix = create_model_matrix(center, scale, scene.rotations[body]);
+    render(
+        &mut framebuffer,
+        &frame.material_uniforms(
+            model_matrix,
+            Arc::clone(&scene.noises[body]),
+            scene.materials[body],
+        ),
+        vertices,
+        shader,
+        BlendMode::Opaque,
+    );
+
+    framebuffer.buffer
+}
+
+// Miniaturas de todos los cuerpos en la parte inferior; la del cuerpo enfocado va resaltada
+fn render_thumbnail_strip(
+    framebuffer: &mut Framebuffer,
+    thumbnails: &[Vec<color::Color>],
+    focused: usize,
+) {
+    let spacing = 8;
+    let strip_width = thumbnails.len() * (THUMBNAIL_SIZE + spacing) + spacing;
+    let strip_x = framebuffer.width.saturating_sub(strip_width) / 2;
+    let strip_y = framebuffer
+        .height
+        .saturating_sub(THUMBNAIL_SIZE + spacing * 3);
+
+    framebuffer.fill_rect_alpha(
+        strip_x,
+        strip_y,
+        strip_width,
+        THUMBNAIL_SIZE + spacing * 2,
+        color::Color::new(10, 10, 30),
+        0.6,
+    );
+
+    for (i, thumbnail) in thumbnails.iter().enumerate() {
+        let x = strip_x + spacing + i * (THUMBNAIL_SIZE + spacing);
+        let y = strip_y + spacing;
+
+        if i == focused {
+            framebuffer.fill_rect_alpha(
+                x - 2,
+                y - 2,
+                THUMBNAIL_SIZE + 4,
+                THUMBNAIL_SIZE + 4,
+                color::Color::new(255, 200, 120),
+                0.8,
+            );
+        }
+
+        // El fondo negro de la miniatura se trata como transparente
+        for (index, pixel) in thumbnail.iter().enumerate() {
+            if !pixel.is_black() {
+                framebuffer.point_with_color(
+                    x + index % THUMBNAIL_SIZE,
+                    y + index / THUMBNAIL_SIZE,
+                    *pixel,
+                );
+            }
+        }
+    }
+}
+
+// HUD y recuadros de depuración: siempre encima de todo
+fn render_overlay_pass(
+    framebuffer: &mut Framebuffer,
+    settings: &RenderSettings,
+    thumbnails: &[Vec<color::Color>],
+    focused: usize,
+) {
+    // Recuadro con el mapa de profundidad en la esquina superior derecha
+    if settings.depth_view == DepthView::Inset {
+        let inset_width = framebuffer.width / 4;
+        let inset_height = framebuffer.height / 4;
+        let heatmap = framebuffer.depth_heatmap(inset_width, inset_height);
+        let inset_x = framebuffer.width - inset_width - 10;
+        framebuffer.blit(inset_x, 10, inset_width, inset_height, &heatmap);
+    }
+
+    if settings.show_thumbnails {
+        render_thumbnail_strip(framebuffer, thumbnails, focused);
+    }
+
+    if settings.show_help {
+        render_help(framebuffer);
+    }
+}
+
+// Panel semitransparente con todos los controles, generado desde `controls`
+fn render_help(framebuffer: &mut Framebuffer) {
+    let padding = 12;
+    let column_gap = 24;
+
+    let lines: Vec<(&str, &str)> = controls::TOGGLES
+        .iter()
+        .chain(controls::ADJUSTMENTS.iter())
+        .map(|binding| (binding.label, binding.description))
+        .chain(controls::CONTINUOUS)
+        .collect();
+
+    // Dos columnas: la lista completa ya no cabe en la altura de la ventana
+    let rows = lines.len().div_ceil(2);
+    let column_width = |scale: usize| {
+        let label_width = font::text_width("CLIC DER.", scale) + 16;
+        lines
+            .iter()
+            .map(|(_, description)| label_width + font::text_width(description, scale))
+            .max()
+            .unwrap_or(0)
+    };
+    let panel_width = |scale: usize| column_width(scale) * 2 + column_gap + padding * 2;
+
+    // Texto a escala 2 si el panel cabe en el framebuffer (puede ser más chico que la ventana)
+    let scale = if panel_width(2) <= framebuffer.width {
+        2
+    } else {
+        1
+    };
+    let line_height = font::text_height(scale) + 6;
+    let label_width = font::text_width("CLIC DER.", scale) + 16;
+    let column_width = column_width(scale);
+    let panel_width = panel_width(scale);
+    let panel_height = (rows + 2) * line_height + padding * 2;
+    let panel_x = framebuffer.width.saturating_sub(panel_width) / 2;
+    let panel_y = framebuffer.height.saturating_sub(panel_height) / 2;
+
+    framebuffer.fill_rect_alpha(
+        panel_x,
+        panel_y,
+        panel_width,
+        panel_height,
+        color::Color::new(10, 10, 30),
+        0.75,
+    );
+
+    let left = panel_x + padding;
+    let top = panel_y + padding;
+    framebuffer.draw_text(
+        left,
+        top,
+        "CONTROLES",
+        color::Color::new(255, 200, 120),
+        scale,
+    );
+
+    for (i, (label, description)) in lines.into_iter().enumerate() {
+        let x = left + (i / rows) * (column_width + column_gap);
+        let y = top + (i % rows + 2) * line_height;
+        framebuffer.draw_text(x, y, label, color::Color::new(255, 200, 120), scale);
+        framebuffer.draw_text(
+            x + label_width,
+            y,
+            description,
+            color::Color::new(230, 230, 230),
+            scale,
+        );
+    }
+}
+
+// Ejecuta todas las pasadas en orden. `overlay` (miniaturas y cuerpo enfocado) es None
+// cuando no hay interfaz, p. ej. al grabar un GIF; sin cuerpo enfocado tampoco hay
+// supermuestreo del cuerpo enfocado.
+fn render_passes(
+    framebuffer: &mut Framebuffer,
+    scene: &Scene,
+    meshes: &Meshes,
+    frame: &FrameContext,
+    settings: &RenderSettings,
+    water: &mut WaterPlane,
+    overlay: Option<(&[Vec<color::Color>], usize)>,
+) {
+    for pass in RENDER_PASSES {
+        match pass {
+            RenderPass::Opaque => {
+                let focused = overlay.map(|(_, focused)| focused);
+                render_opaque_pass(framebuffer, scene, meshes, frame, settings, water, focused)
+            }
+            // En modo albedo no se agregan atmósferas ni efectos sobre la imagen
+            RenderPass::Transparent if !settings.albedo => {
+                render_transparent_pass(framebuffer, scene, meshes, frame, settings)
+            }
+            RenderPass::PostProcess if !settings.albedo => {
+                render_post_process_pass(framebuffer, scene, frame, settings)
+            }
+            RenderPass::Transparent | RenderPass::PostProcess => {}
+            RenderPass::Overlay => {
+                // Mapa de sobredibujo (si se está contando) en lugar de la imagen final
+                framebuffer.show_overdraw();
+
+                if let Some((thumbnails, focused)) = overlay {
+                    render_overlay_pass(framebuffer, settings, thumbnails, focused);
+                }
+            }
+        }
+    }
+}
+
+// Título de la ventana con el FPS objetivo y los frames que no cumplieron el presupuesto
+fn window_title(pacer: &FramePacer) -> String {
+    format!(
+        "Sistema Solar - Proyecto Final | {} FPS objetivo | {} de {} frames perdidos",
+        pacer.target_fps(),
+        pacer.dropped_frames,
+        pacer.frames
+    )
+}
+
+fn main() {
+    let window_width = 1300;
+    let window_height = 600;
+
+    // Resolución interna: se renderiza a window * render_scale y al presentar se escala a la
+    // ventana (p. ej. 0.5 para un aspecto pixelado)
+    let render_scale = parse_arg("--render-scale", "un número positivo")
+        .filter(|scale: &f32| *scale > 0.0)
+        .unwrap_or(DEFAULT_RENDER_SCALE);
+    let framebuffer_width = ((window_width as f32 * render_scale).round() as usize).max(1);
+    let framebuffer_height = ((window_height as f32 * render_scale).round() as usize).max(1);
+
+    // Crear el framebuffer; con `--supersample N` se dibuja a N veces la resolución en cada
+    // eje y se promedia al presentar (bordes suavizados a costa de N² veces más píxeles)
+    let supersample = parse_arg("--supersample", "un número entero positivo")
+        .filter(|samples: &usize| *samples > 0)
+        .unwrap_or(1);
+    let mut framebuffer =
+        Framebuffer::new_with_scale(framebuffer_width, framebuffer_height, supersample);
+
+    framebuffer.set_background_color(0x333355);
+    // Corrección de gamma al presentar (`--gamma 2.2`); sin el argumento los colores se
+    // muestran tal como los escriben los shaders
+    framebuffer.set_gamma(parse_arg("--gamma", "un número positivo"));
+
+    let comet_count = parse_arg("--comets", "un número entero").unwrap_or(DEFAULT_COMET_COUNT);
+    let trail_length = 240;
+    let substeps = parse_arg("--substeps", "un número entero positivo")
+        .filter(|substeps: &u32| *substeps > 0)
+        .unwrap_or(DEFAULT_SUBSTEPS);
+    let pixel_aspect = parse_arg("--pixel-aspect", "un número positivo")
+        .filter(|aspect: &f32| *aspect > 0.0)
+        .unwrap_or(DEFAULT_PIXEL_ASPECT);
+    let seed = parse_arg("--seed", "un número entero positivo").unwrap_or(DEFAULT_SEED);
+    let scene_config = match parse_arg::<String>("--scene", "la ruta del archivo .json") {
+        Some(path) => SceneConfig::load(&path).unwrap_or_else(|err| {
+            eprintln!("No se pudo cargar la escena {}: {}", path, err);
+            std::process::exit(1);
+        }),
+        None => SceneConfig::default(),
+    };
+    let mut scene = Scene::new(&scene_config, comet_count, trail_length, substeps, seed);
+    // Fondo de cubemap (`--skybox directorio` con px, nx, py, ny, pz y nz); sin él se usa la
+    // imagen plana de siempre
+    let skybox = parse_arg::<String>("--skybox", "el directorio del cubemap").map(|path| {
+        Cubemap::load(&path).unwrap_or_else(|err| {
+            eprintln!("No se pudo cargar el cubemap {}: {}", path, err);
+            std::process::exit(1);
+        })
+    });
+
+    // Estelas de estrellas de los primeros segundos ("salto al sistema")
+    let warp_intro = WarpIntro::new(400, 2.5, seed);
+
+    // Estrellas suavizadas sobre la imagen de fondo
+    let mut starfield = Starfield::new(300, seed);
+    // El radio se da en píxeles de la ventana; con supersampling cada uno tiene más muestras
+    starfield.star_size = parse_arg("--star-size", "un número positivo")
+        .filter(|size: &f32| *size > 0.0)
+        .unwrap_or(DEFAULT_STAR_SIZE)
+        * supersample as f32;
+    starfield.flicker = Some(0.35); // None: brillo fijo
+    scene.orbit_spacing = ORBIT_SPACING; // Aumentar (p. ej. a 2.5) para separar los planetas interiores
+    scene.max_visible_bodies = None; // Some(5): solo los 5 cuerpos más grandes en pantalla
+
+    // OBJs
+
+    //Luego hacer un array de modelos para manejar planetas, estrellas, etc.
+    let sphere = load_vertex_array(DEFAULT_MODEL);
+    // Cada OBJ se carga una sola vez aunque lo compartan varios cuerpos
+    let mut loaded_models: HashMap<&str, (Vec<Vertex>, MaterialTextures)> = HashMap::new();
+    let (models, model_textures): (Vec<Vec<Vertex>>, Vec<MaterialTextures>) = scene_config
+        .planets
+        .iter()
+        .map(|planet| {
+            let (vertices, textures) = loaded_models
+                .entry(planet.model_path())
+                .or_insert_with(|| load_model(planet.model_path()));
+            (vertices.clone(), Arc::clone(textures))
+        })
+        .unzip();
+    // Esfera por latitud/longitud: más densa que sphere.obj para que el relieve de los
+    // cráteres se vea en la silueta
+    let moon = mesh::uv_sphere(32, 64);
+    let meshes = Meshes {
+        model_bounds: models
+            .iter()
+            .map(|model| Aabb::from_vertices(model))
+            .collect(),
+        moon_bounds: Aabb::from_vertices(&moon),
+        sphere,
+        models,
+        model_textures,
+        moon,
+        comet: load_vertex_array(DEFAULT_MODEL),
+        // OBJ de mi nave
+        tie_fighter: load_vertex_array("assets/models/tiefighter.obj"),
+    };
+
+    // Tiempo de simulación: suma de los delta_time ya limitados, así una pausa larga (mover la
+    // ventana, exportar) no hace que los planetas salten en su órbita
+    let mut elapsed_time = 0.0;
+    // Reloj de la simulación (órbitas, rotaciones y shaders): avanza `time_scale` veces más
+    // rápido que `elapsed_time` y se detiene en pausa. La cámara, la nave y los efectos de
+    // interfaz siguen con el tiempo real
+    let mut simulation_time = 0.0;
+    let mut time_scale = 1.0;
+    let mut paused = false;
+    // Miniaturas de cada cuerpo para la barra de selección (se generan una sola vez)
+    let thumbnails: Vec<Vec<color::Color>> = (0..scene.translations.len())
+        .map(|body| render_thumbnail(&scene, &meshes, body, THUMBNAIL_SIZE))
+        .collect();
+
+    let mut last_mouse_pos = (0.0, 0.0);
+    // Dónde se presionó el clic izquierdo en vuelo libre, para distinguir un clic de un arrastre
+    let mut click_start: Option<(f32, f32)> = None;
+
+    // Configuración de la cámara
+    let mut camera = Camera::new(
+        Vec3::new(0.0, 0.0, 5.0), // Posición inicial de la cámara
+        Vec3::new(0.0, 0.0, 0.0), // Centro (Sol)
+        Vec3::new(0.0, 1.0, 0.0), // Vector "arriba"
+    );
+
+    let mut should_update_camera_target = false;
+    // Vuelo hacia el planeta elegido con 1-7; Some mientras dura
+    let mut camera_transition: Option<CameraTransition> = None;
+
+    // Índice del planeta seleccionado
+    let mut current_camera_target = 0;
+    // Objetivo del frame anterior, para detectar el cambio que dispara la transición
+    let mut previous_camera_target = current_camera_target;
+    let mut transition: Option<ScanTransition> = None;
+
+    let mut zoom_factor = 3.0; // Zoom inicial
+
+    // Cámara cinematográfica: radio, altura sobre la eclíptica y velocidad angular (rad/s)
+    let mut cinematic_orbit = CinematicOrbit::new(30.0, 12.0, 0.15);
+    let mut camera_mode = CameraMode::ChaseShip;
+    // Tiempo de reproducción del recorrido de cámara; Some mientras se reproduce
+    let mut camera_path_time: Option<f32> = None;
+
+    // Con el bloqueo activo la cámara que sigue a la nave ignora su alabeo
+    let mut lock_camera_up = false;
+
+    // Estado previo al modo foto; Some mientras está activo
+    let mut photo_mode_backup: Option<PhotoModeBackup> = None;
+    let photo_mode_hides_ship = true;
+
+    let mut last_frame_time = Instant::now();
+
+    let sun_halo = SunHalo {
+        scale: 3.0,
+        color: color::Color::new(255, 200, 120),
+        intensity: 0.6,
+    };
+    let ecliptic_grid = EclipticGrid {
+        spacing: 2.0,
+        extent: 30.0,
+        color: color::Color::new(90, 140, 200),
+        alpha: 0.5,
+    };
+    let chromatic_aberration_strength = 4.0;
+    let scan_transition_duration = 0.4;
+    let retro_palette = match parse_arg::<String>("--palette", "la ruta del archivo .json") {
+        Some(path) => load_palette(&path).unwrap_or_else(|err| {
+            eprintln!("No se pudo cargar la paleta {}: {}", path, err);
+            std::process::exit(1);
+        }),
+        None => EGA_PALETTE.map(color::Color::from_hex).to_vec(),
+    };
+    let focus_supersampling = 4; // 4x4 muestras por píxel
+    let mut settings = RenderSettings {
+        show_orbits: true,
+        show_ship: true,
+        show_trails: false,
+        show_water: false,
+        god_rays_enabled: true,
+        depth_view: DepthView::Hidden,
+        show_bounds: false,
+        show_overdraw: false,
+        show_help: false,
+        show_thumbnails: false,
+        focus_antialiasing: None,
+        depth_peeling: true,
+        sun_halo: Some(sun_halo),
+        ecliptic_grid: None,
+        chromatic_aberration: None,
+        albedo: false,
+        cull_backfaces: true,
+        retro_palette: None,
+        scan_transition: Some(scan_transition_duration),
+    };
+
+    let water_height = -4.0;
+    let mut water = WaterPlane {
+        height: water_height,
+        vertices: create_plane_vertices(water_height, 40.0),
+        color: color::Color::new(20, 50, 90),
+        reflectivity: 0.6,
+        reflection: Framebuffer::new(framebuffer.width, framebuffer.height),
+    };
+
+    // Con `--camera-path` explícito el GIF también sigue el recorrido
+    let camera_path_arg = parse_arg::<String>("--camera-path", "la ruta del archivo .json");
+    let camera_path_file = camera_path_arg
+        .clone()
+        .unwrap_or_else(|| DEFAULT_CAMERA_PATH.to_string());
+    let camera_path = match CameraPath::load(&camera_path_file) {
+        Ok(path) => Some(path),
+        Err(err) => {
+            eprintln!(
+                "No se pudo cargar el recorrido {}: {}",
+                camera_path_file, err
+            );
+            None
+        }
+    };
+
+    // Modo sin ventana: graba `--frames` frames con un dt fijo en un GIF y termina
+    if let Some(gif_path) = parse_arg::<String>("--gif", "la ruta del archivo .gif") {
+        let frames =
+            parse_arg("--frames", "un número entero positivo").unwrap_or(DEFAULT_GIF_FRAMES);
+        let fps = parse_arg("--fps", "un número entero positivo")
+            .filter(|fps: &u32| *fps > 0)
+            .unwrap_or(DEFAULT_GIF_FPS);
+
+        // Vista fija y elevada de todo el sistema, o el recorrido si se indicó uno
+        let gif_path_camera = camera_path.as_ref().filter(|_| camera_path_arg.is_some());
+        let mut gif_camera = Camera::new(
+            Vec3::new(30.0, 0.0, 12.0),
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        );
+        let frame_time = 1.0 / fps as f32;
+
+        let result = GifRecorder::create(&gif_path, fps).and_then(|mut recorder| {
+            for index in 0..frames {
+                let elapsed_time = index as f32 * frame_time;
+                if let Some(path) = gif_path_camera {
+                    path.apply(&mut gif_camera, elapsed_time);
+                }
+                scene.update(elapsed_time, settings.show_trails);
+
+                let frame = FrameContext {
+                    camera_eye: gif_camera.eye,
+                    sun_position: scene.translations[SUN],
+                    view_matrix: create_view_matrix(
+                        gif_camera.eye,
+                        gif_camera.center,
+                        gif_camera.up,
+                    ),
+                    projection_matrix: create_perspective_matrix(
+                        framebuffer_width as f32,
+                        framebuffer_height as f32,
+                        pixel_aspect,
+                    ),
+                    viewport_matrix: create_viewport_matrix(
+                        framebuffer.width as f32,
+                        framebuffer.height as f32,
+                    ),
+                    elapsed_time,
+                    seed,
+                    albedo: settings.albedo,
+                    cull_backfaces: settings.cull_backfaces,
+                    linear_textures: framebuffer.is_linear(),
                 };
 
-                if i == 1 && is_visible(&translations[i], &view_matrix, &projection_matrix) {
-                    // Renderizar Neon con el shader que usa el mapa normal
-                    render(
-                        &mut framebuffer,
-                        &uniforms,
-                        &vertex_arrays,
-                        neon_normal_map_shader,
-                    );
-                } else if i == 4 && is_visible(&translations[i], &view_matrix, &projection_matrix) {
-                    // Renderizar el anillo adicional para el planeta con ID 4 (Saturno)
-                    let ring_model_matrix = create_model_matrix(
-                        translations[i], // Posición igual al planeta
-                        scales[i] * 0.7, // Escala ajustada (1.5 veces el tamaño del planeta)
-                        rotations[i],    // Rotación igual al planeta
-                    );
+                framebuffer.clear();
+                draw_background(&mut framebuffer, skybox.as_ref(), &frame);
+                starfield.render(&mut framebuffer, elapsed_time);
 
-                    let noise_ring = create_noise_for_planet(i);
+                select_visible_bodies(&mut scene, &meshes, &frame, &framebuffer, None);
+                render_passes(
+                    &mut framebuffer,
+                    &scene,
+                    &meshes,
+                    &frame,
+                    &settings,
+                    &mut water,
+                    None,
+                );
 
-                    let ring_uniforms = Uniforms {
-                        model_matrix: ring_model_matrix, // Matriz específica del anillo
-                        view_matrix,
-                        projection_matrix,
-                        viewport_matrix,
-                        time: elapsed_time as u32,
-                        noise: noise_ring,
-                    };
+                recorder.add_frame(&framebuffer)?;
+                println!("Frame {}/{}", index + 1, frames);
+            }
+            Ok(())
+        });
+
+        match result {
+            Ok(()) => println!("GIF guardado en {}", gif_path),
+            Err(err) => {
+                eprintln!("No se pudo guardar el GIF en {}: {}", gif_path, err);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
 
-                    render(
-                        &mut framebuffer,
-                        &ring_uniforms,
-                        &vertex_arrays_ring,
-                        shaders[i],
-                    );
-                } else if i == 6 && is_visible(&translations[i], &view_matrix, &projection_matrix) {
-                    // Renderizar la Tierra
-                    render(&mut framebuffer, &uniforms, &vertex_arrays, earth);
-
-                    // Calcular la órbita de la luna
-                    let moon_orbit_radius = 0.7; // Radio de la órbita
-                    let moon_speed = 0.5; // Velocidad de la órbita
-                    let moon_angle = elapsed_time * moon_speed;
-
-                    let moon_x = translations[i].x + moon_orbit_radius * moon_angle.cos();
-                    let moon_y = translations[i].y + moon_orbit_radius * moon_angle.sin();
-
-                    let moon_translation = Vec3::new(moon_x, moon_y, 0.0);
-                    let moon_model_matrix =
-                        create_model_matrix(moon_translation, scales[i] * 0.3, rotations[i]);
-
-                    let moon_uniforms = Uniforms {
-                        model_matrix: moon_model_matrix,
-                        view_matrix,
-                        projection_matrix,
-                        viewport_matrix,
-                        time: elapsed_time as u32,
-                        noise: create_noise_for_planet(7),
-                    };
+    let target_fps = parse_arg("--target-fps", "un número entero positivo")
+        .filter(|fps: &u32| *fps > 0)
+        .unwrap_or(DEFAULT_TARGET_FPS);
+    let mut pacer = FramePacer::new(target_fps);
 
-                    // Renderizar la Luna
-                    render(
-                        &mut framebuffer,
-                        &moon_uniforms,
-                        &vertex_arrays_moon,
-                        luna_shader,
-                    );
-                } else if i == 7 && is_visible(&translations[i], &view_matrix, &projection_matrix) {
-                    // Renderizar el cometa
-                    let comet_x = elapsed_time.sin() * 4.0; // Movimiento en el eje X
-                    let comet_y = elapsed_time.cos() * 2.0; // Movimiento en el eje Y
-                    let comet_translation = Vec3::new(comet_x, comet_y, 0.0);
-
-                    let comet_model_matrix =
-                        create_model_matrix(comet_translation, 0.2, Vec3::new(0.0, 0.0, 0.0));
-
-                    let comet_uniforms = Uniforms {
-                        model_matrix: comet_model_matrix,
-                        view_matrix,
-                        projection_matrix,
-                        viewport_matrix,
-                        time: elapsed_time as u32,
-                        noise: create_noise_for_planet(i),
-                    };
+    let mut window = Window::new(
+        &window_title(&pacer),
+        window_width,
+        window_height,
+        WindowOptions {
+            // Si el framebuffer no mide lo mismo que la ventana se escala y se centra
+            scale_mode: ScaleMode::AspectRatioStretch,
+            ..WindowOptions::default()
+        },
+    )
+    .unwrap();
 
-                    render(
-                        &mut framebuffer,
-                        &comet_uniforms,
-                        &vertex_arrays_comet,
-                        comet_shader,
-                    );
-                } else if i == 2 && is_visible(&translations[i], &view_matrix, &projection_matrix) {
-                    // Renderizar el Sol
-                    let sun_translation = Vec3::new(0.0, 0.0, 0.0);
-                    let sun_model_matrix = create_model_matrix(
-                        sun_translation,
-                        scales[i] * 1.5,
-                        Vec3::new(0.0, 0.0, 0.0),
-                    );
+    window.set_position(500, 500);
+    window.update();
 
-                    let sun_uniforms = Uniforms {
-                        model_matrix: sun_model_matrix,
-                        view_matrix,
-                        projection_matrix,
-                        viewport_matrix,
-                        time: elapsed_time as u32,
-                        noise: create_noise_for_planet(i),
-                    };
+    let mut session_stats = SessionStats::new();
+
+    while window.is_open() {
+        if window.is_key_down(Key::Escape) {
+            println!("{}", session_stats.report());
+            break;
+        }
+
+        let delta_time = last_frame_time.elapsed().as_secs_f32().min(MAX_FRAME_TIME);
+        elapsed_time += delta_time;
+        if !paused {
+            simulation_time += delta_time * time_scale;
+        }
+        last_frame_time = Instant::now();
+
+        if window.is_key_pressed(controls::PHOTO_MODE.key, KeyRepeat::No) {
+            photo_mode_backup = match photo_mode_backup.take() {
+                Some(backup) => {
+                    settings.exit_photo_mode(backup);
+                    None
+                }
+                None => Some(settings.enter_photo_mode(photo_mode_hides_ship)),
+            };
+        }
+
+        if window.is_key_pressed(controls::HELP.key, KeyRepeat::No) {
+            settings.show_help = !settings.show_help;
+        }
+
+        if window.is_key_pressed(controls::CAMERA_MODE.key, KeyRepeat::No) {
+            camera_mode = camera_mode.next();
+            // Un vuelo pendiente de otro modo no debe mover la cámara del nuevo
+            camera_transition = None;
+        }
+
+        if window.is_key_pressed(controls::CAMERA_PATH.key, KeyRepeat::No) {
+            camera_path_time = match (camera_path_time, &camera_path) {
+                (None, Some(_)) => Some(0.0),
+                (None, None) => {
+                    println!("No hay recorrido de cámara cargado ({})", camera_path_file);
+                    None
+                }
+                (Some(_), _) => None,
+            };
+        }
+
+        if window.is_key_pressed(controls::LOCK_UP.key, KeyRepeat::No) {
+            lock_camera_up = !lock_camera_up;
+        }
+
+        if window.is_key_pressed(controls::CLIPPING.key, KeyRepeat::No) {
+            framebuffer.frustum_clipping = !framebuffer.frustum_clipping;
+        }
+
+        if window.is_key_pressed(controls::PAINTER.key, KeyRepeat::No) {
+            framebuffer.depth_test = !framebuffer.depth_test;
+        }
+
+        if window.is_key_pressed(controls::GOD_RAYS.key, KeyRepeat::No) {
+            settings.god_rays_enabled = !settings.god_rays_enabled;
+        }
+
+        if window.is_key_pressed(controls::DEPTH_VIEW.key, KeyRepeat::No) {
+            settings.depth_view = settings.depth_view.next();
+        }
+
+        if window.is_key_pressed(controls::TRAILS.key, KeyRepeat::No) {
+            settings.show_trails = !settings.show_trails;
+        }
+
+        if window.is_key_pressed(controls::WATER.key, KeyRepeat::No) {
+            settings.show_water = !settings.show_water;
+        }
+
+        if window.is_key_pressed(controls::SUN_HALO.key, KeyRepeat::No) {
+            settings.sun_halo = match settings.sun_halo {
+                Some(_) => None,
+                None => Some(sun_halo),
+            };
+        }
+
+        if window.is_key_pressed(controls::GRID.key, KeyRepeat::No) {
+            settings.ecliptic_grid = match settings.ecliptic_grid {
+                Some(_) => None,
+                None => Some(ecliptic_grid),
+            };
+        }
+
+        // Activar/desactivar la textura y el mapa normal del cuerpo enfocado
+        if window.is_key_pressed(controls::TEXTURE.key, KeyRepeat::No) {
+            let material = &mut scene.materials[current_camera_target];
+            material.use_texture = !material.use_texture;
+        }
+
+        if window.is_key_pressed(controls::NORMAL_MAP.key, KeyRepeat::No) {
+            let material = &mut scene.materials[current_camera_target];
+            material.use_normal_map = !material.use_normal_map;
+        }
+
+        if window.is_key_pressed(controls::THUMBNAILS.key, KeyRepeat::No) {
+            settings.show_thumbnails = !settings.show_thumbnails;
+        }
+
+        if window.is_key_pressed(controls::CHROMATIC_ABERRATION.key, KeyRepeat::No) {
+            settings.chromatic_aberration = match settings.chromatic_aberration {
+                Some(_) => None,
+                None => Some(chromatic_aberration_strength),
+            };
+        }
+
+        if window.is_key_pressed(controls::FOCUS_ANTIALIASING.key, KeyRepeat::No) {
+            settings.focus_antialiasing = match settings.focus_antialiasing {
+                Some(_) => None,
+                None => Some(focus_supersampling),
+            };
+        }
+
+        if window.is_key_pressed(controls::DEPTH_PEELING.key, KeyRepeat::No) {
+            settings.depth_peeling = !settings.depth_peeling;
+        }
+
+        if window.is_key_pressed(controls::BOUNDS.key, KeyRepeat::No) {
+            settings.show_bounds = !settings.show_bounds;
+        }
+
+        if window.is_key_pressed(controls::BACKFACE_CULLING.key, KeyRepeat::No) {
+            settings.cull_backfaces = !settings.cull_backfaces;
+        }
+
+        if window.is_key_pressed(controls::OVERDRAW.key, KeyRepeat::No) {
+            settings.show_overdraw = !settings.show_overdraw;
+            framebuffer.set_overdraw_counting(settings.show_overdraw);
+        }
+
+        if window.is_key_pressed(controls::RETRO_PALETTE.key, KeyRepeat::No) {
+            settings.retro_palette = match settings.retro_palette {
+                Some(_) => None,
+                None => Some(retro_palette.clone()),
+            };
+        }
+
+        if window.is_key_pressed(controls::SCAN_TRANSITION.key, KeyRepeat::No) {
+            settings.scan_transition = match settings.scan_transition {
+                Some(_) => None,
+                None => Some(scan_transition_duration),
+            };
+            transition = None; // Desactivarla también corta la que esté en curso
+        }
+
+        if window.is_key_pressed(controls::ALBEDO.key, KeyRepeat::No) {
+            settings.albedo = !settings.albedo;
+        }
+
+        let orbit_speed_factor =
+            if window.is_key_pressed(controls::ORBIT_SPEED_UP.key, KeyRepeat::Yes) {
+                Some(ORBIT_SPEED_STEP)
+            } else if window.is_key_pressed(controls::ORBIT_SPEED_DOWN.key, KeyRepeat::Yes) {
+                Some(1.0 / ORBIT_SPEED_STEP)
+            } else {
+                None
+            };
+        if let Some(factor) = orbit_speed_factor {
+            let (min_speed, max_speed) = ORBIT_SPEED_RANGE;
+            scene.orbit_speed = (scene.orbit_speed * factor).clamp(min_speed, max_speed);
+            println!("Velocidad de las órbitas: x{:.2}", scene.orbit_speed);
+        }
+
+        if window.is_key_pressed(controls::PAUSE.key, KeyRepeat::No) {
+            paused = !paused;
+            println!(
+                "Simulación {}",
+                if paused { "en pausa" } else { "reanudada" }
+            );
+        }
+
+        let time_scale_factor = if window.is_key_pressed(controls::TIME_FASTER.key, KeyRepeat::Yes)
+        {
+            Some(TIME_SCALE_STEP)
+        } else if window.is_key_pressed(controls::TIME_SLOWER.key, KeyRepeat::Yes) {
+            Some(1.0 / TIME_SCALE_STEP)
+        } else {
+            None
+        };
+        if let Some(factor) = time_scale_factor {
+            let (min_scale, max_scale) = TIME_SCALE_RANGE;
+            time_scale = (time_scale * factor).clamp(min_scale, max_scale);
+            println!("Escala de tiempo: x{:.3}", time_scale);
+        }
+
+        if window.is_key_pressed(controls::NOISE_TYPE.key, KeyRepeat::No) {
+            let noise_name = scene.cycle_noise(current_camera_target);
+            println!(
+                "Ruido de {}: {}",
+                scene.names[current_camera_target], noise_name
+            );
+        }
+
+        let target_fps_changed = window.is_key_pressed(controls::TARGET_FPS.key, KeyRepeat::No);
+        if target_fps_changed {
+            let next = TARGET_FPS_OPTIONS
+                .iter()
+                .position(|&fps| fps == pacer.target_fps())
+                .map_or(0, |index| (index + 1) % TARGET_FPS_OPTIONS.len());
+            pacer.set_target_fps(TARGET_FPS_OPTIONS[next]);
+        }
+
+        if window.is_key_pressed(controls::DUMP_STATE.key, KeyRepeat::No) {
+            print_render_state(
+                &scene,
+                &camera,
+                current_camera_target,
+                simulation_time,
+                framebuffer_width as f32 * pixel_aspect / framebuffer_height as f32,
+            );
+        }
+
+        if window.is_key_pressed(controls::EXPORT_SCENE.key, KeyRepeat::No) {
+            export_scene(&scene, &camera, SCENE_EXPORT_PATH);
+        }
+
+        // La captura se guarda al final del frame, con todo lo que se va a mostrar
+        let screenshot_requested = window.is_key_pressed(controls::SCREENSHOT.key, KeyRepeat::No);
+
+        // Único manejo del scroll: ajusta el zoom del modo de cámara activo
+        if let Some((_, scroll_y)) = window.get_scroll_wheel() {
+            match camera_mode {
+                CameraMode::ChaseShip => {
+                    zoom_factor = apply_scroll_zoom(zoom_factor, scroll_y, 0.1, 2.0, 50.0);
+                }
+                CameraMode::FreeFly if scroll_y.is_finite() => camera.move_forward(scroll_y * 0.5),
+                CameraMode::FreeFly => {}
+                CameraMode::Cinematic => {
+                    cinematic_orbit.radius =
+                        apply_scroll_zoom(cinematic_orbit.radius, scroll_y, 1.0, 10.0, 80.0);
+                }
+            }
+        }
+
+        // Control de cámara con teclas numéricas
+        if window.is_key_down(Key::Key1) {
+            current_camera_target = 0; // Marte
+            should_update_camera_target = true;
+        } else if window.is_key_down(Key::Key2) {
+            current_camera_target = 1; // Neon
+            should_update_camera_target = true;
+        } else if window.is_key_down(Key::Key3) {
+            current_camera_target = 2; // Sol
+            should_update_camera_target = true;
+        } else if window.is_key_down(Key::Key4) {
+            current_camera_target = 3; // Dalmata
+            should_update_camera_target = true;
+        } else if window.is_key_down(Key::Key5) {
+            current_camera_target = 4; // Saturno
+            should_update_camera_target = true;
+        } else if window.is_key_down(Key::Key6) {
+            current_camera_target = 5; // Kepler-452b
+            should_update_camera_target = true;
+        } else if window.is_key_down(Key::Key7) {
+            current_camera_target = 6; // Tierra
+            should_update_camera_target = true;
+        }
+
+        // Clic izquierdo sin arrastrar en vuelo libre: volar al planeta bajo el mouse
+        if let (CameraMode::FreeFly, Some(mouse)) = (
+            camera_mode,
+            window.get_mouse_pos(minifb::MouseMode::Discard),
+        ) {
+            let pressed = window.get_mouse_down(minifb::MouseButton::Left);
+            match click_start {
+                None if pressed => click_start = Some(mouse),
+                Some(start) if !pressed => {
+                    click_start = None;
+                    let moved = (mouse.0 - start.0).abs().max((mouse.1 - start.1).abs());
+                    if moved <= CLICK_TOLERANCE {
+                        let projection_matrix = create_perspective_matrix(
+                            framebuffer_width as f32,
+                            framebuffer_height as f32,
+                            pixel_aspect,
+                        );
+                        if let Some(body) = window_to_image(
+                            mouse,
+                            window.get_size(),
+                            (framebuffer_width, framebuffer_height),
+                        )
+                        .and_then(|screen| {
+                            pick_body(&scene, &meshes, &camera, &projection_matrix, screen)
+                        }) {
+                            current_camera_target = body;
+                            should_update_camera_target = true;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // Asegúrate de que current_camera_target esté dentro del rango válido
+        if current_camera_target >= scene.planet_orbits.len() {
+            current_camera_target = 0; // Regresar al valor por defecto (Marte)
+            should_update_camera_target = true;
+        }
 
-                    render(&mut framebuffer, &sun_uniforms, &vertex_arrays, sun_shader);
-                } else {
-                    // Renderizar los demás planetas normalmente
-                    render(&mut framebuffer, &uniforms, &vertex_arrays, shaders[i]);
+        if current_camera_target != previous_camera_target {
+            // El framebuffer todavía tiene el último frame mostrado: es el punto de partida
+            transition = settings
+                .scan_transition
+                .map(|duration| ScanTransition::start(&framebuffer, duration));
+            previous_camera_target = current_camera_target;
+        }
+
+        if should_update_camera_target {
+            // El vuelo sale de donde está la cámara ahora y termina junto al planeta
+            camera_transition = Some(CameraTransition::start(
+                &camera,
+                current_camera_target,
+                elapsed_time,
+                CAMERA_TRANSITION_SECONDS,
+            ));
+            should_update_camera_target = false; // Actualización completa
+        }
+
+        // Verificar colisiones para la nave
+        for (i, planet_position) in scene.translations.iter().enumerate() {
+            let planet_radius = scene.scales[i] + 0.5; // Aumentar ligeramente el radio para mayor seguridad
+            if check_collision(&scene.tie_fighter_position, planet_position, planet_radius) {
+                // Ajustar la posición de la nave para evitar la colisión
+                let direction =
+                    nalgebra_glm::normalize(&(scene.tie_fighter_position - planet_position));
+                scene.tie_fighter_position = *planet_position + direction * (planet_radius + 0.05);
+            }
+        }
+
+        if let (Some(time), Some(path)) = (camera_path_time.as_mut(), &camera_path) {
+            // El recorrido siempre empieza desde 0 y avanza con el tiempo real, así se repite
+            // igual cada vez; al terminar la cámara se queda en la última clave
+            path.apply(&mut camera, *time);
+            *time += delta_time;
+        } else {
+            match camera_mode {
+                CameraMode::ChaseShip => {
+                    // Actualizar la posición y orientación de la cámara para seguir la nave
+                    let tie_fighter_direction = scene.tie_fighter_orientation.direction();
+                    let follow_up = if lock_camera_up {
+                        level_up_vector(&tie_fighter_direction, &camera.up)
+                    } else {
+                        scene.tie_fighter_orientation.up()
+                    };
+                    camera.eye = scene.tie_fighter_position - tie_fighter_direction * zoom_factor
+                        + follow_up * 2.0;
+                    camera.center = scene.tie_fighter_position;
+                    camera.up = follow_up;
+
+                    // Manejar los controles de la nave
+                    handle_tie_fighter_input(
+                        &window,
+                        &mut scene.tie_fighter_position,
+                        &mut scene.tie_fighter_orientation,
+                        &mut last_mouse_pos,
+                        delta_time,
+                    );
+                }
+                // Mientras vuela hacia un planeta la cámara no responde a los controles
+                CameraMode::FreeFly => match &camera_transition {
+                    Some(active) => {
+                        let target = planet_view(&scene, active.to);
+                        if active.apply(&mut camera, target, elapsed_time) {
+                            camera_transition = None;
+                        }
+                    }
+                    None => {
+                        handle_free_fly_input(&window, &mut camera, &mut last_mouse_pos, delta_time)
+                    }
+                },
+                CameraMode::Cinematic => {
+                    // La nave queda quieta mientras la cámara orbita sola
+                    cinematic_orbit.update(&mut camera, delta_time);
                 }
             }
         }
 
+        scene.update(simulation_time, settings.show_trails);
+
+        let frame = FrameContext {
+            camera_eye: camera.eye,
+            sun_position: scene.translations[SUN],
+            view_matrix: create_view_matrix(camera.eye, camera.center, camera.up),
+            projection_matrix: create_perspective_matrix(
+                framebuffer_width as f32,
+                framebuffer_height as f32,
+                pixel_aspect,
+            ),
+            viewport_matrix: create_viewport_matrix(
+                framebuffer.width as f32,
+                framebuffer.height as f32,
+            ),
+            elapsed_time: simulation_time,
+            seed,
+            albedo: settings.albedo,
+            cull_backfaces: settings.cull_backfaces,
+            linear_textures: framebuffer.is_linear(),
+        };
+
+        framebuffer.clear();
+
+        draw_background(&mut framebuffer, skybox.as_ref(), &frame);
+        starfield.render(&mut framebuffer, simulation_time);
+        warp_intro.render(&mut framebuffer, elapsed_time);
+
+        select_visible_bodies(
+            &mut scene,
+            &meshes,
+            &frame,
+            &framebuffer,
+            Some(current_camera_target),
+        );
+        render_passes(
+            &mut framebuffer,
+            &scene,
+            &meshes,
+            &frame,
+            &settings,
+            &mut water,
+            Some((&thumbnails, current_camera_target)),
+        );
+
+        if let Some(active) = transition.as_mut() {
+            active.apply(&mut framebuffer, delta_time);
+            if active.is_finished() {
+                transition = None;
+            }
+        }
+
+        if screenshot_requested {
+            save_screenshot(&framebuffer);
+        }
+
+        let pixels = match settings.depth_view {
+            DepthView::FullScreen => framebuffer.to_u32_buffer_depth(NEAR_PLANE, FAR_PLANE),
+            DepthView::Hidden | DepthView::Inset => framebuffer.to_u32_buffer(),
+        };
         window
-            .update_with_buffer(
-                &framebuffer.to_u32_buffer(),
-                framebuffer_width,
-                framebuffer_height,
-            )
+            .update_with_buffer(&pixels, framebuffer_width, framebuffer_height)
             .unwrap();
 
-        std::thread::sleep(frame_delay);
+        session_stats.finish_frame();
+
+        // Esperar lo que falte del presupuesto; el título se actualiza solo si algo cambió
+        if pacer.finish_frame() || target_fps_changed {
+            window.set_title(&window_title(&pacer));
+        }
     }
 }
 
-fn handle_input(window: &Window, camera: &mut Camera, last_mouse_pos: &mut (f32, f32)) {
-    // Movimiento de la cámara hacia adelante y hacia atrás: W/S
+// Vuelo libre: WASD mueve la cámara en su propia base, Q/E la sube o baja y arrastrar con el
+// clic izquierdo gira la vista. Shift frena el movimiento igual que con la nave.
+fn handle_free_fly_input(
+    window: &Window,
+    camera: &mut Camera,
+    last_mouse_pos: &mut (f32, f32),
+    delta_time: f32,
+) {
+    let precision = if window.is_key_down(Key::LeftShift) || window.is_key_down(Key::RightShift) {
+        SHIP_PRECISION_FACTOR
+    } else {
+        1.0
+    };
+    let step = FREE_FLY_SPEED * precision * delta_time;
+
+    // Adelante/atrás: W/S
     if window.is_key_down(Key::W) {
-        camera.zoom(1.0); // Acercar la cámara
+        camera.move_forward(step);
     }
     if window.is_key_down(Key::S) {
-        camera.zoom(-1.0); // Alejar la cámara
+        camera.move_forward(-step);
     }
 
-    // Movimiento lateral de la cámara: A/D (Orbitar alrededor del centro)
+    // Lateral: A/D
     if window.is_key_down(Key::A) {
-        camera.orbit(PI / 180.0, 0.0); // Orbitar hacia la izquierda
+        camera.move_right(-step);
     }
     if window.is_key_down(Key::D) {
-        camera.orbit(-PI / 180.0, 0.0); // Orbitar hacia la derecha
+        camera.move_right(step);
     }
 
-    // Movimiento vertical de la cámara: Q/E
+    // Vertical: Q/E
     if window.is_key_down(Key::Q) {
-        camera.orbit(0.0, PI / 180.0); // Elevar la cámara
+        camera.move_up(step);
     }
     if window.is_key_down(Key::E) {
-        camera.orbit(0.0, -PI / 180.0); // Bajar la cámara
+        camera.move_up(-step);
     }
 
-    // Obtener la posición actual del mouse
     if let Some((mouse_x, mouse_y)) = window.get_mouse_pos(minifb::MouseMode::Discard) {
-        let sensitivity = 0.005; // Ajusta la sensibilidad del mouse
+        let sensitivity = 0.1 * precision; // move_center gira 0.05 rad por unidad
 
-        // Calcular el delta (diferencia) del movimiento del mouse
-        let dx = mouse_x as f32 - last_mouse_pos.0;
-        let dy = mouse_y as f32 - last_mouse_pos.1;
+        let dx = mouse_x - last_mouse_pos.0;
+        let dy = mouse_y - last_mouse_pos.1;
 
-        // Solo aplica la rotación si el botón izquierdo del mouse está presionado
+        // Mouse a la derecha gira a la derecha; hacia abajo inclina la vista hacia abajo
         if window.get_mouse_down(minifb::MouseButton::Left) {
-            camera.orbit(-dx * sensitivity, -dy * sensitivity); // Controlar yaw y pitch con el mouse
+            camera.move_center(Vec3::new(-dx * sensitivity, -dy * sensitivity, 0.0));
         }
 
-        // Actualizar la última posición del mouse
-        *last_mouse_pos = (mouse_x as f32, mouse_y as f32);
+        *last_mouse_pos = (mouse_x, mouse_y);
     }
+}
 
-    // Controlar el zoom con el scroll del mouse
-    if let Some((scroll_x, scroll_y)) = window.get_scroll_wheel() {
-        // Scroll hacia arriba (zoom in) o abajo (zoom out)
-        let zoom_sensitivity = 0.1;
-        camera.eye.z -= scroll_y as f32 * zoom_sensitivity;
-
-        // Limitar la distancia mínima y máxima del zoom
-        camera.eye.z = camera.eye.z.clamp(2.0, 100.0); // Valores arbitrarios, ajustables según tu escena
+// Scroll hacia arriba acerca (reduce la distancia) y hacia abajo aleja, siempre dentro de [min, max]
+fn apply_scroll_zoom(zoom: f32, scroll_y: f32, sensitivity: f32, min: f32, max: f32) -> f32 {
+    if !scroll_y.is_finite() {
+        return zoom.clamp(min, max);
     }
+    (zoom - scroll_y * sensitivity).clamp(min, max)
 }
 
 fn calculate_sphere_radius(vertices: &[Vertex]) -> f32 {
@@ -765,46 +3190,52 @@ fn calculate_sphere_radius(vertices: &[Vertex]) -> f32 {
 fn handle_tie_fighter_input(
     window: &Window,
     position: &mut Vec3,
-    direction: &mut Vec3,
-    up: &mut Vec3,
-    camera: &mut Camera,
+    orientation: &mut Orientation,
     last_mouse_pos: &mut (f32, f32),
+    delta_time: f32,
 ) {
-    let speed = 0.5; // Velocidad de la nave
-    let rotation_speed = 0.05; // Velocidad de rotación
-    let sensitivity = 0.005; // Sensibilidad del mouse
-    let zoom_sensitivity = 0.001; // Sensibilidad del zoom
+    // Mantener Shift mueve y gira la nave más lento para acomodarla con precisión; la
+    // simulación sigue a velocidad normal
+    let precision = if window.is_key_down(Key::LeftShift) || window.is_key_down(Key::RightShift) {
+        SHIP_PRECISION_FACTOR
+    } else {
+        1.0
+    };
+
+    let speed = 0.5 * precision; // Velocidad de la nave
+    let rotation_speed = 3.0 * delta_time * precision; // Velocidad de rotación (rad/s), igual a cualquier FPS
+    let sensitivity = 0.005 * precision; // Sensibilidad del mouse
 
     // Movimiento adelante/atrás de la nave
     if window.is_key_down(Key::Up) {
-        *position += *direction * speed; // Avanzar en la dirección actual
+        *position += orientation.direction() * speed; // Avanzar en la dirección actual
     }
     if window.is_key_down(Key::Down) {
-        *position -= *direction * speed; // Retroceder en la dirección actual
+        *position -= orientation.direction() * speed; // Retroceder en la dirección actual
     }
 
     // Rotación con teclas hacia arriba/abajo (pitch)
     if window.is_key_down(Key::W) {
-        let right = nalgebra_glm::cross(&direction, &up).normalize();
-        let rotation_matrix = nalgebra_glm::rotation(rotation_speed, &right);
-        *direction = nalgebra_glm::normalize(&(rotation_matrix.transform_vector(direction)));
-        *up = nalgebra_glm::normalize(&(rotation_matrix.transform_vector(up)));
+        orientation.pitch(rotation_speed);
     }
     if window.is_key_down(Key::S) {
-        let right = nalgebra_glm::cross(&direction, &up).normalize();
-        let rotation_matrix = nalgebra_glm::rotation(-rotation_speed, &right);
-        *direction = nalgebra_glm::normalize(&(rotation_matrix.transform_vector(direction)));
-        *up = nalgebra_glm::normalize(&(rotation_matrix.transform_vector(up)));
+        orientation.pitch(-rotation_speed);
     }
 
     // Rotación con teclas hacia los lados (yaw)
     if window.is_key_down(Key::A) {
-        let rotation_matrix = nalgebra_glm::rotation(rotation_speed, &up.normalize());
-        *direction = nalgebra_glm::normalize(&(rotation_matrix.transform_vector(direction)));
+        orientation.yaw(rotation_speed);
     }
     if window.is_key_down(Key::D) {
-        let rotation_matrix = nalgebra_glm::rotation(-rotation_speed, &up.normalize());
-        *direction = nalgebra_glm::normalize(&(rotation_matrix.transform_vector(direction)));
+        orientation.yaw(-rotation_speed);
+    }
+
+    // Alabeo (roll)
+    if window.is_key_down(Key::Q) {
+        orientation.roll(-rotation_speed);
+    }
+    if window.is_key_down(Key::E) {
+        orientation.roll(rotation_speed);
     }
 
     // Rotación con clic derecho y movimiento del mouse
@@ -813,19 +3244,177 @@ fn handle_tie_fighter_input(
         let dy = mouse_y as f32 - last_mouse_pos.1;
 
         if window.get_mouse_down(minifb::MouseButton::Left) {
-            let right = nalgebra_glm::cross(&direction, &up).normalize();
-
-            // Aplicar rotación pitch (vertical)
-            let pitch_rotation = nalgebra_glm::rotation(-dy * sensitivity, &right);
-            *direction = nalgebra_glm::normalize(&(pitch_rotation.transform_vector(direction)));
-            *up = nalgebra_glm::normalize(&(pitch_rotation.transform_vector(up)));
-
-            // Aplicar rotación yaw (horizontal)
-            let yaw_rotation = nalgebra_glm::rotation(-dx * sensitivity, &up.normalize());
-            *direction = nalgebra_glm::normalize(&(yaw_rotation.transform_vector(direction)));
+            orientation.pitch(-dy * sensitivity); // Vertical
+            orientation.yaw(-dx * sensitivity); // Horizontal
         }
 
         // Actualizar la última posición del mouse
         *last_mouse_pos = (mouse_x as f32, mouse_y as f32);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Cámara en +Z mirando al origen sobre un framebuffer cuadrado de `size` píxeles
+    fn test_frame(size: usize, cull_backfaces: bool) -> FrameContext {
+        let eye = Vec3::new(0.0, 0.0, 5.0);
+        FrameContext {
+            camera_eye: eye,
+            sun_position: Vec3::zeros(),
+            view_matrix: create_view_matrix(eye, Vec3::zeros(), Vec3::new(0.0, 1.0, 0.0)),
+            projection_matrix: create_perspective_matrix(size as f32, size as f32, 1.0),
+            viewport_matrix: create_viewport_matrix(size as f32, size as f32),
+            elapsed_time: 0.0,
+            seed: DEFAULT_SEED,
+            albedo: false,
+            cull_backfaces,
+            linear_textures: false,
+        }
+    }
+
+    // Triángulos y fragmentos que produce una malla en el origen cuyas caras frontales siguen
+    // `front_face`
+    fn rasterize(
+        frame: &FrameContext,
+        size: usize,
+        vertices: &[Vertex],
+        front_face: Winding,
+    ) -> (usize, usize) {
+        let model_matrix = create_model_matrix(Vec3::zeros(), 1.0, Vec3::zeros());
+        let mut uniforms = frame.uniforms(model_matrix, Arc::new(FastNoiseLite::new()));
+        uniforms.front_face = front_face;
+        let triangles = assemble_triangles(&Framebuffer::new(size, size), &uniforms, vertices);
+        let fragments = triangles
+            .iter()
+            .map(|[v1, v2, v3]| triangle(v1, v2, v3, size, size).len())
+            .sum();
+        (triangles.len(), fragments)
+    }
+
+    // Dos esferas que se tapan en parte, con el shader de la Tierra: `render` en paralelo
+    // o el mismo pipeline sombreando de a un fragmento
+    fn render_overlapping_spheres(framebuffer: &mut Framebuffer, parallel: bool) {
+        let frame = test_frame(framebuffer.width, true);
+        let sphere = mesh::uv_sphere(32, 64);
+        let noise = Arc::new(create_cloud_noise());
+
+        for translation in [Vec3::new(0.5, 0.0, 0.0), Vec3::new(-0.5, 0.2, 1.0)] {
+            let model_matrix = create_model_matrix(translation, 1.0, Vec3::zeros());
+            let uniforms = frame.uniforms(model_matrix, noise.clone());
+            if parallel {
+                render(framebuffer, &uniforms, &sphere, earth, BlendMode::Opaque);
+                continue;
+            }
+
+            for [v1, v2, v3] in assemble_triangles(framebuffer, &uniforms, &sphere) {
+                for fragment in triangle(&v1, &v2, &v3, framebuffer.width, framebuffer.height) {
+                    framebuffer.set_current_color(earth(&fragment, &uniforms).to_hex());
+                    framebuffer.point(
+                        fragment.position.x as usize,
+                        fragment.position.y as usize,
+                        fragment.depth,
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn parallel_shading_matches_serial() {
+        let mut serial = Framebuffer::new(200, 200);
+        let mut parallel = Framebuffer::new(200, 200);
+        render_overlapping_spheres(&mut serial, false);
+        render_overlapping_spheres(&mut parallel, true);
+
+        assert!(serial.buffer == parallel.buffer);
+        assert!(serial.zbuffer == parallel.zbuffer);
+    }
+
+    // Una esfera cercana y otra más lejana que la tapa en parte, sin solaparse en profundidad
+    fn render_spheres_near_first(framebuffer: &mut Framebuffer) {
+        let frame = test_frame(framebuffer.width, true);
+        let sphere = mesh::uv_sphere(32, 64);
+        let noise = Arc::new(create_cloud_noise());
+        let centers = [Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.8, 0.3, -3.0)];
+
+        for i in draw_order(framebuffer, &frame.view_matrix, &centers) {
+            let model_matrix = create_model_matrix(centers[i], 1.0, Vec3::zeros());
+            let uniforms = frame.uniforms(model_matrix, noise.clone());
+            render(framebuffer, &uniforms, &sphere, earth, BlendMode::Opaque);
+        }
+    }
+
+    #[test]
+    fn painters_order_matches_the_depth_buffer_across_meshes() {
+        let mut depth_tested = Framebuffer::new(200, 200);
+        let mut painted = Framebuffer::new(200, 200);
+        painted.depth_test = false;
+        render_spheres_near_first(&mut depth_tested);
+        render_spheres_near_first(&mut painted);
+
+        assert!(depth_tested.buffer == painted.buffer);
+    }
+
+    // Medición: `cargo test --release -- --ignored --nocapture shading_speedup`
+    #[test]
+    #[ignore]
+    fn parallel_shading_speedup() {
+        Lazy::force(&SKY_AMBIENT); // Que la carga del fondo no cuente en la primera medición
+        let time = |parallel: bool| {
+            let mut framebuffer = Framebuffer::new(800, 800);
+            let start = Instant::now();
+            render_overlapping_spheres(&mut framebuffer, parallel);
+            start.elapsed()
+        };
+
+        let (serial, parallel) = (time(false), time(true));
+        println!(
+            "Sombreado en serie: {:?}, en paralelo: {:?} (x{:.2})",
+            serial,
+            parallel,
+            serial.as_secs_f64() / parallel.as_secs_f64()
+        );
+    }
+
+    #[test]
+    fn sphere_straddling_the_left_edge_is_visible() {
+        let frame = test_frame(200, true);
+        // El borde izquierdo de la vista pasa por x = -5·tan(22.5°) ≈ -2.07 en z = 0
+        let position = Vec3::new(-2.6, 0.0, 0.0);
+        assert!(!frame.is_visible(&position, 0.0));
+        assert!(frame.is_visible(&position, 1.0));
+        assert!(!frame.is_visible(&Vec3::new(-4.0, 0.0, 0.0), 1.0));
+    }
+
+    #[test]
+    fn sphere_behind_the_near_plane_is_not_visible() {
+        let frame = test_frame(200, true);
+        // El plano cercano está en z = 5 - NEAR_PLANE; la esfera ocupa de z = 5 a z = 6
+        assert!(!frame.is_visible(&Vec3::new(0.0, 0.0, 5.5), 0.5));
+        assert!(frame.is_visible(&Vec3::new(0.0, 0.0, 5.5), 1.0));
+    }
+
+    #[test]
+    fn backface_culling_skips_the_far_side_of_the_sphere() {
+        let sphere = mesh::uv_sphere(32, 64);
+        let counter_clockwise = Winding::CounterClockwise;
+        let (all_triangles, all_fragments) =
+            rasterize(&test_frame(200, false), 200, &sphere, counter_clockwise);
+        let (front_triangles, front_fragments) =
+            rasterize(&test_frame(200, true), 200, &sphere, counter_clockwise);
+
+        // Desde fuera se ve algo menos de media esfera
+        assert!(front_triangles * 2 <= all_triangles);
+        assert!(front_fragments * 10 < all_fragments * 6);
+        assert!(front_fragments > 0);
+
+        // Con el orden invertido se descarta la cara cercana y queda la lejana
+        let (back_triangles, back_fragments) =
+            rasterize(&test_frame(200, true), 200, &sphere, Winding::Clockwise);
+        assert!(back_triangles * 2 >= all_triangles);
+        assert_eq!(front_triangles + back_triangles, all_triangles);
+        assert_eq!(front_fragments + back_fragments, all_fragments);
+    }
+}
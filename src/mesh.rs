@@ -0,0 +1,215 @@
+use crate::vertex::Vertex;
+use nalgebra_glm::{Mat4, Vec2, Vec3, Vec4};
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+// Esfera de radio 1 por latitud/longitud (+Y es el polo norte), como lista de triángulos.
+// Las UV siguen la proyección equirectangular: U va de 0 a 1 alrededor del eje con la
+// costura en la longitud 0 (+X), y V de 0 en el polo norte a 1 en el polo sur. La columna
+// de la costura se duplica para que U no salte de 1 a 0 dentro de un triángulo. En los
+// polos cada cuadrilátero colapsa a un solo triángulo, así no se generan triángulos de
+// área cero.
+pub fn uv_sphere(rings: usize, sectors: usize) -> Vec<Vertex> {
+    let rings = rings.max(2);
+    let sectors = sectors.max(3);
+
+    let vertex = |ring: usize, sector: f32| {
+        let v = ring as f32 / rings as f32;
+        let u = sector / sectors as f32;
+        let polar = v * PI;
+        let longitude = u * 2.0 * PI;
+
+        let position = Vec3::new(
+            polar.sin() * longitude.cos(),
+            polar.cos(),
+            polar.sin() * longitude.sin(),
+        );
+        Vertex::new(position, position, Vec2::new(u, v))
+    };
+
+    let mut vertices = Vec::with_capacity(rings * sectors * 6);
+    for ring in 0..rings {
+        for sector in 0..sectors {
+            let left = sector as f32;
+            let right = left + 1.0;
+
+            if ring == 0 {
+                // Polo norte: un vértice por sector, con U en el centro del sector
+                vertices.push(vertex(0, left + 0.5));
+                vertices.push(vertex(1, right));
+                vertices.push(vertex(1, left));
+            } else if ring == rings - 1 {
+                // Polo sur
+                vertices.push(vertex(ring, left));
+                vertices.push(vertex(ring, right));
+                vertices.push(vertex(rings, left + 0.5));
+            } else {
+                let top_left = vertex(ring, left);
+                let top_right = vertex(ring, right);
+                let bottom_left = vertex(ring + 1, left);
+                let bottom_right = vertex(ring + 1, right);
+
+                vertices.push(top_left.clone());
+                vertices.push(top_right.clone());
+                vertices.push(bottom_left.clone());
+
+                vertices.push(top_right);
+                vertices.push(bottom_right);
+                vertices.push(bottom_left);
+            }
+        }
+    }
+
+    compute_tangents(&mut vertices);
+    vertices
+}
+
+// Calcula tangente (dirección de +U) y bitangente (dirección de +V) de una lista de
+// triángulos a partir de sus posiciones y UV. Cada triángulo aporta las suyas a sus tres
+// vértices, y los vértices que comparten posición, normal y UV las promedian, así la base
+// varía suave entre triángulos vecinos. Luego se ortogonalizan contra la normal
+// (Gram-Schmidt); la bitangente conserva su sentido, que depende de si las UV están
+// espejadas. Los triángulos sin UV (área cero en UV) no aportan nada.
+pub fn compute_tangents(vertices: &mut [Vertex]) {
+    let key = |vertex: &Vertex| -> [u32; 8] {
+        let (p, n, uv) = (vertex.position, vertex.normal, vertex.tex_coords);
+        [p.x, p.y, p.z, n.x, n.y, n.z, uv.x, uv.y].map(f32::to_bits)
+    };
+
+    let mut sums: HashMap<_, (Vec3, Vec3)> = HashMap::new();
+    for triangle in vertices.chunks_exact(3) {
+        let edge1 = triangle[1].position - triangle[0].position;
+        let edge2 = triangle[2].position - triangle[0].position;
+        let delta_uv1 = triangle[1].tex_coords - triangle[0].tex_coords;
+        let delta_uv2 = triangle[2].tex_coords - triangle[0].tex_coords;
+
+        let determinant = delta_uv1.x * delta_uv2.y - delta_uv2.x * delta_uv1.y;
+        if determinant.abs() < f32::EPSILON {
+            continue;
+        }
+        let tangent = (edge1 * delta_uv2.y - edge2 * delta_uv1.y) / determinant;
+        let bitangent = (edge2 * delta_uv1.x - edge1 * delta_uv2.x) / determinant;
+
+        for vertex in triangle {
+            let sum = sums
+                .entry(key(vertex))
+                .or_insert((Vec3::zeros(), Vec3::zeros()));
+            sum.0 += tangent;
+            sum.1 += bitangent;
+        }
+    }
+
+    for vertex in vertices.iter_mut() {
+        let Some(&(tangent, bitangent)) = sums.get(&key(vertex)) else {
+            continue;
+        };
+        let normal = vertex.normal.normalize();
+
+        let tangent = tangent - normal * normal.dot(&tangent);
+        if tangent.magnitude() < 1e-6 {
+            continue;
+        }
+        let tangent = tangent.normalize();
+
+        // La bitangente queda perpendicular a la normal y a la tangente, con el sentido de V
+        let mut orthogonal = normal.cross(&tangent);
+        if orthogonal.dot(&bitangent) < 0.0 {
+            orthogonal = -orthogonal;
+        }
+
+        vertex.tangent = tangent;
+        vertex.bitangent = orthogonal;
+    }
+}
+
+// Caja alineada a los ejes (AABB) que contiene una malla
+#[derive(Clone, Copy, Debug)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+// Pares de esquinas (índices de `Aabb::corners`) que forman las 12 aristas de la caja
+pub const AABB_EDGES: [(usize, usize); 12] = [
+    (0, 1),
+    (2, 3),
+    (4, 5),
+    (6, 7),
+    (0, 2),
+    (1, 3),
+    (4, 6),
+    (5, 7),
+    (0, 4),
+    (1, 5),
+    (2, 6),
+    (3, 7),
+];
+
+impl Aabb {
+    pub fn from_vertices(vertices: &[Vertex]) -> Self {
+        Aabb::from_points(vertices.iter().map(|vertex| vertex.position))
+    }
+
+    pub fn from_points(points: impl IntoIterator<Item = Vec3>) -> Self {
+        points.into_iter().fold(
+            Aabb {
+                min: Vec3::repeat(f32::INFINITY),
+                max: Vec3::repeat(f32::NEG_INFINITY),
+            },
+            |bounds, point| Aabb {
+                min: bounds.min.inf(&point),
+                max: bounds.max.sup(&point),
+            },
+        )
+    }
+
+    // Esquina `i`: el bit 0 elige x, el bit 1 y y el bit 2 z (0 = min, 1 = max)
+    pub fn corners(&self) -> [Vec3; 8] {
+        std::array::from_fn(|i| {
+            Vec3::new(
+                if i & 1 == 0 { self.min.x } else { self.max.x },
+                if i & 2 == 0 { self.min.y } else { self.max.y },
+                if i & 4 == 0 { self.min.z } else { self.max.z },
+            )
+        })
+    }
+
+    // Caja alineada a los ejes del mundo que contiene esta caja transformada por `matrix`
+    pub fn transformed(&self, matrix: &Mat4) -> Self {
+        Aabb::from_points(self.corners().map(|corner| {
+            let world = matrix * Vec4::new(corner.x, corner.y, corner.z, 1.0);
+            Vec3::new(world.x, world.y, world.z)
+        }))
+    }
+
+    pub fn union(&self, other: &Aabb) -> Self {
+        Aabb {
+            min: self.min.inf(&other.min),
+            max: self.max.sup(&other.max),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quad_tangent_points_along_u() {
+        // Cuadrilátero en el plano XZ mirando a +Y: U crece hacia -Z y V hacia +X
+        let normal = Vec3::new(0.0, 1.0, 0.0);
+        let corner = |x: f32, z: f32, u: f32, v: f32| {
+            Vertex::new(Vec3::new(x, 0.0, z), normal, Vec2::new(u, v))
+        };
+        let (a, b) = (corner(0.0, 0.0, 0.0, 0.0), corner(0.0, -2.0, 1.0, 0.0));
+        let (c, d) = (corner(2.0, -2.0, 1.0, 1.0), corner(2.0, 0.0, 0.0, 1.0));
+        let mut vertices = vec![a.clone(), b, c.clone(), a, c, d];
+
+        compute_tangents(&mut vertices);
+
+        for vertex in &vertices {
+            assert!((vertex.tangent - Vec3::new(0.0, 0.0, -1.0)).magnitude() < 1e-5);
+            assert!((vertex.bitangent - Vec3::new(1.0, 0.0, 0.0)).magnitude() < 1e-5);
+        }
+    }
+}
@@ -1,78 +1,570 @@
+use crate::color::Color;
+use crate::mesh;
 use crate::vertex::Vertex;
 use nalgebra_glm::{Vec2, Vec3};
-use tobj;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
 
 pub struct Obj {
+    positions: Vec<Vec3>,
+    normals: Vec<Vec3>,
+    texcoords: Vec<Vec2>,
     meshes: Vec<Mesh>,
+    // Materiales por índice; el 0 es el blanco por defecto de las caras sin `usemtl`
+    materials: Vec<MtlMaterial>,
+    material_libraries: Vec<String>, // Archivos de `mtllib`, relativos al OBJ
+    current_material: usize,
+    options: ObjOptions,
+}
+
+// Opciones de carga de `Obj::load_with_options`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ObjOptions {
+    // Calcular normales suaves para los vértices sin `vn`: cada uno promedia las normales de
+    // las caras que comparten su posición. Sin esta opción esos vértices apuntan a +Y
+    pub smooth_normals: bool,
+}
+
+// Material de un archivo .mtl: color difuso (`Kd`) y textura difusa opcional (`map_Kd`)
+#[derive(Clone, Debug)]
+pub struct MtlMaterial {
+    pub name: String,
+    pub diffuse: Color,
+    pub diffuse_texture: Option<String>, // Ruta lista para abrir (relativa al directorio actual)
+}
+
+impl MtlMaterial {
+    // Blanco y sin textura: lo que usan las caras cuyo material no aparece en ningún .mtl
+    fn named(name: &str) -> Self {
+        MtlMaterial {
+            name: name.to_string(),
+            diffuse: Color::new(255, 255, 255),
+            diffuse_texture: None,
+        }
+    }
 }
 
 struct Mesh {
-    vertices: Vec<Vec3>,
-    normals: Vec<Vec3>,
-    texcoords: Vec<Vec2>,
-    indices: Vec<u32>,
+    indices: Vec<FaceIndex>,
+}
+
+#[derive(Clone, Copy)]
+struct FaceIndex {
+    position: usize,
+    texcoord: Option<usize>,
+    normal: Option<usize>,
+    material: usize,
+}
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io(io::Error),
+    Parse {
+        line: usize,
+        content: String,
+        message: String,
+    },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjError::Io(err) => write!(f, "io error: {}", err),
+            ObjError::Parse {
+                line,
+                content,
+                message,
+            } => write!(
+                f,
+                "parse error at line {}: {} ('{}')",
+                line, message, content
+            ),
+        }
+    }
+}
+
+impl From<io::Error> for ObjError {
+    fn from(err: io::Error) -> Self {
+        ObjError::Io(err)
+    }
 }
 
 impl Obj {
-    pub fn load(filename: &str) -> Result<Self, tobj::LoadError> {
-        let (models, _) = tobj::load_obj(
-            filename,
-            &tobj::LoadOptions {
-                single_index: true,
-                triangulate: true,
-                ..Default::default()
-            },
-        )?;
-
-        let meshes = models
-            .into_iter()
-            .map(|model| {
-                let mesh = model.mesh;
-                Mesh {
-                    vertices: mesh
-                        .positions
-                        .chunks(3)
-                        .map(|v| Vec3::new(v[0], v[1], v[2]))
-                        .collect(),
-                    normals: mesh
-                        .normals
-                        .chunks(3)
-                        .map(|n| Vec3::new(n[0], -n[1], n[2]))
-                        .collect(),
-                    texcoords: mesh
-                        .texcoords
-                        .chunks(2)
-                        .map(|t| Vec2::new(t[0], 1.0 - t[1]))
-                        .collect(),
-                    indices: mesh.indices,
+    pub fn load_with_options(filename: &str, options: ObjOptions) -> Result<Self, ObjError> {
+        let source = fs::read_to_string(filename)?;
+        let mut obj = Self::parse(&source)?;
+        obj.options = options;
+
+        // Las rutas de `mtllib` son relativas al OBJ. Un .mtl que falta no es un error: sus
+        // materiales se quedan en blanco (muchos exportadores lo nombran aunque no exista)
+        let directory = Path::new(filename).parent().unwrap_or(Path::new(""));
+        for library in obj.material_libraries.clone() {
+            let path = directory.join(&library);
+            if let Ok(source) = fs::read_to_string(&path) {
+                obj.parse_material_library(&source, directory).map_err(
+                    |(line, content, message)| ObjError::Parse {
+                        line,
+                        content,
+                        message: format!("{} in {}", message, library),
+                    },
+                )?;
+            }
+        }
+
+        Ok(obj)
+    }
+
+    pub fn parse(source: &str) -> Result<Self, ObjError> {
+        let mut obj = Obj {
+            positions: Vec::new(),
+            normals: Vec::new(),
+            texcoords: Vec::new(),
+            meshes: vec![Mesh {
+                indices: Vec::new(),
+            }],
+            materials: vec![MtlMaterial::named("")],
+            material_libraries: Vec::new(),
+            current_material: 0,
+            options: ObjOptions::default(),
+        };
+
+        for (line_index, line) in source.lines().enumerate() {
+            obj.parse_line(line).map_err(|message| ObjError::Parse {
+                line: line_index + 1,
+                content: line.trim().to_string(),
+                message,
+            })?;
+        }
+
+        Ok(obj)
+    }
+
+    // Las directivas desconocidas o no soportadas (s, l, ...) se ignoran;
+    // una directiva soportada pero mal formada es un error.
+    fn parse_line(&mut self, line: &str) -> Result<(), String> {
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            return Ok(());
+        };
+
+        match keyword {
+            "v" => {
+                let v = parse_floats::<3>(tokens, "vertex position")?;
+                self.positions.push(Vec3::new(v[0], v[1], v[2]));
+            }
+            "vn" => {
+                let n = parse_floats::<3>(tokens, "vertex normal")?;
+                self.normals.push(Vec3::new(n[0], n[1], n[2]));
+            }
+            "vt" => {
+                let t = parse_floats::<2>(tokens, "texture coordinate")?;
+                self.texcoords.push(Vec2::new(t[0], 1.0 - t[1]));
+            }
+            "f" => {
+                let face = tokens
+                    .map(|token| self.parse_face_index(token))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                if face.len() < 3 {
+                    return Err("expected 3 indices in face".to_string());
                 }
-            })
-            .collect();
 
-        Ok(Obj { meshes })
+                // Triangulación en abanico para caras con más de 3 vértices
+                let mesh = self.meshes.last_mut().unwrap();
+                for i in 1..face.len() - 1 {
+                    mesh.indices.push(face[0]);
+                    mesh.indices.push(face[i]);
+                    mesh.indices.push(face[i + 1]);
+                }
+            }
+            "mtllib" => {
+                let libraries: Vec<_> = tokens.map(str::to_string).collect();
+                if libraries.is_empty() {
+                    return Err("expected a file name in mtllib".to_string());
+                }
+                self.material_libraries.extend(libraries);
+            }
+            "usemtl" => {
+                let name = tokens
+                    .next()
+                    .ok_or_else(|| "expected a material name in usemtl".to_string())?;
+                self.current_material = self.material_index(name);
+            }
+            "o" | "g" if !self.meshes.last().unwrap().indices.is_empty() => {
+                self.meshes.push(Mesh {
+                    indices: Vec::new(),
+                });
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn parse_face_index(&self, token: &str) -> Result<FaceIndex, String> {
+        let mut parts = token.split('/');
+
+        let position = parts
+            .next()
+            .ok_or_else(|| format!("invalid face index '{}'", token))?;
+        let position = resolve_index(position, self.positions.len())?;
+
+        let texcoord = match parts.next() {
+            Some(t) if !t.is_empty() => Some(resolve_index(t, self.texcoords.len())?),
+            _ => None,
+        };
+        let normal = match parts.next() {
+            Some(n) if !n.is_empty() => Some(resolve_index(n, self.normals.len())?),
+            _ => None,
+        };
+
+        Ok(FaceIndex {
+            position,
+            texcoord,
+            normal,
+            material: self.current_material,
+        })
+    }
+
+    // Índice del material con ese nombre; si todavía no existe se agrega en blanco y el .mtl
+    // lo completa al cargarse
+    fn material_index(&mut self, name: &str) -> usize {
+        match self
+            .materials
+            .iter()
+            .position(|material| material.name == name)
+        {
+            Some(index) => index,
+            None => {
+                self.materials.push(MtlMaterial::named(name));
+                self.materials.len() - 1
+            }
+        }
+    }
+
+    // Lee `newmtl`, `Kd` y `map_Kd` de un .mtl; el resto de directivas (Ns, Ks, illum, ...)
+    // se ignoran. Los errores devuelven (línea, contenido, mensaje)
+    fn parse_material_library(
+        &mut self,
+        source: &str,
+        directory: &Path,
+    ) -> Result<(), (usize, String, String)> {
+        let mut current = None;
+
+        for (line_index, line) in source.lines().enumerate() {
+            let error = |message: String| (line_index + 1, line.trim().to_string(), message);
+            let mut tokens = line.split_whitespace();
+            let Some(keyword) = tokens.next() else {
+                continue;
+            };
+
+            match keyword {
+                "newmtl" => {
+                    let name = tokens
+                        .next()
+                        .ok_or_else(|| error("expected a material name in newmtl".to_string()))?;
+                    current = Some(self.material_index(name));
+                }
+                "Kd" | "map_Kd" => {
+                    let Some(index) = current else {
+                        return Err(error(format!("{} before newmtl", keyword)));
+                    };
+                    let material = &mut self.materials[index];
+                    if keyword == "Kd" {
+                        let kd = parse_floats::<3>(tokens, "diffuse color").map_err(error)?;
+                        material.diffuse = Color::from_float(kd[0], kd[1], kd[2]);
+                    } else {
+                        // Las opciones (-s, -o, ...) van antes de la ruta: se toma el último token
+                        let file = tokens
+                            .last()
+                            .ok_or_else(|| error("expected a file name in map_Kd".to_string()))?;
+                        material.diffuse_texture =
+                            Some(directory.join(file).to_string_lossy().into_owned());
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    // Materiales del modelo; `Vertex::material` es un índice en esta lista
+    pub fn materials(&self) -> &[MtlMaterial] {
+        &self.materials
     }
 
     pub fn get_vertex_array(&self) -> Vec<Vertex> {
         let mut vertices = Vec::new();
+        let smooth_normals = self.options.smooth_normals.then(|| self.smooth_normals());
 
         for mesh in &self.meshes {
-            for &index in &mesh.indices {
-                let position = mesh.vertices[index as usize];
-                let normal = mesh
-                    .normals
-                    .get(index as usize)
+            for index in &mesh.indices {
+                let position = self.positions[index.position];
+                let fallback_normal = match &smooth_normals {
+                    Some(normals) => normals[index.position],
+                    None => Vec3::new(0.0, 1.0, 0.0),
+                };
+                let normal = index
+                    .normal
+                    .and_then(|i| self.normals.get(i))
                     .cloned()
-                    .unwrap_or(Vec3::new(0.0, 1.0, 0.0));
-                let tex_coords = mesh
-                    .texcoords
-                    .get(index as usize)
+                    .unwrap_or(fallback_normal);
+                let tex_coords = index
+                    .texcoord
+                    .and_then(|i| self.texcoords.get(i))
                     .cloned()
                     .unwrap_or(Vec2::new(0.0, 0.0));
 
-                vertices.push(Vertex::new(position, normal, tex_coords));
+                let mut vertex = Vertex::new(position, normal, tex_coords);
+                vertex.color = self.materials[index.material].diffuse;
+                vertex.material = index.material;
+                vertices.push(vertex);
             }
         }
 
+        mesh::compute_tangents(&mut vertices);
         vertices
     }
+
+    // Normal suave de cada posición: suma de las normales de sus caras ponderadas por área
+    // (el producto cruz sin normalizar), así un triángulo pequeño pesa poco. Las caras siguen
+    // el orden antihorario del formato OBJ
+    fn smooth_normals(&self) -> Vec<Vec3> {
+        let mut sums = vec![Vec3::zeros(); self.positions.len()];
+        for mesh in &self.meshes {
+            for face in mesh.indices.chunks_exact(3) {
+                let [a, b, c] = [0, 1, 2].map(|i| self.positions[face[i].position]);
+                let face_normal = (b - a).cross(&(c - a));
+                for index in face {
+                    sums[index.position] += face_normal;
+                }
+            }
+        }
+
+        sums.into_iter()
+            .map(|sum| match sum.try_normalize(f32::EPSILON) {
+                Some(normal) => normal,
+                None => Vec3::new(0.0, 1.0, 0.0),
+            })
+            .collect()
+    }
+}
+
+fn parse_floats<'a, const N: usize>(
+    tokens: impl Iterator<Item = &'a str>,
+    what: &str,
+) -> Result<[f32; N], String> {
+    let mut values = [0.0; N];
+    let mut count = 0;
+
+    for token in tokens.take(N) {
+        values[count] = token
+            .parse()
+            .map_err(|_| format!("invalid number '{}' in {}", token, what))?;
+        count += 1;
+    }
+
+    if count < N {
+        return Err(format!("expected {} values in {}", N, what));
+    }
+
+    Ok(values)
+}
+
+// Los índices en OBJ empiezan en 1; los negativos son relativos al último elemento leído
+fn resolve_index(token: &str, count: usize) -> Result<usize, String> {
+    let index: isize = token
+        .parse()
+        .map_err(|_| format!("invalid index '{}' in face", token))?;
+
+    let resolved = if index > 0 {
+        index - 1
+    } else if index < 0 {
+        count as isize + index
+    } else {
+        -1
+    };
+
+    if resolved < 0 || resolved as usize >= count {
+        return Err(format!("index {} out of range ({} elements)", index, count));
+    }
+
+    Ok(resolved as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUAD: &str = "\
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 1
+f 1/1/1 2/2/1 3/3/1 4/4/1
+";
+
+    #[test]
+    fn bad_face_reports_its_line_and_content() {
+        let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2\n";
+        let error = Obj::parse(source).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "parse error at line 4: expected 3 indices in face ('f 1 2')"
+        );
+    }
+
+    #[test]
+    fn unknown_directive_is_skipped() {
+        let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\nfoo bar 1 2\nf 1 2 3\n";
+        assert_eq!(Obj::parse(source).unwrap().get_vertex_array().len(), 3);
+    }
+
+    #[test]
+    fn negative_indices_count_back_from_the_last_element() {
+        let vertices = |face: &str| {
+            let source = format!(
+                "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 0 1\n{}\n",
+                face
+            );
+            Obj::parse(&source).unwrap().get_vertex_array()
+        };
+        let relative = vertices("f -3/-3 -2/-2 -1/-1");
+        let absolute = vertices("f 1/1 2/2 3/3");
+        assert_eq!(relative.len(), 3);
+        for (a, b) in relative.iter().zip(&absolute) {
+            assert_eq!(a.position, b.position);
+            assert_eq!(a.tex_coords, b.tex_coords);
+            assert_eq!(a.normal, b.normal);
+        }
+    }
+
+    #[test]
+    fn negative_index_past_the_first_element_is_an_error() {
+        let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf -4 -2 -1\n";
+        match Obj::parse(source) {
+            Err(ObjError::Parse { line, message, .. }) => {
+                assert_eq!(line, 4);
+                assert_eq!(message, "index -4 out of range (3 elements)");
+            }
+            other => panic!("expected a parse error, got {:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn sphere_without_normals_gets_radial_smooth_normals() {
+        // La esfera del proyecto sin sus `vn` ni los índices de normal de las caras
+        let source = fs::read_to_string("assets/models/sphere.obj").unwrap();
+        let stripped: String = source
+            .lines()
+            .filter(|line| !line.starts_with("vn "))
+            .map(|line| match line.strip_prefix("f ") {
+                Some(face) => {
+                    let indices: Vec<_> = face
+                        .split_whitespace()
+                        .map(|index| index.rsplit_once('/').map_or(index, |(rest, _)| rest))
+                        .collect();
+                    format!("f {}\n", indices.join(" "))
+                }
+                None => format!("{}\n", line),
+            })
+            .collect();
+        let path = std::env::temp_dir().join("sphere_without_normals.obj");
+        fs::write(&path, stripped).unwrap();
+
+        let options = ObjOptions {
+            smooth_normals: true,
+        };
+        let vertices = Obj::load_with_options(path.to_str().unwrap(), options)
+            .unwrap()
+            .get_vertex_array();
+        fs::remove_file(&path).unwrap();
+
+        assert_points_outward(&vertices);
+    }
+
+    #[test]
+    fn vertex_normal_keeps_its_y_sign() {
+        let source = "v 0 0 0\nv 1 0 0\nv 0 0 -1\nvn 0 1 0\nf 1//1 2//1 3//1\n";
+        for vertex in Obj::parse(source).unwrap().get_vertex_array() {
+            assert_eq!(vertex.normal, Vec3::new(0.0, 1.0, 0.0));
+        }
+    }
+
+    #[test]
+    fn sphere_normals_point_outward() {
+        let source = fs::read_to_string("assets/models/sphere.obj").unwrap();
+        assert_points_outward(&Obj::parse(&source).unwrap().get_vertex_array());
+    }
+
+    #[test]
+    fn ship_normals_agree_with_face_winding() {
+        // Las caras son antihorarias, así que cada `vn` debería quedar del lado del producto
+        // cruz de su triángulo. Unas pocas caras del modelo están invertidas; con Y negada
+        // coincidía solo la mitad
+        let source = fs::read_to_string("assets/models/tiefighter.obj").unwrap();
+        let vertices = Obj::parse(&source).unwrap().get_vertex_array();
+        let agreeing = vertices
+            .chunks_exact(3)
+            .flat_map(|triangle| {
+                let [a, b, c] = [0, 1, 2].map(|i| triangle[i].position);
+                let face_normal = (b - a).cross(&(c - a));
+                triangle
+                    .iter()
+                    .map(move |vertex| vertex.normal.dot(&face_normal) > 0.0)
+            })
+            .filter(|&agrees| agrees)
+            .count();
+        assert!(
+            agreeing * 10 > vertices.len() * 9,
+            "{} of {}",
+            agreeing,
+            vertices.len()
+        );
+    }
+
+    // Cada normal apunta hacia afuera de la esfera centrada en el origen, tanto en el
+    // hemisferio norte como en el sur
+    fn assert_points_outward(vertices: &[Vertex]) {
+        let mut hemispheres = [0, 0];
+        for vertex in vertices {
+            let outward = vertex.position.normalize();
+            assert!(vertex.normal.dot(&outward) > 0.95, "{:?}", vertex.position);
+            if outward.y > 0.5 {
+                assert!(vertex.normal.y > 0.0, "{:?}", vertex.position);
+                hemispheres[0] += 1;
+            } else if outward.y < -0.5 {
+                assert!(vertex.normal.y < 0.0, "{:?}", vertex.position);
+                hemispheres[1] += 1;
+            }
+        }
+        assert!(hemispheres[0] > 0 && hemispheres[1] > 0);
+    }
+
+    #[test]
+    fn quad_face_is_split_into_two_triangles() {
+        let vertices = Obj::parse(QUAD).unwrap().get_vertex_array();
+        assert_eq!(vertices.len(), 6);
+
+        // Abanico desde el primer vértice: (1, 2, 3) y (1, 3, 4)
+        let positions: Vec<_> = vertices.iter().map(|vertex| vertex.position).collect();
+        let corners = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+        let [a, b, c, d] = corners.map(|(x, y)| Vec3::new(x, y, 0.0));
+        assert_eq!(positions, vec![a, b, c, a, c, d]);
+
+        // Cada vértice conserva su UV (con V invertida al cargar) y su normal
+        for vertex in &vertices {
+            let position = vertex.position;
+            assert_eq!(vertex.tex_coords, Vec2::new(position.x, 1.0 - position.y));
+            assert_eq!(vertex.normal, Vec3::new(0.0, 0.0, 1.0));
+        }
+    }
 }
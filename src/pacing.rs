@@ -0,0 +1,53 @@
+use std::time::{Duration, Instant};
+
+// Ritmo de frames: al terminar cada frame duerme lo que falte para cumplir el presupuesto
+// de 1 / target_fps segundos, y cuenta como perdido el frame que ya lo había excedido.
+// El presupuesto se calcula en cada frame, así cambiar el objetivo en vivo aplica desde
+// el frame siguiente.
+pub struct FramePacer {
+    target_fps: u32,
+    frame_start: Instant,
+    pub frames: u64,
+    pub dropped_frames: u64,
+}
+
+impl FramePacer {
+    pub fn new(target_fps: u32) -> Self {
+        FramePacer {
+            target_fps: target_fps.max(1),
+            frame_start: Instant::now(),
+            frames: 0,
+            dropped_frames: 0,
+        }
+    }
+
+    pub fn target_fps(&self) -> u32 {
+        self.target_fps
+    }
+
+    pub fn set_target_fps(&mut self, target_fps: u32) {
+        self.target_fps = target_fps.max(1);
+    }
+
+    pub fn frame_budget(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.target_fps as f64)
+    }
+
+    // Cierra el frame actual (trabajo medido desde el cierre del anterior) y devuelve si se
+    // pasó del presupuesto
+    pub fn finish_frame(&mut self) -> bool {
+        let budget = self.frame_budget();
+        let work = self.frame_start.elapsed();
+
+        let dropped = work > budget;
+        if dropped {
+            self.dropped_frames += 1;
+        } else {
+            std::thread::sleep(budget - work);
+        }
+
+        self.frames += 1;
+        self.frame_start = Instant::now();
+        dropped
+    }
+}
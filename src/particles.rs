@@ -0,0 +1,89 @@
+use crate::color::Color;
+use nalgebra_glm::Vec3;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+pub struct Particle {
+    pub position: Vec3,
+    pub velocity: Vec3,
+    pub age: f32,
+    pub lifetime: f32,
+}
+
+// Parada del degradado: a la edad relativa `t` (0 = recién creada, 1 = muerta)
+// la partícula tiene `color` y opacidad `alpha`
+#[derive(Clone, Copy)]
+pub struct GradientStop {
+    pub t: f32,
+    pub color: Color,
+    pub alpha: f32,
+}
+
+pub struct ParticleSystem {
+    pub particles: Vec<Particle>,
+    pub gradient: Vec<GradientStop>,
+    pub lifetime: f32,
+    rng: StdRng,
+}
+
+impl ParticleSystem {
+    pub fn new(gradient: Vec<GradientStop>, lifetime: f32, seed: u64) -> Self {
+        ParticleSystem {
+            particles: Vec::new(),
+            gradient,
+            lifetime,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    // Emite una partícula en `position` que se aleja en `direction` con algo de dispersión
+    pub fn emit(&mut self, position: Vec3, direction: Vec3, speed: f32, spread: f32) {
+        let jitter = Vec3::new(
+            self.rng.gen_range(-spread..=spread),
+            self.rng.gen_range(-spread..=spread),
+            self.rng.gen_range(-spread..=spread),
+        );
+
+        self.particles.push(Particle {
+            position,
+            velocity: (direction + jitter) * speed,
+            age: 0.0,
+            lifetime: self.lifetime * self.rng.gen_range(0.7..=1.0),
+        });
+    }
+
+    pub fn update(&mut self, delta_time: f32) {
+        for particle in &mut self.particles {
+            particle.age += delta_time;
+            particle.position += particle.velocity * delta_time;
+        }
+        self.particles
+            .retain(|particle| particle.age < particle.lifetime);
+    }
+
+    // Color y opacidad de una partícula según su edad, interpolando entre las paradas
+    pub fn color_at(&self, particle: &Particle) -> (Color, f32) {
+        let t = (particle.age / particle.lifetime).clamp(0.0, 1.0);
+
+        let Some(first) = self.gradient.first() else {
+            return (Color::new(255, 255, 255), 1.0 - t);
+        };
+        if t <= first.t {
+            return (first.color, first.alpha);
+        }
+
+        for pair in self.gradient.windows(2) {
+            let (start, end) = (pair[0], pair[1]);
+            if t <= end.t {
+                let local_t = (t - start.t) / (end.t - start.t).max(f32::EPSILON);
+                return (
+                    start.color.lerp(&end.color, local_t),
+                    start.alpha + (end.alpha - start.alpha) * local_t,
+                );
+            }
+        }
+
+        let last = self.gradient[self.gradient.len() - 1];
+        (last.color, last.alpha)
+    }
+}
@@ -0,0 +1,41 @@
+use crate::framebuffer::Framebuffer;
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, ImageResult, Rgba, RgbaImage};
+use std::fs::File;
+use std::io::BufWriter;
+
+// Calidad de la cuantización de la paleta (1 = mejor y más lento, 30 = más rápido).
+// Cada frame se reduce a una paleta de 256 colores con NeuQuant.
+const PALETTE_SPEED: i32 = 10;
+
+// Graba frames del framebuffer en un GIF animado que se repite indefinidamente. Los frames
+// se codifican al agregarlos, así no se guardan todos en memoria.
+pub struct GifRecorder {
+    encoder: GifEncoder<BufWriter<File>>,
+    delay: Delay,
+}
+
+impl GifRecorder {
+    pub fn create(path: &str, fps: u32) -> ImageResult<Self> {
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = GifEncoder::new_with_speed(file, PALETTE_SPEED);
+        encoder.set_repeat(Repeat::Infinite)?;
+
+        Ok(GifRecorder {
+            encoder,
+            delay: Delay::from_numer_denom_ms(1000, fps.max(1)),
+        })
+    }
+
+    pub fn add_frame(&mut self, framebuffer: &Framebuffer) -> ImageResult<()> {
+        let pixels = framebuffer.to_u32_buffer();
+        let width = framebuffer.output_width();
+        let image = RgbaImage::from_fn(width as u32, framebuffer.output_height() as u32, |x, y| {
+            let [_, r, g, b] = pixels[y as usize * width + x as usize].to_be_bytes();
+            Rgba([r, g, b, 255])
+        });
+
+        self.encoder
+            .encode_frame(Frame::from_parts(image, 0, 0, self.delay))
+    }
+}
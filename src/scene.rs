@@ -0,0 +1,608 @@
+use crate::camera::{Camera, Orientation};
+use crate::color::Color;
+use crate::comet::Comet;
+use crate::fragment::Fragment;
+use crate::mesh::Aabb;
+use crate::particles::{GradientStop, ParticleSystem};
+use crate::shaders::{
+    cellular_shader, combined_shader, dalmata_shader, earth, iridescence_shader, lava_shader,
+    material_shader, neon_normal_map_shader, ring_shader, static_pattern_shader, Atmosphere,
+    MaterialTextures, NoiseDisplacement,
+};
+use crate::trail::OrbitTrail;
+use crate::vertex::Vertex;
+use crate::{create_noise_for_planet, Uniforms};
+use fastnoise_lite::{FastNoiseLite, NoiseType};
+use nalgebra_glm::Vec3;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::sync::Arc;
+
+pub type Shader = fn(&Fragment, &Uniforms) -> Color;
+
+// Shader de objetos semitransparentes: color y opacidad (0.0 - 1.0)
+pub type TransparentShader = fn(&Fragment, &Uniforms) -> (Color, f32);
+
+// Animación de escala por cuerpo: escala base + sin(tiempo * frecuencia) * amplitud
+#[derive(Clone, Copy)]
+pub struct ScaleAnimation {
+    pub amplitude: f32,
+    pub frequency: f32,
+}
+
+impl ScaleAnimation {
+    pub const STATIC: ScaleAnimation = ScaleAnimation {
+        amplitude: 0.0,
+        frequency: 0.0,
+    };
+
+    pub fn scale_at(&self, base_scale: f32, time: f32) -> f32 {
+        base_scale + (time * self.frequency).sin() * self.amplitude
+    }
+}
+
+// Opciones de superficie de cada cuerpo que los shaders consultan en `Uniforms`
+#[derive(Clone, Copy)]
+pub struct Material {
+    pub use_texture: bool, // Patrón de superficie (p. ej. los continentes de la Tierra)
+    pub use_normal_map: bool, // Relieve que perturba la normal del fragmento
+    pub specular: f32,     // Intensidad del brillo especular del Sol (0 = superficie mate)
+    pub shininess: f32,    // Exponente de Phong: más alto, brillo más chico y definido
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material {
+            use_texture: true,
+            use_normal_map: true,
+            specular: 0.0,
+            shininess: 32.0,
+        }
+    }
+}
+
+// Índice del Sol dentro de los cuerpos de la escena
+pub const SUN: usize = 2;
+
+// Separación por defecto entre órbitas: multiplica `planet_orbits` para obtener el radio real
+pub const ORBIT_SPACING: f32 = 1.5;
+
+// Shaders que se pueden asignar a un cuerpo, identificados por nombre
+const SHADERS: [(&str, Shader); 10] = [
+    ("lava", lava_shader),
+    ("neon_normal_map", neon_normal_map_shader),
+    ("iridescence", iridescence_shader),
+    ("static_pattern", static_pattern_shader),
+    ("dalmata", dalmata_shader),
+    ("combined", combined_shader),
+    ("cellular", cellular_shader),
+    ("earth", earth),
+    ("material", material_shader),
+    ("ring", ring_shader),
+];
+
+pub fn shader_by_name(name: &str) -> Option<Shader> {
+    SHADERS
+        .iter()
+        .find(|(shader_name, _)| *shader_name == name)
+        .map(|(_, shader)| *shader)
+}
+
+// Lista de cuerpos de la escena (`--scene archivo.json`). El cuerpo en la posición SUN es el
+// Sol; los efectos propios de Saturno (anillo) y la Tierra (Luna) siguen atados a su índice
+#[derive(Deserialize)]
+pub struct SceneConfig {
+    pub planets: Vec<PlanetConfig>,
+}
+
+#[derive(Deserialize)]
+pub struct PlanetConfig {
+    pub name: String,
+    pub orbit_radius: f32, // Se multiplica por `orbit_spacing`
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+    pub shader_name: String, // Nombre en SHADERS
+    #[serde(default)]
+    pub model_path: Option<String>, // OBJ del cuerpo; None = DEFAULT_MODEL
+}
+
+// Malla de los cuerpos que no indican `model_path`
+pub const DEFAULT_MODEL: &str = "assets/models/sphere.obj";
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+impl PlanetConfig {
+    fn new(name: &str, orbit_radius: f32, shader_name: &str, model_path: Option<&str>) -> Self {
+        PlanetConfig {
+            name: name.to_string(),
+            orbit_radius,
+            scale: 1.0,
+            shader_name: shader_name.to_string(),
+            model_path: model_path.map(str::to_string),
+        }
+    }
+
+    pub fn model_path(&self) -> &str {
+        self.model_path.as_deref().unwrap_or(DEFAULT_MODEL)
+    }
+}
+
+impl SceneConfig {
+    // Lee y valida la escena: todos los shaders deben existir y debe haber un cuerpo en SUN
+    pub fn load(path: &str) -> io::Result<Self> {
+        let config: SceneConfig = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        if config.planets.len() <= SUN {
+            return Err(invalid(format!(
+                "la escena necesita al menos {} cuerpos (el Sol es el número {})",
+                SUN + 1,
+                SUN + 1
+            )));
+        }
+        for planet in &config.planets {
+            if shader_by_name(&planet.shader_name).is_none() {
+                let known: Vec<&str> = SHADERS.iter().map(|(name, _)| *name).collect();
+                return Err(invalid(format!(
+                    "shader desconocido \"{}\" en {} (disponibles: {})",
+                    planet.shader_name,
+                    planet.name,
+                    known.join(", ")
+                )));
+            }
+            if planet.scale <= 0.0 {
+                return Err(invalid(format!(
+                    "la escala de {} debe ser positiva",
+                    planet.name
+                )));
+            }
+        }
+        Ok(config)
+    }
+}
+
+impl Default for SceneConfig {
+    // El sistema original del proyecto
+    fn default() -> Self {
+        SceneConfig {
+            planets: vec![
+                PlanetConfig::new("Marte", 4.0, "lava", None),
+                PlanetConfig::new("Neon", 6.0, "iridescence", None),
+                // Sol: su órbita solo mantiene la alineación de los anillos
+                PlanetConfig::new("Sol", 8.0, "static_pattern", None),
+                PlanetConfig::new("Dalmata", 10.0, "dalmata", None),
+                PlanetConfig::new("Saturno", 12.0, "ring", Some("assets/models/saturn.obj")),
+                PlanetConfig::new("Kepler-452b", 14.0, "cellular", None),
+                PlanetConfig::new("Tierra", 16.0, "earth", None),
+            ],
+        }
+    }
+}
+
+// Tipos de ruido para probar en vivo sobre el cuerpo enfocado; el resto de parámetros
+// (semilla, frecuencia, fractal) se conserva del ruido original del cuerpo
+const NOISE_TYPES: [(&str, NoiseType); 6] = [
+    ("Perlin", NoiseType::Perlin),
+    ("OpenSimplex2", NoiseType::OpenSimplex2),
+    ("OpenSimplex2S", NoiseType::OpenSimplex2S),
+    ("Cellular", NoiseType::Cellular),
+    ("Value", NoiseType::Value),
+    ("ValueCubic", NoiseType::ValueCubic),
+];
+
+// Ventaja de tamaño que conserva un cuerpo ya seleccionado frente a los demás al aplicar
+// `max_visible_bodies`; evita que dos cuerpos de tamaño parecido se alternen cada frame
+const VISIBLE_BODY_HYSTERESIS: f32 = 1.25;
+
+// Largo de la estela de cada cometa, en frames (guarda un punto por subpaso, igual que las
+// estelas de las órbitas)
+const COMET_TRAIL_LENGTH: usize = 90;
+
+// Índice del ruido compartido por la Luna y los cometas en `create_noise_for_planet`
+const SATELLITE_NOISE: usize = 7;
+
+// Estado exportado a JSON: cuerpos, cámara y nave tal como están en el frame actual
+#[derive(Serialize)]
+struct SceneSnapshot<'a> {
+    bodies: Vec<BodySnapshot<'a>>,
+    camera: CameraSnapshot,
+    tie_fighter: TieFighterSnapshot,
+}
+
+#[derive(Serialize)]
+struct BodySnapshot<'a> {
+    name: &'a str,
+    shader_name: &'a str,
+    orbit_radius: f32,
+    position: [f32; 3],
+    rotation: [f32; 3],
+    scale: f32,
+    always_render: bool,
+    use_texture: bool,
+    use_normal_map: bool,
+}
+
+#[derive(Serialize)]
+struct CameraSnapshot {
+    eye: [f32; 3],
+    center: [f32; 3],
+    up: [f32; 3],
+}
+
+#[derive(Serialize)]
+struct TieFighterSnapshot {
+    position: [f32; 3],
+    orientation: [f32; 4], // Cuaternión (x, y, z, w)
+}
+
+fn to_array(v: &Vec3) -> [f32; 3] {
+    [v.x, v.y, v.z]
+}
+
+// Estado de todos los cuerpos; se actualiza una vez por frame y lo leen las pasadas de render
+pub struct Scene {
+    pub names: Vec<String>,
+    pub shader_names: Vec<String>,
+    pub planet_orbits: Vec<f32>,
+    pub orbit_spacing: f32,
+    pub translations: Vec<Vec3>,
+    pub rotations: Vec<Vec3>,
+    pub scales: Vec<f32>,
+    pub scale_animations: Vec<ScaleAnimation>,
+    pub always_render: Vec<bool>,
+    // Máximo de cuerpos dibujados por frame (los más grandes en pantalla); los de
+    // always_render y el enfocado no cuentan. None = sin límite
+    pub max_visible_bodies: Option<usize>,
+    selected_bodies: Vec<bool>,
+    pub shaders: Vec<Shader>,
+    pub materials: Vec<Material>,
+    // Ruido de cada cuerpo; solo se reconstruye al cambiar de tipo, no en cada frame
+    pub noises: Vec<Arc<FastNoiseLite>>,
+    noise_types: Vec<Option<usize>>, // Índice en NOISE_TYPES; None = ruido original
+    pub satellite_noise: Arc<FastNoiseLite>, // Luna y cometas
+    pub atmospheres: Vec<Option<Atmosphere>>,
+    pub orbit_trails: Vec<OrbitTrail>,
+
+    pub comets: Vec<Comet>,
+    pub comet_trails: Vec<OrbitTrail>, // Posiciones recientes de cada cometa
+    pub comet_twinkle: ScaleAnimation,
+    pub comet_wobble: Option<NoiseDisplacement>, // Ondulación de la superficie del núcleo
+    pub moon_relief: f32, // Altura de los cráteres de la Luna (unidades del modelo)
+    pub comet_particles: ParticleSystem,
+    pub comet_particles_per_second: f32,
+    comet_emission_accumulator: f32,
+
+    pub tie_fighter_position: Vec3,
+    pub tie_fighter_orientation: Orientation,
+
+    // Multiplicador de la velocidad orbital; no afecta rotaciones ni animaciones de shaders
+    pub orbit_speed: f32,
+    orbit_time: f32,
+
+    // Pasos de simulación por frame; con dt grandes evita arcos escalonados en las estelas
+    pub substeps: u32,
+    last_time: f32,
+}
+
+// Mallas cargadas una sola vez al iniciar
+pub struct Meshes {
+    pub sphere: Vec<Vertex>,
+    pub models: Vec<Vec<Vertex>>, // Malla de cada cuerpo (`model_path`)
+    pub model_textures: Vec<MaterialTextures>, // Texturas de los materiales (.mtl) de cada malla
+    pub moon: Vec<Vertex>,
+    pub comet: Vec<Vertex>,
+    pub tie_fighter: Vec<Vertex>,
+    // Cajas envolventes en espacio del objeto, calculadas al cargar
+    pub model_bounds: Vec<Aabb>,
+    pub moon_bounds: Aabb,
+}
+
+impl Scene {
+    // Cuerpos según `config` (ya validada); `trail_length` se da en frames y cada estela
+    // guarda un punto por subpaso. Toda la aleatoriedad de la escena (cometas, partículas)
+    // sale de `seed`
+    pub fn new(
+        config: &SceneConfig,
+        comet_count: usize,
+        trail_length: usize,
+        substeps: u32,
+        seed: u64,
+    ) -> Self {
+        let substeps = substeps.max(1);
+        let planets = &config.planets;
+
+        let planet_orbits: Vec<f32> = planets.iter().map(|planet| planet.orbit_radius).collect();
+        // Posiciones iniciales en el plano eclíptico; `update` las recalcula en cada paso
+        let translations: Vec<Vec3> = planet_orbits
+            .iter()
+            .enumerate()
+            .map(|(i, orbit)| {
+                if i == SUN {
+                    Vec3::zeros()
+                } else {
+                    Vec3::new(orbit * ORBIT_SPACING, 0.0, 0.0)
+                }
+            })
+            .collect();
+        let body_count = translations.len();
+
+        let mut scale_animations = vec![ScaleAnimation::STATIC; body_count];
+        scale_animations[SUN] = ScaleAnimation {
+            amplitude: 0.04, // Pulso suave del Sol
+            frequency: 1.5,
+        };
+
+        let mut always_render = vec![false; body_count];
+        always_render[SUN] = true; // El Sol es la luz central, nunca debe desaparecer
+
+        let mut atmospheres = vec![None; body_count];
+        // Capas de atmósfera: esfera translúcida `scale` veces más grande que el planeta
+        if let Some(atmosphere) = atmospheres.get_mut(1) {
+            *atmosphere = Some(Atmosphere {
+                scale: 1.2, // Neon
+                color: Color::new(200, 120, 255),
+                density: 0.12,
+            });
+        }
+        if let Some(atmosphere) = atmospheres.get_mut(6) {
+            *atmosphere = Some(Atmosphere {
+                scale: 1.15, // Tierra
+                color: Color::new(120, 180, 255),
+                density: 0.25,
+            });
+        }
+
+        // La lava fundida de Marte refleja un punto brillante del Sol; el agua de la Tierra,
+        // uno más tenue
+        let mut materials = vec![Material::default(); body_count];
+        materials[0].specular = 0.6;
+        materials[0].shininess = 24.0;
+        if let Some(earth) = materials.get_mut(6) {
+            earth.specular = 0.25;
+            earth.shininess = 48.0;
+        }
+
+        let names = planets.iter().map(|planet| planet.name.clone()).collect();
+        let shader_names: Vec<String> = planets
+            .iter()
+            .map(|planet| planet.shader_name.clone())
+            .collect();
+        let shaders = shader_names
+            .iter()
+            .map(|name| shader_by_name(name).expect("shader desconocido"))
+            .collect();
+
+        // Partículas de las colas: se enfrían con la edad (blanco -> naranja -> azul -> transparente)
+        let comet_tail_gradient = vec![
+            GradientStop {
+                t: 0.0,
+                color: Color::new(255, 255, 255),
+                alpha: 1.0,
+            },
+            GradientStop {
+                t: 0.25,
+                color: Color::new(255, 165, 0),
+                alpha: 0.9,
+            },
+            GradientStop {
+                t: 0.6,
+                color: Color::new(80, 140, 255),
+                alpha: 0.6,
+            },
+            GradientStop {
+                t: 1.0,
+                color: Color::new(40, 60, 160),
+                alpha: 0.0,
+            },
+        ];
+
+        Scene {
+            names,
+            shader_names,
+            planet_orbits,
+            orbit_spacing: ORBIT_SPACING,
+            translations,
+            rotations: vec![Vec3::new(0.0, 0.0, 0.0); body_count],
+            scales: planets.iter().map(|planet| planet.scale).collect(),
+            scale_animations,
+            always_render,
+            max_visible_bodies: None,
+            selected_bodies: vec![true; body_count],
+            shaders,
+            materials,
+            noises: (0..body_count)
+                .map(|i| Arc::new(create_noise_for_planet(i)))
+                .collect(),
+            noise_types: vec![None; body_count],
+            satellite_noise: Arc::new(create_noise_for_planet(SATELLITE_NOISE)),
+            atmospheres,
+            orbit_trails: (0..body_count)
+                .map(|_| OrbitTrail::new(trail_length * substeps as usize))
+                .collect(),
+
+            // Cometas con trayectorias independientes (reproducibles con la misma semilla)
+            comets: Comet::spawn(comet_count, seed),
+            comet_trails: (0..comet_count)
+                .map(|_| OrbitTrail::new(COMET_TRAIL_LENGTH * substeps as usize))
+                .collect(),
+            comet_twinkle: ScaleAnimation {
+                amplitude: 0.03, // Parpadeo del cometa
+                frequency: 12.0,
+            },
+            moon_relief: 0.16,
+            comet_wobble: Some(NoiseDisplacement {
+                amplitude: 0.12,
+                scale: 150.0,
+                speed: 60.0,
+            }),
+            comet_particles: ParticleSystem::new(comet_tail_gradient, 1.5, seed.wrapping_add(1)),
+            comet_particles_per_second: 60.0,
+            comet_emission_accumulator: 0.0,
+
+            tie_fighter_position: Vec3::new(0.0, 0.0, 7.0), // Posición inicial
+            tie_fighter_orientation: Orientation::identity(), // Mirando hacia -Z con +Y arriba
+
+            orbit_speed: 1.0,
+            orbit_time: 0.0,
+
+            substeps,
+            last_time: 0.0,
+        }
+    }
+
+    // Radio real de la órbita del cuerpo `i`; lo usan tanto el movimiento como los anillos
+    pub fn orbit_radius(&self, i: usize) -> f32 {
+        self.planet_orbits[i] * self.orbit_spacing
+    }
+
+    // Tiempo acumulado con el que se calculan los ángulos de las órbitas
+    pub fn orbit_time(&self) -> f32 {
+        self.orbit_time
+    }
+
+    // Pasa el ruido del cuerpo al siguiente tipo de NOISE_TYPES (después del último vuelve
+    // al original) y devuelve el nombre del tipo activo
+    pub fn cycle_noise(&mut self, body: usize) -> &'static str {
+        let next = match self.noise_types[body] {
+            None => Some(0),
+            Some(index) if index + 1 < NOISE_TYPES.len() => Some(index + 1),
+            Some(_) => None,
+        };
+        self.noise_types[body] = next;
+
+        let mut noise = create_noise_for_planet(body);
+        let name = match next {
+            Some(index) => {
+                let (name, noise_type) = NOISE_TYPES[index];
+                noise.set_noise_type(Some(noise_type));
+                name
+            }
+            None => "original",
+        };
+        self.noises[body] = Arc::new(noise);
+        name
+    }
+
+    // Elige qué cuerpos se dibujan este frame según `max_visible_bodies`. `screen_sizes` es
+    // el radio en pantalla de cada cuerpo (None si quedó fuera del frustum); los cuerpos ya
+    // seleccionados compiten con su tamaño aumentado por VISIBLE_BODY_HYSTERESIS
+    pub fn select_visible_bodies(&mut self, screen_sizes: &[Option<f32>], focused: Option<usize>) {
+        let Some(limit) = self.max_visible_bodies else {
+            self.selected_bodies.fill(true);
+            return;
+        };
+
+        let exempt = |body: usize| self.always_render[body] || focused == Some(body);
+        let mut candidates: Vec<(usize, f32)> = screen_sizes
+            .iter()
+            .enumerate()
+            .filter(|&(body, _)| !exempt(body))
+            .filter_map(|(body, size)| {
+                let bonus = if self.selected_bodies[body] {
+                    VISIBLE_BODY_HYSTERESIS
+                } else {
+                    1.0
+                };
+                size.map(|size| (body, size * bonus))
+            })
+            .collect();
+        candidates.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        let mut selected: Vec<bool> = (0..screen_sizes.len()).map(exempt).collect();
+        for &(body, _) in candidates.iter().take(limit) {
+            selected[body] = true;
+        }
+        self.selected_bodies = selected;
+    }
+
+    // Si el cuerpo quedó dentro del límite de `select_visible_bodies`
+    pub fn is_selected(&self, body: usize) -> bool {
+        self.selected_bodies[body]
+    }
+
+    // Avanza la simulación hasta `elapsed_time` en `substeps` incrementos iguales
+    pub fn update(&mut self, elapsed_time: f32, record_trails: bool) {
+        let delta_time = (elapsed_time - self.last_time).max(0.0);
+        let step = delta_time / self.substeps as f32;
+
+        for k in 1..=self.substeps {
+            self.step(self.last_time + step * k as f32, step, record_trails);
+        }
+        self.last_time = elapsed_time;
+    }
+
+    // Un paso de simulación: órbitas, rotaciones, estelas y partículas
+    fn step(&mut self, elapsed_time: f32, delta_time: f32, record_trails: bool) {
+        // El tiempo orbital se acumula para que cambiar el multiplicador no haga saltar
+        // a los planetas; con 1.0 avanza igual que `elapsed_time`
+        self.orbit_time += delta_time * self.orbit_speed;
+
+        for i in 0..self.translations.len() {
+            let angular_speed = 0.1 + i as f32 * 0.05;
+            let orbit_angle = self.orbit_time * angular_speed;
+            if i != SUN {
+                let orbit_radius = self.orbit_radius(i);
+                self.translations[i].x = orbit_radius * orbit_angle.cos();
+                self.translations[i].y = orbit_radius * orbit_angle.sin();
+
+                if record_trails {
+                    self.orbit_trails[i].push(self.translations[i]);
+                }
+            }
+
+            self.rotations[i].y = elapsed_time * angular_speed;
+        }
+
+        // Estelas de los cometas, y emitir y envejecer las partículas de sus colas
+        self.comet_emission_accumulator += self.comet_particles_per_second * delta_time;
+        let emitted_per_comet = self.comet_emission_accumulator as usize;
+        self.comet_emission_accumulator -= emitted_per_comet as f32;
+        for (comet, trail) in self.comets.iter().zip(&mut self.comet_trails) {
+            trail.push(comet.position_at(elapsed_time));
+            for _ in 0..emitted_per_comet {
+                self.comet_particles.emit(
+                    comet.position_at(elapsed_time),
+                    comet.tail_direction(elapsed_time),
+                    1.5,
+                    0.2,
+                );
+            }
+        }
+        self.comet_particles.update(delta_time);
+    }
+
+    // Serializa el estado actual de los cuerpos, la cámara y la nave
+    pub fn to_json(&self, camera: &Camera) -> serde_json::Result<String> {
+        let bodies = (0..self.translations.len())
+            .map(|i| BodySnapshot {
+                name: &self.names[i],
+                shader_name: &self.shader_names[i],
+                orbit_radius: self.planet_orbits[i],
+                position: to_array(&self.translations[i]),
+                rotation: to_array(&self.rotations[i]),
+                scale: self.scales[i],
+                always_render: self.always_render[i],
+                use_texture: self.materials[i].use_texture,
+                use_normal_map: self.materials[i].use_normal_map,
+            })
+            .collect();
+
+        let orientation = self.tie_fighter_orientation.quaternion().coords;
+        let snapshot = SceneSnapshot {
+            bodies,
+            camera: CameraSnapshot {
+                eye: to_array(&camera.eye),
+                center: to_array(&camera.center),
+                up: to_array(&camera.up),
+            },
+            tie_fighter: TieFighterSnapshot {
+                position: to_array(&self.tie_fighter_position),
+                orientation: [orientation.x, orientation.y, orientation.z, orientation.w],
+            },
+        };
+
+        serde_json::to_string_pretty(&snapshot)
+    }
+}
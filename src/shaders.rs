@@ -1,21 +1,132 @@
 use crate::color::Color;
 use crate::fragment::Fragment;
-use crate::vertex::Vertex;
+use crate::scene::Material;
+use crate::texture::Texture;
+use crate::vertex::{Vertex, MIN_CLIP_W};
 use crate::Uniforms;
-use nalgebra_glm::{dot, mat4_to_mat3, Mat3, Vec3, Vec4};
+use nalgebra_glm::{dot, mat4_to_mat3, Mat3, Vec2, Vec3, Vec4};
 use rand::rngs::StdRng;
 use rand::Rng;
 use rand::SeedableRng;
 use std::f32::consts::PI;
+use std::sync::Arc;
+
+// Vertex displacement along the normal by animated noise (`uniforms.noise`), sampled at
+// `position * scale + time * speed`. The same vertex at the same time always gets the same
+// offset, so the surface ripples instead of flickering.
+#[derive(Clone, Copy)]
+pub struct NoiseDisplacement {
+    pub amplitude: f32, // Maximum offset in object units
+    pub scale: f32,     // Noise coordinates per object unit
+    pub speed: f32,     // Noise coordinates per second
+}
+
+impl NoiseDisplacement {
+    fn offset(&self, position: &Vec3, uniforms: &Uniforms) -> f32 {
+        let t = uniforms.time * self.speed;
+        let p = position * self.scale;
+        uniforms.noise.get_noise_3d(p.x + t, p.y, p.z - t) * self.amplitude
+    }
+
+    // Displaced position and an approximate normal, rebuilt from two displaced neighbours
+    // along the tangent plane
+    fn apply(&self, position: &Vec3, normal: &Vec3, uniforms: &Uniforms) -> (Vec3, Vec3) {
+        let displace = |p: Vec3| p + normal * self.offset(&p, uniforms);
+
+        let helper = if normal.x.abs() < 0.9 {
+            Vec3::new(1.0, 0.0, 0.0)
+        } else {
+            Vec3::new(0.0, 1.0, 0.0)
+        };
+        let tangent = normal.cross(&helper).normalize();
+        let bitangent = normal.cross(&tangent);
+
+        let epsilon = 0.01;
+        let center = displace(*position);
+        let along_tangent = displace(position + tangent * epsilon) - center;
+        let along_bitangent = displace(position + bitangent * epsilon) - center;
+
+        let displaced_normal = along_tangent.cross(&along_bitangent).normalize();
+        if displaced_normal.x.is_finite() {
+            (center, displaced_normal)
+        } else {
+            (center, *normal)
+        }
+    }
+}
+
+// Displacement mapping for spheres: each vertex moves along its normal by the height
+// texture (0.0 - 1.0, centered on 0.5) times `scale`. The texture is equirectangular;
+// vertices sample it with UVs from their direction, and the per-fragment relief normal
+// with the mesh's interpolated UVs, so the mesh must be a `mesh::uv_sphere`.
+#[derive(Clone)]
+pub struct HeightMap {
+    pub texture: Arc<Texture>,
+    pub scale: f32, // Offset in object units between the lowest and highest point
+}
+
+impl HeightMap {
+    // Equirectangular UVs of a direction from the sphere's center
+    fn uv(direction: &Vec3) -> (f32, f32) {
+        let longitude = direction.z.atan2(direction.x);
+        let latitude = direction.y.clamp(-1.0, 1.0).asin();
+        (0.5 + longitude / (2.0 * PI), 0.5 - latitude / PI)
+    }
+
+    fn height(&self, u: f32, v: f32) -> f32 {
+        (self.texture.sample_height(u, v) - 0.5) * self.scale
+    }
+
+    // Displaced position. The normal is left alone: the relief's shading comes from
+    // `tangent_normal` per fragment, so it is not counted twice
+    fn apply(&self, position: &Vec3, normal: &Vec3) -> Vec3 {
+        let direction = normal.normalize();
+        let (u, v) = Self::uv(&direction);
+        position + direction * self.height(u, v)
+    }
+
+    // Tangent-space normal of the displaced surface at an object-space point with the given
+    // mesh UVs (`mesh::uv_sphere`), rebuilt from the height gradient between neighbouring
+    // texels. Tangent space follows those UVs: +X east (+U), +Y south (+V), +Z out of the
+    // surface
+    pub fn tangent_normal(&self, object_position: &Vec3, tex_coords: &Vec2) -> Vec3 {
+        let radius = object_position.magnitude().max(1e-3);
+        // The mesh's U starts at +X and `uv`'s at -X, half a turn apart; V matches
+        let (u, v) = (tex_coords.x + 0.5, tex_coords.y);
+        let latitude = (0.5 - v) * PI;
+
+        // Central differences one texel apart, converted to height per object unit
+        let du = 1.0 / self.texture.width as f32;
+        let dv = 1.0 / self.texture.height as f32;
+        let dh_du = (self.height(u + du, v) - self.height(u - du, v)) / (2.0 * du);
+        let dh_dv = (self.height(u, v + dv) - self.height(u, v - dv)) / (2.0 * dv);
+        let circle_radius = (radius * latitude.cos()).max(1e-3);
+        let east_slope = dh_du / (2.0 * PI * circle_radius);
+        let south_slope = dh_dv / (PI * radius);
+
+        Vec3::new(-east_slope, -south_slope, 1.0).normalize()
+    }
+}
 
 pub fn vertex_shader(vertex: &Vertex, uniforms: &Uniforms) -> Vertex {
+    let (object_position, object_normal) = match &uniforms.displacement {
+        Some(displacement) => displacement.apply(&vertex.position, &vertex.normal, uniforms),
+        None => (vertex.position, vertex.normal),
+    };
+    let object_position = match &uniforms.height_map {
+        Some(height_map) => height_map.apply(&object_position, &object_normal),
+        None => object_position,
+    };
+
     // Transform position
-    let position = Vec4::new(vertex.position.x, vertex.position.y, vertex.position.z, 1.0);
+    let position = Vec4::new(object_position.x, object_position.y, object_position.z, 1.0);
     let transformed =
         uniforms.projection_matrix * uniforms.view_matrix * uniforms.model_matrix * position;
 
-    // Perform perspective division
-    let w = transformed.w;
+    // Perform perspective division. With w <= 0 the vertex is on or behind the camera plane and
+    // dividing would give inf/NaN; w is clamped so the position stays finite, and the original
+    // `clip_position` flags the vertex (`Vertex::is_projectable`) for clipping or culling
+    let w = transformed.w.max(MIN_CLIP_W);
     let ndc_position = Vec4::new(transformed.x / w, transformed.y / w, transformed.z / w, 1.0);
 
     // apply viewport matrix
@@ -28,17 +139,82 @@ pub fn vertex_shader(vertex: &Vertex, uniforms: &Uniforms) -> Vertex {
         .try_inverse()
         .unwrap_or(Mat3::identity());
 
-    let transformed_normal = normal_matrix * vertex.normal;
+    let transformed_normal = normal_matrix * object_normal;
+
+    // Tangents lie on the surface, so they follow the model matrix itself
+    let transformed_tangent = model_mat3 * vertex.tangent;
+    let transformed_bitangent = model_mat3 * vertex.bitangent;
 
     // Create a new Vertex with transformed attributes
     Vertex {
-        position: vertex.position,
-        normal: vertex.normal,
+        position: object_position,
+        normal: object_normal,
         tex_coords: vertex.tex_coords,
         color: vertex.color,
+        material: vertex.material,
+        tangent: vertex.tangent,
+        bitangent: vertex.bitangent,
         transformed_position: Vec3::new(screen_position.x, screen_position.y, screen_position.z),
         transformed_normal,
+        transformed_tangent,
+        transformed_bitangent,
+        clip_position: transformed,
+    }
+}
+
+// Phong reflection: the ambient term, plus diffuse (Lambert) light from `light_dir` and a
+// white highlight where the light mirrored about the normal points at the camera. All
+// directions are unit vectors pointing away from the surface.
+pub fn phong(
+    base: Color,
+    normal: &Vec3,
+    light_dir: &Vec3,
+    view_dir: &Vec3,
+    ambient: Color,
+    material: &Material,
+) -> Color {
+    let diffuse = dot(normal, light_dir).max(0.0);
+    let ambient_color = base * ambient;
+    if diffuse <= 0.0 {
+        return ambient_color;
+    }
+
+    let reflected = normal * 2.0 * dot(normal, light_dir) - light_dir;
+    let specular = material.specular * dot(&reflected, view_dir).max(0.0).powf(material.shininess);
+
+    base * diffuse + ambient_color + Color::new(255, 255, 255) * specular
+}
+
+// World-space position of a fragment, from its object-space position and the model matrix
+fn world_position(fragment: &Fragment, uniforms: &Uniforms) -> Vec3 {
+    let object_position = fragment.object_position;
+    (uniforms.model_matrix
+        * Vec4::new(object_position.x, object_position.y, object_position.z, 1.0))
+    .xyz()
+}
+
+// Phong lighting from the Sun plus the environment ambient, so the side facing away from the
+// Sun takes the sky's tint instead of going fully black
+fn lit(base: Color, fragment: &Fragment, uniforms: &Uniforms) -> Color {
+    lit_with_normal(base, &fragment.normal, fragment, uniforms)
+}
+
+// Same as `lit`, for shaders that perturb the normal
+fn lit_with_normal(base: Color, normal: &Vec3, fragment: &Fragment, uniforms: &Uniforms) -> Color {
+    if uniforms.albedo {
+        return base;
     }
+    let position = world_position(fragment, uniforms);
+    let light_dir = (uniforms.sun_position - position).normalize();
+    let view_dir = (uniforms.camera_position - position).normalize();
+    phong(
+        base,
+        normal,
+        &light_dir,
+        &view_dir,
+        uniforms.ambient,
+        &uniforms.material,
+    )
 }
 
 pub fn fragment_shader(
@@ -50,8 +226,8 @@ pub fn fragment_shader(
 }
 
 pub fn static_pattern_shader(fragment: &Fragment, _uniforms: &Uniforms) -> Color {
-    let x = fragment.vertex_position.x;
-    let y = fragment.vertex_position.y;
+    let x = fragment.object_position.x;
+    let y = fragment.object_position.y;
 
     let pattern = ((x * 10.0).sin() * (y * 10.0).sin()).abs();
 
@@ -63,10 +239,10 @@ pub fn static_pattern_shader(fragment: &Fragment, _uniforms: &Uniforms) -> Color
 }
 
 pub fn moving_circles_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
-    let x = fragment.vertex_position.x;
-    let y = fragment.vertex_position.y;
+    let x = fragment.object_position.x;
+    let y = fragment.object_position.y;
 
-    let time = uniforms.time as f32 * 0.05;
+    let time = uniforms.time * 0.05;
     let circle1_x = (time.sin() * 0.4 + 0.5) % 1.0;
     let circle2_x = (time.cos() * 0.4 + 0.5) % 1.0;
 
@@ -93,10 +269,55 @@ pub fn combined_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
 
     // Combine shaders: use circle color if it's not black, otherwise use base color
     if !circle_color.is_black() {
-        circle_color * fragment.intensity
+        lit(circle_color, fragment, uniforms)
     } else {
-        base_color * fragment.intensity
+        lit(base_color, fragment, uniforms)
+    }
+}
+
+// Saturn's ring in object space (the planet has radius 1): inner and outer edge of the disc
+const RING_INNER_RADIUS: f32 = 1.17;
+const RING_OUTER_RADIUS: f32 = 1.73;
+
+// Saturn: the sphere keeps the combined pattern and the ring around it gets concentric bands.
+// Everything in the ring depends only on the radial distance from the planet's axis, so the
+// bands stay circular; the noise along that distance opens the gaps between them, and the
+// brightness falls off toward the inner and outer edges.
+pub fn ring_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
+    let position = fragment.object_position;
+    if position.magnitude() < 1.05 {
+        return combined_shader(fragment, uniforms);
     }
+
+    let radial = (position.x * position.x + position.z * position.z).sqrt();
+    let t =
+        ((radial - RING_INNER_RADIUS) / (RING_OUTER_RADIUS - RING_INNER_RADIUS)).clamp(0.0, 1.0);
+
+    // Concentric bands alternating between a light and a dusty tone
+    let bands = 0.5 + 0.5 * (radial * 70.0).sin();
+    let band_color = Color::new(215, 190, 150).lerp(&Color::new(150, 120, 90), bands);
+
+    // Gaps: where the noise dips the ring thins out almost completely
+    let gap = uniforms.noise.get_noise_2d(radial * 400.0, 0.0);
+    let density = if gap < -0.3 { 0.15 } else { 0.65 + 0.35 * gap };
+
+    // Smooth falloff over the first and last 20% of the ring's width
+    let edge = |x: f32| {
+        let x = (x / 0.2).clamp(0.0, 1.0);
+        x * x * (3.0 - 2.0 * x)
+    };
+    let falloff = edge(t) * edge(1.0 - t);
+    let base = band_color * (density * falloff);
+
+    // The ring is a thin sheet of ice and dust: it is lit from whichever side faces the Sun,
+    // and it keeps part of its color when the light grazes its plane
+    let light_dir = (uniforms.sun_position - world_position(fragment, uniforms)).normalize();
+    let normal = if dot(&fragment.normal, &light_dir) < 0.0 {
+        -fragment.normal
+    } else {
+        fragment.normal
+    };
+    lit_with_normal(base, &normal, fragment, uniforms).lerp(&base, 0.4)
 }
 
 // Simple purple shader
@@ -106,8 +327,8 @@ fn purple_shader(_fragment: &Fragment) -> Color {
 
 // Circle shader
 fn circle_shader(fragment: &Fragment) -> Color {
-    let x = fragment.vertex_position.x;
-    let y = fragment.vertex_position.y;
+    let x = fragment.object_position.x;
+    let y = fragment.object_position.y;
     let distance = (x * x + y * y).sqrt();
 
     if distance < 0.25 {
@@ -128,6 +349,11 @@ pub fn combined_blend_shader(fragment: &Fragment, blend_mode: &str) -> Color {
         "multiply" => base_color.blend_multiply(&circle_color),
         "add" => base_color.blend_add(&circle_color),
         "subtract" => base_color.blend_subtract(&circle_color),
+        "screen" => base_color.blend_screen(&circle_color),
+        "overlay" => base_color.blend_overlay(&circle_color),
+        "lighten" => base_color.blend_lighten(&circle_color),
+        "darken" => base_color.blend_darken(&circle_color),
+        "color_dodge" => base_color.blend_color_dodge(&circle_color),
         _ => base_color, // Default to base color if unknown blend mode
     };
 
@@ -135,7 +361,7 @@ pub fn combined_blend_shader(fragment: &Fragment, blend_mode: &str) -> Color {
 }
 
 fn glow_shader(fragment: &Fragment) -> Color {
-    let y = fragment.vertex_position.y;
+    let y = fragment.object_position.y;
     let stripe_width = 0.2;
     let glow_size = 0.05;
 
@@ -151,7 +377,7 @@ fn glow_shader(fragment: &Fragment) -> Color {
 }
 
 fn core_shader(fragment: &Fragment) -> Color {
-    let y = fragment.vertex_position.y;
+    let y = fragment.object_position.y;
     let stripe_width = 0.2;
     let core_size = 0.02;
 
@@ -184,9 +410,10 @@ pub fn neon_light_shader(fragment: &Fragment, _uniforms: &Uniforms) -> Color {
 }
 
 fn random_color_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
+    // A new random color each whole second
     let seed = uniforms.time as u64;
 
-    let mut rng = StdRng::seed_from_u64(seed);
+    let mut rng = StdRng::seed_from_u64(uniforms.seed ^ seed);
 
     let r = rng.gen_range(0..=255);
     let g = rng.gen_range(0..=255);
@@ -198,9 +425,10 @@ fn random_color_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
 }
 
 fn black_and_white(fragment: &Fragment, uniforms: &Uniforms) -> Color {
-    let seed = uniforms.time as f32 * fragment.vertex_position.y * fragment.vertex_position.x;
+    // The pattern is reseeded once per whole second
+    let seed = uniforms.time.floor() * fragment.object_position.y * fragment.object_position.x;
 
-    let mut rng = StdRng::seed_from_u64(seed.abs() as u64);
+    let mut rng = StdRng::seed_from_u64(uniforms.seed ^ seed.abs() as u64);
 
     let random_number = rng.gen_range(0..=100);
 
@@ -217,8 +445,8 @@ pub fn dalmata_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
     let zoom = 100.0;
     let ox = 0.0;
     let oy = 0.0;
-    let x = fragment.vertex_position.x;
-    let y = fragment.vertex_position.y;
+    let x = fragment.object_position.x;
+    let y = fragment.object_position.y;
 
     let noise_value = uniforms
         .noise
@@ -234,16 +462,16 @@ pub fn dalmata_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
         base_color
     };
 
-    noise_color * fragment.intensity
+    lit(noise_color, fragment, uniforms)
 }
 
 pub fn cloud_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
     let zoom = 300.0; // to move our values
     let ox = 100.0; // offset x in the noise map
     let oy = 10.0;
-    let x = fragment.vertex_position.x;
-    let y = fragment.vertex_position.y;
-    let t = uniforms.time as f32 * 0.5;
+    let x = fragment.object_position.x;
+    let y = fragment.object_position.y;
+    let t = uniforms.time * 0.5;
 
     let noise_value = uniforms
         .noise
@@ -261,15 +489,15 @@ pub fn cloud_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
         sky_color
     };
 
-    noise_color * fragment.intensity
+    lit(noise_color, fragment, uniforms)
 }
 
 pub fn cellular_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
     let zoom = 300.0; // Zoom factor to adjust the scale of the cell pattern
     let ox = 50.0; // Offset x in the noise map
     let oy = 50.0; // Offset y in the noise map
-    let x = fragment.vertex_position.x;
-    let y = fragment.vertex_position.y;
+    let x = fragment.object_position.x;
+    let y = fragment.object_position.y;
 
     // Use a cellular noise function to create the plant cell pattern
     let cell_noise_value = uniforms
@@ -295,7 +523,7 @@ pub fn cellular_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
     };
 
     // Adjust intensity to simulate lighting effects (optional)
-    final_color * fragment.intensity
+    lit(final_color, fragment, uniforms)
 }
 
 pub fn lava_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
@@ -305,15 +533,15 @@ pub fn lava_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
 
     // Get fragment position
     let position = Vec3::new(
-        fragment.vertex_position.x,
-        fragment.vertex_position.y,
-        fragment.depth,
+        fragment.object_position.x,
+        fragment.object_position.y,
+        fragment.object_position.z,
     );
 
     // Base frequency and amplitude for the pulsating effect
     let base_frequency = 0.2;
     let pulsate_amplitude = 0.5;
-    let t = uniforms.time as f32 * 0.01;
+    let t = uniforms.time * 0.01;
 
     // Pulsate on the z-axis to change spot size
     let pulsate = (t * base_frequency).sin() * pulsate_amplitude;
@@ -335,28 +563,55 @@ pub fn lava_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
     // Use lerp for color blending based on noise value
     let color = dark_color.lerp(&bright_color, noise_value);
 
-    color * fragment.intensity
+    lit(color, fragment, uniforms)
+}
+
+// Diffuse texture (`map_Kd`) of each material of a mesh, indexed like `Obj::materials`
+pub type MaterialTextures = Arc<[Option<Arc<Texture>>]>;
+
+// Surface straight from the model's .mtl: the face's diffuse color (`Kd`), times its
+// diffuse texture when the material has one, lit by the Sun. With gamma correction on, the
+// texture is decoded to linear first so the lighting math doesn't run on sRGB values
+pub fn material_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
+    let texture = uniforms
+        .material_textures
+        .as_ref()
+        .and_then(|textures| textures.get(fragment.material))
+        .and_then(Option::as_ref);
+    let base = match texture {
+        Some(texture) if uniforms.linear_textures => {
+            let texel = texture.sample_linear(fragment.tex_coords.x, fragment.tex_coords.y);
+            Color::from_float(texel.x, texel.y, texel.z).blend_multiply(&fragment.color)
+        }
+        Some(texture) => texture
+            .sample(fragment.tex_coords.x, fragment.tex_coords.y)
+            .blend_multiply(&fragment.color),
+        None => fragment.color,
+    };
+    lit(base, fragment, uniforms)
 }
 
 pub fn earth(fragment: &Fragment, uniforms: &Uniforms) -> Color {
     let zoom = 30.0; // Zoom factor to adjust the scale of the cell pattern
     let base_offset = 50.0; // Base offset in the noise map
-    let x = fragment.vertex_position.x;
-    let y = fragment.vertex_position.y;
+    let x = fragment.object_position.x;
+    let y = fragment.object_position.y;
 
     // Desplazamiento dinámico en el tiempo para el efecto de movimiento
-    let t = uniforms.time as f32 * 0.5; // Velocidad del movimiento
+    let t = uniforms.time * 0.5; // Velocidad del movimiento
     let dynamic_offset_x = base_offset + t.sin() * 10.0; // Movimiento sinusoidal en x
     let dynamic_offset_y = base_offset + t.cos() * 10.0; // Movimiento sinusoidal en y
 
     // Use a cellular noise function to create the plant cell pattern
-    let cell_noise_value = uniforms
-        .noise
-        .get_noise_2d(x * zoom + dynamic_offset_x, y * zoom + dynamic_offset_y)
-        .abs();
+    let land_x = x * zoom + dynamic_offset_x;
+    let land_y = y * zoom + dynamic_offset_y;
+    let cell_noise_value = uniforms.noise.get_noise_2d(land_x, land_y).abs();
 
     // Define different shades of green for the plant cells
-    let base_color = if cell_noise_value < 0.15 {
+    // (without the texture the planet is plain ocean, to compare against the continents)
+    let base_color = if !uniforms.material.use_texture {
+        Color::new(2, 100, 177) // Celeste
+    } else if cell_noise_value < 0.15 {
         Color::new(85, 107, 47) // Dark olive green
     } else if cell_noise_value < 0.7 {
         Color::new(2, 100, 177) // Celeste
@@ -376,25 +631,71 @@ pub fn earth(fragment: &Fragment, uniforms: &Uniforms) -> Color {
     );
 
     let cloud_threshold = 0.5; // Adjust this value to change cloud density
-    let cloud_color = Color::new(255, 255, 255); // White for clouds
+    let haze_threshold = 0.35; // Thin haze fringing each cloud
+    let cloud_color = Color::new_rgba(255, 255, 255, 128); // Half-transparent white clouds
+
+    // The clouds' shadows, sampled slightly off the clouds themselves
+    let shadow_noise_value = uniforms.noise.get_noise_2d(
+        x * cloud_zoom + cloud_ox + t * 1.7 + 4.0,
+        y * cloud_zoom + cloud_oy - 4.0,
+    );
 
-    let blended_color = if cloud_noise_value > cloud_threshold {
-        // Blend the cloud color with the base color using a blending factor to keep the base visible
-        base_color.blend_normal(&cloud_color) * 0.5 + base_color * 0.5
+    let cloudy = cloud_noise_value > cloud_threshold;
+    let blended_color = if cloudy {
+        // Draw the clouds over the base color with their alpha so the base stays visible
+        base_color.blend_alpha(&cloud_color)
+    } else if cloud_noise_value > haze_threshold {
+        // Overlay brightens land and sea alike without washing out the contrast between them
+        let haze = (cloud_noise_value - haze_threshold) / (cloud_threshold - haze_threshold);
+        base_color.lerp(&base_color.blend_overlay(&Color::new(200, 210, 230)), haze)
+    } else if shadow_noise_value > cloud_threshold {
+        // Darken only pulls down the channels brighter than the shadow tint
+        base_color.blend_darken(&Color::new(40, 70, 110))
     } else {
         base_color
     };
 
     // Adjust intensity to simulate lighting effects (optional)
-    blended_color * fragment.intensity
+    let surface = lit(blended_color, fragment, uniforms);
+    if uniforms.albedo {
+        return surface;
+    }
+
+    // City lights on the night side: emissive speckles over the land, sampled from the same
+    // noise coordinates as the continents so they move with them, and dimmed under clouds.
+    // They fade in across a narrow band around the terminator instead of switching on at once
+    let is_land = uniforms.material.use_texture && !(0.15..0.7).contains(&cell_noise_value);
+    let surface = if is_land {
+        let light_dir = (uniforms.sun_position - world_position(fragment, uniforms)).normalize();
+        let sun_height = dot(&fragment.normal, &light_dir);
+        let dusk = ((0.1 - sun_height) / 0.25).clamp(0.0, 1.0);
+        let night = dusk * dusk * (3.0 - 2.0 * dusk);
+
+        let city_noise = uniforms
+            .noise
+            .get_noise_2d(land_x * 80.0 + 500.0, land_y * 80.0 + 500.0);
+        let cities = ((city_noise - 0.4) / 0.3).clamp(0.0, 1.0);
+        let cloud_cover = if cloudy { 0.4 } else { 1.0 };
+        // Lighten keeps whichever is brighter, so the lights never blow out the dusk side
+        surface.blend_lighten(&(Color::new(255, 200, 110) * (night * cities * cloud_cover)))
+    } else {
+        surface
+    };
+
+    // Atmospheric rim: fresnel term that is 0 facing the camera and 1 at the silhouette, where
+    // the line of sight crosses the most air
+    let view_dir = (uniforms.camera_position - world_position(fragment, uniforms)).normalize();
+    let facing = dot(&fragment.normal, &view_dir).clamp(0.0, 1.0);
+    let fresnel = (1.0 - facing).powi(3);
+    surface.lerp(&Color::new(120, 220, 255), fresnel)
 }
 
 pub fn luna_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
     let zoom = 100.0;
     let ox = 0.0;
     let oy = 0.0;
-    let x = fragment.vertex_position.x;
-    let y = fragment.vertex_position.y;
+    let x = fragment.object_position.x;
+    let y = fragment.object_position.y;
 
     let noise_value = uniforms
         .noise
@@ -410,15 +711,24 @@ pub fn luna_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
         base_color
     };
 
-    noise_color * fragment.intensity
+    // Relief from the height map as a tangent-space normal, lit by the Sun
+    match &uniforms.height_map {
+        Some(height_map) => {
+            let normal = fragment.tangent_to_world(
+                &height_map.tangent_normal(&fragment.object_position, &fragment.tex_coords),
+            );
+            lit_with_normal(noise_color, &normal, fragment, uniforms)
+        }
+        None => lit(noise_color, fragment, uniforms),
+    }
 }
 
 pub fn comet_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
     let zoom = 200.0; // Ajustar el zoom para la cola
-    let ox = uniforms.time as f32 * 0.1; // Movimiento dinámico de la cola
+    let ox = uniforms.time * 0.1; // Movimiento dinámico de la cola
     let oy = 0.0;
-    let x = fragment.vertex_position.x;
-    let y = fragment.vertex_position.y;
+    let x = fragment.object_position.x;
+    let y = fragment.object_position.y;
 
     // Valor de ruido para el efecto de la cola
     let noise_value = uniforms
@@ -446,14 +756,14 @@ pub fn sun_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
 
     // Obtener la posición del fragmento
     let position = Vec3::new(
-        fragment.vertex_position.x,
-        fragment.vertex_position.y,
-        fragment.depth,
+        fragment.object_position.x,
+        fragment.object_position.y,
+        fragment.object_position.z,
     );
 
     // Ajustes para el efecto de ruido
     let zoom = 200.0; // Factor de zoom para el ruido
-    let time_factor = uniforms.time as f32 * 0.02; // Factor temporal para animación
+    let time_factor = uniforms.time * 0.02; // Factor temporal para animación
 
     // Calcular valores de ruido en 3D con un desplazamiento basado en el tiempo
     let noise_value1 = uniforms.noise.get_noise_3d(
@@ -476,18 +786,186 @@ pub fn sun_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
     // Mezclar colores en función del ruido y la oscilación
     let color = hot_color.lerp(&bright_color, (noise_value + pulsate).clamp(0.0, 1.0));
 
+    // El tono oscila lentamente unos grados entre naranja y amarillo
+    let (hue, saturation, value) = color.to_hsv();
+    let color = Color::from_hsv(hue + (time_factor * 3.0).sin() * 6.0, saturation, value);
+
     color * fragment.intensity // Ajustar por intensidad del fragmento
 }
 
-pub fn neon_normal_map_shader(fragment: &Fragment, _uniforms: &Uniforms) -> Color {
-    // Obtener la normal del fragmento
-    let normal = fragment.normal;
+// Neon panels: the fragment normal encoded as RGB. With a tangent-space normal map the
+// normal is perturbed first, so the panel bevels show in the color and catch the sunlight
+pub fn neon_normal_map_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
+    let normal = match &uniforms.normal_map {
+        Some(normal_map) => {
+            let (u, v) = (fragment.tex_coords.x, fragment.tex_coords.y);
+            fragment.tangent_to_world(&normal_map.sample_normal(u, v))
+        }
+        None => fragment.normal,
+    };
 
     // Convertir la normal a valores RGB utilizando el rango [-1, 1] -> [0, 255]
     let r = ((normal.x + 1.0) * 0.5 * 255.0) as u8;
     let g = ((normal.y + 1.0) * 0.5 * 255.0) as u8;
     let b = ((normal.z + 1.0) * 0.5 * 255.0) as u8;
+    let color = Color::new(r, g, b);
 
-    // Crear el color basado en el mapa normal
-    Color::new(r, g, b)
+    match uniforms.normal_map {
+        Some(_) => lit_with_normal(color, &normal, fragment, uniforms),
+        None => color,
+    }
+}
+
+// Polished metal for the ship: a dark diffuse base plus the starfield reflected about the
+// fragment normal. The scene has no cubemap, so the environment texture is read as an
+// equirectangular map around the world +Z axis (the ecliptic normal). Schlick's fresnel
+// makes grazing angles reflect more, which is what sells the hull as metal.
+pub fn ship_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
+    let base_color = Color::new(40, 42, 48);
+    let lit_base = lit(base_color, fragment, uniforms);
+    let Some(environment) = uniforms.environment.as_ref().filter(|_| !uniforms.albedo) else {
+        return lit_base;
+    };
+
+    let object_position = fragment.object_position;
+    let world_position = uniforms.model_matrix
+        * Vec4::new(object_position.x, object_position.y, object_position.z, 1.0);
+    let view_dir = (world_position.xyz() - uniforms.camera_position).normalize();
+    let normal = fragment.normal.normalize();
+
+    // Flip back-facing normals so both sides of the thin wing panels reflect
+    let normal = if dot(&normal, &view_dir) > 0.0 {
+        -normal
+    } else {
+        normal
+    };
+    let reflected = view_dir - normal * 2.0 * dot(&view_dir, &normal);
+
+    let longitude = reflected.y.atan2(reflected.x);
+    let latitude = reflected.z.clamp(-1.0, 1.0).asin();
+    let reflection = environment.sample(0.5 + longitude / (2.0 * PI), 0.5 - latitude / PI);
+
+    let base_reflectance = 0.3;
+    let cos_theta = (-dot(&view_dir, &normal)).clamp(0.0, 1.0);
+    let fresnel = base_reflectance + (1.0 - base_reflectance) * (1.0 - cos_theta).powi(5);
+
+    lit_base.lerp(&reflection, fresnel)
+}
+
+pub fn iridescence_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
+    // Superficie base: violeta oscuro iluminado por la luz direccional
+    let base_color = Color::new(60, 20, 90) * (0.3 + 0.7 * fragment.intensity);
+
+    // Dirección hacia la cámara desde la posición del fragmento en el mundo
+    let object_position = fragment.object_position;
+    let world_position = uniforms.model_matrix
+        * Vec4::new(object_position.x, object_position.y, object_position.z, 1.0);
+    let view_dir = (uniforms.camera_position - world_position.xyz()).normalize();
+
+    // Mapa normal procedural: el gradiente del ruido inclina la normal para dar relieve
+    let normal = if uniforms.material.use_normal_map {
+        perturb_normal(&fragment.normal, &object_position, uniforms, 40.0, 0.3)
+    } else {
+        fragment.normal
+    };
+
+    // Ángulo de Fresnel: 1 de frente, 0 en los bordes (ángulo rasante)
+    let facing = dot(&normal, &view_dir).abs().clamp(0.0, 1.0);
+    let fresnel = (1.0 - facing).powf(2.0);
+
+    // La película delgada recorre el arcoíris según el ángulo de vista
+    let hue = facing * 540.0 + uniforms.time * 10.0;
+    let film_color = Color::from_hsv(hue, 0.8, 1.0);
+
+    base_color.lerp(&film_color, 0.25 + 0.75 * fresnel)
+}
+
+// Inclina `normal` según el gradiente del ruido de los uniforms alrededor de `position`
+// (espacio del objeto). `zoom` controla el tamaño del relieve y `strength` su profundidad.
+fn perturb_normal(
+    normal: &Vec3,
+    position: &Vec3,
+    uniforms: &Uniforms,
+    zoom: f32,
+    strength: f32,
+) -> Vec3 {
+    let epsilon = 0.01;
+    let sample = |p: Vec3| {
+        uniforms
+            .noise
+            .get_noise_3d(p.x * zoom, p.y * zoom, p.z * zoom)
+    };
+
+    let center = sample(*position);
+    let gradient = Vec3::new(
+        sample(position + Vec3::new(epsilon, 0.0, 0.0)) - center,
+        sample(position + Vec3::new(0.0, epsilon, 0.0)) - center,
+        sample(position + Vec3::new(0.0, 0.0, epsilon)) - center,
+    ) / epsilon;
+    // La normal del fragmento está en espacio del mundo; el gradiente también debe estarlo
+    let gradient = mat4_to_mat3(&uniforms.model_matrix) * gradient;
+
+    // Solo cuenta la parte del gradiente tangente a la superficie
+    let tangent_gradient = gradient - normal * dot(&gradient, normal);
+    (normal - tangent_gradient * strength).normalize()
+}
+
+// Atmosphere shell: a sphere `scale` times the planet's radius drawn translucent over it.
+// The opacity comes from how much air the view ray crosses inside the shell (from entering
+// it until it hits the planet or leaves again), so the disk gets a thin tint and the limb,
+// where the ray grazes the planet, glows. `density` is the optical depth of a ray that
+// crosses the shell straight down, one thickness deep.
+#[derive(Clone, Copy)]
+pub struct Atmosphere {
+    pub scale: f32,
+    pub color: Color,
+    pub density: f32,
+}
+
+pub fn atmosphere_shader(fragment: &Fragment, uniforms: &Uniforms) -> (Color, f32) {
+    let Some(atmosphere) = uniforms.atmosphere else {
+        return (Color::black(), 0.0);
+    };
+
+    let object_position = fragment.object_position;
+    let world_position = (uniforms.model_matrix
+        * Vec4::new(object_position.x, object_position.y, object_position.z, 1.0))
+    .xyz();
+    let center = (uniforms.model_matrix * Vec4::new(0.0, 0.0, 0.0, 1.0)).xyz();
+    let eye = uniforms.camera_position;
+    let ray = (world_position - eye).normalize();
+
+    // Only the face of the shell where the ray enters adds air; the far face is the same ray
+    if dot(&fragment.normal, &ray) > 0.0 {
+        return (Color::black(), 0.0);
+    }
+
+    // The fragment lies on the shell, so its distance to the center is the outer radius
+    let outer_radius = (world_position - center).magnitude();
+    let inner_radius = outer_radius / atmosphere.scale.max(1.0 + 1e-3);
+
+    // Ray-sphere intersections: distances along the ray where it enters and leaves
+    let to_eye = eye - center;
+    let b = dot(&to_eye, &ray);
+    let hits = |radius: f32| {
+        let discriminant = b * b - (to_eye.magnitude_squared() - radius * radius);
+        (discriminant >= 0.0).then(|| {
+            let root = discriminant.sqrt();
+            (-b - root, -b + root)
+        })
+    };
+
+    let Some((outer_enter, outer_exit)) = hits(outer_radius) else {
+        return (Color::black(), 0.0);
+    };
+    let end = match hits(inner_radius) {
+        Some((inner_enter, _)) if inner_enter > 0.0 => inner_enter,
+        _ => outer_exit,
+    };
+    let chord = (end - outer_enter.max(0.0)).max(0.0);
+
+    let optical_depth = atmosphere.density * chord / (outer_radius - inner_radius);
+    let alpha = 1.0 - (-optical_depth).exp();
+
+    (atmosphere.color * (0.4 + 0.6 * fragment.intensity), alpha)
 }
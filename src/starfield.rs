@@ -0,0 +1,69 @@
+use crate::color::Color;
+use crate::framebuffer::Framebuffer;
+use nalgebra_glm::Vec2;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::f32::consts::PI;
+
+// Estrella del fondo procedural, en coordenadas de pantalla normalizadas (0 a 1) para que
+// el campo no cambie con la resolución
+struct Star {
+    position: Vec2,
+    brightness: f32, // 0 a 1; también decide el tamaño del disco
+    phase: f32,      // Desfase del titileo, fijo por estrella
+    speed: f32,      // Radianes por segundo del titileo
+    color: Color,
+}
+
+// Estrellas dibujadas como discos suavizados sobre el fondo. El brillo sigue una ley de
+// potencia (muchas tenues y pocas brillantes, como en el cielo real) y el titileo es una
+// onda con fase y velocidad propias de cada estrella, así se repite igual con la misma semilla.
+pub struct Starfield {
+    stars: Vec<Star>,
+    pub star_size: f32,       // Radio en píxeles de las estrellas más brillantes
+    pub flicker: Option<f32>, // Fracción del brillo que oscila al titilar (None = brillo fijo)
+}
+
+impl Starfield {
+    pub fn new(star_count: usize, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let tints = [
+            Color::new(255, 255, 255),
+            Color::new(200, 220, 255),
+            Color::new(255, 240, 210),
+        ];
+        let stars = (0..star_count)
+            .map(|_| Star {
+                position: Vec2::new(rng.gen_range(0.0..1.0), rng.gen_range(0.0..1.0)),
+                brightness: rng.gen_range(0.0f32..1.0).powi(3),
+                phase: rng.gen_range(0.0..2.0 * PI),
+                speed: rng.gen_range(1.0..4.0),
+                color: tints[rng.gen_range(0..tints.len())],
+            })
+            .collect();
+
+        Starfield {
+            stars,
+            star_size: 1.5,
+            flicker: None,
+        }
+    }
+
+    // Se mezcla sobre el fondo ya dibujado; no escribe el z-buffer
+    pub fn render(&self, framebuffer: &mut Framebuffer, time: f32) {
+        let width = framebuffer.width as f32;
+        let height = framebuffer.height as f32;
+
+        for star in &self.stars {
+            let twinkle = match self.flicker {
+                Some(amount) => 1.0 - amount * 0.5 * (1.0 + (time * star.speed + star.phase).sin()),
+                None => 1.0,
+            };
+            let center = Vec2::new(star.position.x * width, star.position.y * height);
+            let radius = self.star_size * (0.3 + 0.7 * star.brightness);
+            let alpha = (0.25 + 0.75 * star.brightness) * twinkle;
+
+            framebuffer.draw_disc(center, radius, star.color, alpha);
+        }
+    }
+}
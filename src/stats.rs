@@ -0,0 +1,62 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+// Contadores del frame en curso. Se suman una vez por llamada (no por fragmento) y sin
+// sincronización fuerte, así medir casi no cuesta nada
+static TRIANGLES: AtomicUsize = AtomicUsize::new(0);
+static FRAGMENTS: AtomicUsize = AtomicUsize::new(0);
+
+// Triángulos que llegaron a rasterizarse (ya descartados y recortados)
+pub fn count_triangles(count: usize) {
+    TRIANGLES.fetch_add(count, Ordering::Relaxed);
+}
+
+// Fragmentos generados por el rasterizador, antes de la prueba de profundidad
+pub fn count_fragments(count: usize) {
+    FRAGMENTS.fetch_add(count, Ordering::Relaxed);
+}
+
+// Resumen de la sesión que se imprime al salir
+pub struct SessionStats {
+    start: Instant,
+    frames: u64,
+    peak_triangles: usize,
+    peak_fragments: usize,
+}
+
+impl SessionStats {
+    pub fn new() -> Self {
+        SessionStats {
+            start: Instant::now(),
+            frames: 0,
+            peak_triangles: 0,
+            peak_fragments: 0,
+        }
+    }
+
+    // Cierra el frame: toma los contadores, guarda los máximos y los deja en cero
+    pub fn finish_frame(&mut self) {
+        self.frames += 1;
+        self.peak_triangles = self
+            .peak_triangles
+            .max(TRIANGLES.swap(0, Ordering::Relaxed));
+        self.peak_fragments = self
+            .peak_fragments
+            .max(FRAGMENTS.swap(0, Ordering::Relaxed));
+    }
+
+    pub fn report(&self) -> String {
+        let duration = self.start.elapsed().as_secs_f64();
+        let average_fps = if duration > 0.0 {
+            self.frames as f64 / duration
+        } else {
+            0.0
+        };
+
+        format!(
+            "Sesión: {} frames en {:.1} s ({:.1} FPS promedio)\n\
+             Pico por frame: {} triángulos, {} fragmentos",
+            self.frames, duration, average_fps, self.peak_triangles, self.peak_fragments
+        )
+    }
+}
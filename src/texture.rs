@@ -2,46 +2,359 @@ extern crate image;
 
 use crate::color::Color;
 use image::{DynamicImage, GenericImageView};
-use image::{ImageReader, RgbImage};
+use image::{ImageReader, ImageResult, RgbImage, RgbaImage};
+use nalgebra_glm::Vec3;
+use std::path::Path;
+
+// Píxeles de la textura: RGB para fondos y superficies, RGBA cuando se necesita transparencia
+enum TextureImage {
+    Rgb(RgbImage),
+    Rgba(RgbaImage),
+}
+
+// Cómo interpretar los valores de la imagen. Las texturas de color (fondos, superficies)
+// vienen codificadas en sRGB; los mapas de datos (alturas, normales) guardan valores lineales
+// que no deben pasar por la curva de gamma.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorSpace {
+    Srgb,
+    Linear,
+}
+
+// Qué hacer con las UV fuera de [0, 1] al muestrear
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WrapMode {
+    Clamp,  // Se repite el borde
+    Repeat, // La textura se repite (mosaico)
+    // Ninguna textura del proyecto la usa todavía: queda para imágenes que no son mosaicos
+    #[allow(dead_code)]
+    Mirror, // Se repite alternando copias reflejadas, sin costuras en los bordes
+}
+
+impl WrapMode {
+    // Lleva una coordenada a [0, 1]
+    fn apply(self, coordinate: f32) -> f32 {
+        match self {
+            WrapMode::Clamp => coordinate.clamp(0.0, 1.0),
+            WrapMode::Repeat => coordinate.rem_euclid(1.0),
+            WrapMode::Mirror => {
+                let period = coordinate.rem_euclid(2.0);
+                if period > 1.0 {
+                    2.0 - period
+                } else {
+                    period
+                }
+            }
+        }
+    }
+}
 
 pub struct Texture {
-    image: RgbImage,
+    image: TextureImage,
     pub width: u32,
     pub height: u32,
+    pub color_space: ColorSpace,
+    wrap_mode: WrapMode,
 }
 
 impl Texture {
     pub fn new(file_path: &str) -> Texture {
-        let img = ImageReader::open(file_path)
-            .unwrap()
-            .decode()
-            .unwrap()
-            .to_rgb8();
-        let width = img.width();
-        let height = img.height();
+        Self::load(file_path).unwrap()
+    }
+
+    // Igual que `new` pero conserva el canal alfa (sprites, íconos y billboards)
+    pub fn new_rgba(file_path: &str) -> Texture {
+        Self::load_rgba(file_path).unwrap()
+    }
+
+    // Versiones de `new` y `new_rgba` que devuelven el error en lugar de terminar el programa,
+    // para texturas opcionales. Ambas cargan texturas de color (sRGB).
+    pub fn load(file_path: &str) -> ImageResult<Texture> {
+        Self::load_as(file_path, ColorSpace::Srgb)
+    }
+
+    pub fn load_rgba(file_path: &str) -> ImageResult<Texture> {
+        Ok(Self::from_image(
+            TextureImage::Rgba(Self::decode(file_path)?.to_rgba8()),
+            ColorSpace::Srgb,
+        ))
+    }
+
+    // Textura RGB con el espacio de color indicado; los mapas de datos usan `Linear`
+    pub fn load_as(file_path: &str, color_space: ColorSpace) -> ImageResult<Texture> {
+        Ok(Self::from_image(
+            TextureImage::Rgb(Self::decode(file_path)?.to_rgb8()),
+            color_space,
+        ))
+    }
+
+    fn decode(file_path: &str) -> ImageResult<DynamicImage> {
+        ImageReader::open(file_path)?.decode()
+    }
+
+    fn from_image(image: TextureImage, color_space: ColorSpace) -> Texture {
+        let (width, height) = match &image {
+            TextureImage::Rgb(image) => image.dimensions(),
+            TextureImage::Rgba(image) => image.dimensions(),
+        };
         Texture {
-            image: img,
+            image,
             width,
             height,
+            color_space,
+            wrap_mode: WrapMode::Clamp,
         }
     }
 
+    pub fn set_wrap_mode(&mut self, wrap_mode: WrapMode) {
+        self.wrap_mode = wrap_mode;
+    }
+
     pub fn get_pixel(&self, x: usize, y: usize) -> Color {
-        let pixel = self.image.get_pixel(x as u32, y as u32);
-        Color::new(pixel[0] as u8, pixel[1] as u8, pixel[2] as u8)
+        self.texel(x as u32, y as u32).0
     }
 
     pub fn sample(&self, u: f32, v: f32) -> Color {
-        // Asegúrate de que u y v estén en el rango [0, 1]
-        let u = u.clamp(0.0, 1.0);
-        let v = v.clamp(0.0, 1.0);
+        self.sample_rgba(u, v).0
+    }
+
+    // Color y opacidad (0.0 - 1.0) en las coordenadas UV; las texturas RGB son siempre opacas
+    pub fn sample_rgba(&self, u: f32, v: f32) -> (Color, f32) {
+        // Llevar u y v al rango [0, 1] según el modo de repetición
+        let u = self.wrap_mode.apply(u);
+        let v = self.wrap_mode.apply(v);
 
         // Convertir u y v a coordenadas de píxel en la textura
         let x = (u * (self.width - 1) as f32).round() as u32;
         let y = (v * (self.height - 1) as f32).round() as u32;
 
-        // Obtener el color del píxel desde el campo `image`
-        let pixel = self.image.get_pixel(x, y);
-        Color::new(pixel[0], pixel[1], pixel[2])
+        self.texel(x, y)
+    }
+
+    // Valores lineales (0.0 - 1.0) en las coordenadas UV, para cálculos de luz: las texturas
+    // sRGB se decodifican con la curva de gamma y los mapas de datos se devuelven tal cual
+    pub fn sample_linear(&self, u: f32, v: f32) -> Vec3 {
+        let color = self.sample(u, v);
+        self.to_linear(&color)
+    }
+
+    // Normal en espacio tangente de un mapa de normales: cada canal pasa de [0, 255] a [-1, 1]
+    // (X = rojo, Y = verde, Z = azul). Un texel "plano" (128, 128, 255) da (0, 0, 1); si el
+    // texel no codifica una dirección (negro puro, por ejemplo) también se devuelve esa
+    pub fn sample_normal(&self, u: f32, v: f32) -> Vec3 {
+        let color = self.sample(u, v);
+        let channel = |value: u8| value as f32 / 255.0 * 2.0 - 1.0;
+        Vec3::new(channel(color.r), channel(color.g), channel(color.b))
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_else(|| Vec3::new(0.0, 0.0, 1.0))
+    }
+
+    fn to_linear(&self, color: &Color) -> Vec3 {
+        let channel = |value: u8| {
+            let value = value as f32 / 255.0;
+            match self.color_space {
+                ColorSpace::Srgb => srgb_to_linear(value),
+                ColorSpace::Linear => value,
+            }
+        };
+        Vec3::new(channel(color.r), channel(color.g), channel(color.b))
+    }
+
+    // Color promedio de la textura a partir de una cuadrícula de como máximo `grid` x `grid`
+    // muestras, así el costo no depende del tamaño de la imagen. El promedio se hace en
+    // espacio lineal (promediar valores sRGB oscurece el resultado) y se devuelve codificado
+    // en el espacio de color de la textura.
+    pub fn average_color(&self, grid: u32) -> Color {
+        let step_x = (self.width / grid.max(1)).max(1);
+        let step_y = (self.height / grid.max(1)).max(1);
+
+        let mut sum = Vec3::zeros();
+        let mut count = 0.0;
+        for y in (0..self.height).step_by(step_y as usize) {
+            for x in (0..self.width).step_by(step_x as usize) {
+                sum += self.to_linear(&self.texel(x, y).0);
+                count += 1.0;
+            }
+        }
+
+        let average = sum / count;
+        let encode = |value: f32| match self.color_space {
+            ColorSpace::Srgb => linear_to_srgb(value),
+            ColorSpace::Linear => value,
+        };
+        Color::from_float(encode(average.x), encode(average.y), encode(average.z))
+    }
+
+    // Altura (0.0 - 1.0, del canal rojo, sin curva de gamma) con interpolación bilineal; U se repite, así el
+    // borde izquierdo y el derecho de un mapa equirectangular se unen sin costura
+    pub fn sample_height(&self, u: f32, v: f32) -> f32 {
+        let x = u.rem_euclid(1.0) * self.width as f32 - 0.5;
+        let y = (v.clamp(0.0, 1.0) * self.height as f32 - 0.5).clamp(0.0, (self.height - 1) as f32);
+
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let tx = x - x0;
+        let ty = y - y0;
+
+        let height = |x: f32, y: f32| {
+            let x = (x as i64).rem_euclid(self.width as i64) as u32;
+            let y = (y as u32).min(self.height - 1);
+            self.texel(x, y).0.r as f32 / 255.0
+        };
+
+        let top = height(x0, y0) * (1.0 - tx) + height(x0 + 1.0, y0) * tx;
+        let bottom = height(x0, y0 + 1.0) * (1.0 - tx) + height(x0 + 1.0, y0 + 1.0) * tx;
+        top * (1.0 - ty) + bottom * ty
+    }
+
+    fn texel(&self, x: u32, y: u32) -> (Color, f32) {
+        match &self.image {
+            TextureImage::Rgb(image) => {
+                let pixel = image.get_pixel(x, y);
+                (Color::new(pixel[0], pixel[1], pixel[2]), 1.0)
+            }
+            TextureImage::Rgba(image) => {
+                let pixel = image.get_pixel(x, y);
+                (
+                    Color::new(pixel[0], pixel[1], pixel[2]),
+                    pixel[3] as f32 / 255.0,
+                )
+            }
+        }
+    }
+}
+
+// Caras de un cubemap en el orden de OpenGL: +X, -X, +Y, -Y, +Z, -Z
+const CUBEMAP_FACES: [&str; 6] = ["px", "nx", "py", "ny", "pz", "nz"];
+
+// Fondo que rodea a la cámara: seis texturas, una por cara de un cubo centrado en ella. Se
+// muestrea por dirección, así el fondo gira con la cámara en lugar de estar pegado a la pantalla
+pub struct Cubemap {
+    faces: [Texture; 6],
+}
+
+impl Cubemap {
+    // Carga `px`, `nx`, `py`, `ny`, `pz` y `nz` (.png o .jpg) de un directorio
+    pub fn load(directory: &str) -> ImageResult<Cubemap> {
+        let load_face = |name: &str| {
+            let path = Path::new(directory).join(name);
+            Texture::load(&path.with_extension("png").to_string_lossy())
+                .or_else(|_| Texture::load(&path.with_extension("jpg").to_string_lossy()))
+        };
+
+        let [px, nx, py, ny, pz, nz] = CUBEMAP_FACES.map(load_face);
+        Ok(Cubemap {
+            faces: [px?, nx?, py?, ny?, pz?, nz?],
+        })
+    }
+
+    // Color en la dirección `direction` (no hace falta normalizarla). La cara es la del eje
+    // dominante; las UV dentro de la cara siguen la convención de OpenGL, con V hacia abajo
+    // en la imagen
+    pub fn sample(&self, direction: &Vec3) -> Color {
+        let (x, y, z) = (direction.x, direction.y, direction.z);
+        let (ax, ay, az) = (x.abs(), y.abs(), z.abs());
+
+        let (face, major, s, t) = if ax >= ay && ax >= az {
+            if x > 0.0 {
+                (0, ax, -z, -y)
+            } else {
+                (1, ax, z, -y)
+            }
+        } else if ay >= az {
+            if y > 0.0 {
+                (2, ay, x, z)
+            } else {
+                (3, ay, x, -z)
+            }
+        } else if z > 0.0 {
+            (4, az, x, -y)
+        } else {
+            (5, az, -x, -y)
+        };
+
+        let major = major.max(f32::EPSILON);
+        let u = 0.5 * (s / major + 1.0);
+        let v = 0.5 * (t / major + 1.0);
+        self.faces[face].sample(u, v)
+    }
+}
+
+// Curva estándar de sRGB, por canal en el rango 0.0 - 1.0
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Franja de 5x1 píxeles con un color distinto en cada columna
+    fn strip(wrap_mode: WrapMode) -> Texture {
+        let colors = [
+            [255, 0, 0],
+            [0, 255, 0],
+            [0, 0, 255],
+            [255, 255, 0],
+            [0, 255, 255],
+        ];
+        let image = RgbImage::from_fn(5, 1, |x, _| image::Rgb(colors[x as usize]));
+        let mut texture = Texture::from_image(TextureImage::Rgb(image), ColorSpace::Srgb);
+        texture.set_wrap_mode(wrap_mode);
+        texture
+    }
+
+    #[test]
+    fn flat_texel_is_an_unperturbed_normal() {
+        let image = RgbImage::from_pixel(2, 2, image::Rgb([128, 128, 255]));
+        let texture = Texture::from_image(TextureImage::Rgb(image), ColorSpace::Linear);
+        let normal = texture.sample_normal(0.5, 0.5);
+        assert!((normal - Vec3::new(0.0, 0.0, 1.0)).magnitude() < 0.01);
+    }
+
+    #[test]
+    fn wrap_modes_map_u_past_the_edge() {
+        assert_eq!(WrapMode::Clamp.apply(1.5), 1.0);
+        assert_eq!(WrapMode::Repeat.apply(1.5), 0.5);
+        assert_eq!(WrapMode::Mirror.apply(1.5), 0.5);
+    }
+
+    #[test]
+    fn sampling_past_the_edge_follows_the_wrap_mode() {
+        // u = 1.5: el borde derecho, la columna central de la copia siguiente y la del reflejo
+        assert_eq!(
+            strip(WrapMode::Clamp).sample(1.5, 0.0),
+            Color::new(0, 255, 255)
+        );
+        assert_eq!(
+            strip(WrapMode::Repeat).sample(1.5, 0.0),
+            Color::new(0, 0, 255)
+        );
+        assert_eq!(
+            strip(WrapMode::Mirror).sample(1.5, 0.0),
+            Color::new(0, 0, 255)
+        );
+
+        // u = 1.25: la copia repetida avanza desde la izquierda y la reflejada desde la derecha
+        assert_eq!(
+            strip(WrapMode::Repeat).sample(1.25, 0.0),
+            Color::new(0, 255, 0)
+        );
+        assert_eq!(
+            strip(WrapMode::Mirror).sample(1.25, 0.0),
+            Color::new(255, 255, 0)
+        );
     }
 }
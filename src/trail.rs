@@ -0,0 +1,34 @@
+use nalgebra_glm::Vec3;
+use std::collections::VecDeque;
+
+// Búfer circular con las posiciones recientes de un cuerpo
+pub struct OrbitTrail {
+    points: VecDeque<Vec3>,
+    capacity: usize,
+}
+
+impl OrbitTrail {
+    pub fn new(capacity: usize) -> Self {
+        OrbitTrail {
+            points: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, position: Vec3) {
+        if self.points.len() == self.capacity {
+            self.points.pop_front();
+        }
+        self.points.push_back(position);
+    }
+
+    // Segmentos consecutivos con su alfa: los más viejos son los más transparentes
+    pub fn segments(&self) -> impl Iterator<Item = (Vec3, Vec3, f32)> + '_ {
+        let count = self.points.len();
+        self.points
+            .iter()
+            .zip(self.points.iter().skip(1))
+            .enumerate()
+            .map(move |(i, (start, end))| (*start, *end, (i + 1) as f32 / count as f32))
+    }
+}
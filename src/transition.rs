@@ -0,0 +1,60 @@
+use crate::color::Color;
+use crate::framebuffer::Framebuffer;
+
+// Alto (en filas) del brillo que deja la línea de barrido sobre la imagen nueva
+const SCAN_GLOW_ROWS: f32 = 24.0;
+
+// Transición de "escaneo" al cambiar de objetivo: una línea horizontal baja por la pantalla
+// y revela el frame nuevo sobre una copia del último frame antes del cambio
+pub struct ScanTransition {
+    previous: Vec<Color>,
+    elapsed: f32,
+    duration: f32, // Segundos que tarda la línea en cruzar la pantalla
+}
+
+impl ScanTransition {
+    // Guarda el contenido actual del framebuffer (el último frame mostrado)
+    pub fn start(framebuffer: &Framebuffer, duration: f32) -> Self {
+        ScanTransition {
+            previous: framebuffer.buffer.clone(),
+            elapsed: 0.0,
+            duration: duration.max(1e-3),
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+
+    // Compone el frame recién dibujado con el anterior y avanza `delta_time`. Arriba de la
+    // línea queda el frame nuevo, abajo el anterior, y la línea deja un brillo que se apaga
+    pub fn apply(&mut self, framebuffer: &mut Framebuffer, delta_time: f32) {
+        if self.previous.len() != framebuffer.buffer.len() {
+            self.elapsed = self.duration;
+            return;
+        }
+
+        // Avance con aceleración y frenado suaves (smoothstep)
+        let t = (self.elapsed / self.duration).clamp(0.0, 1.0);
+        let eased = t * t * (3.0 - 2.0 * t);
+        let scan_row = eased * (framebuffer.height as f32 + SCAN_GLOW_ROWS);
+        let glow_color = Color::new(120, 230, 255);
+
+        for y in 0..framebuffer.height {
+            let row = &mut framebuffer.buffer[y * framebuffer.width..(y + 1) * framebuffer.width];
+            let distance = scan_row - y as f32;
+
+            if distance < 0.0 {
+                let start = y * framebuffer.width;
+                row.copy_from_slice(&self.previous[start..start + framebuffer.width]);
+            } else if distance < SCAN_GLOW_ROWS {
+                let glow = 1.0 - distance / SCAN_GLOW_ROWS;
+                for pixel in row.iter_mut() {
+                    *pixel = pixel.lerp(&glow_color, glow * glow * 0.8);
+                }
+            }
+        }
+
+        self.elapsed += delta_time;
+    }
+}
@@ -1,6 +1,6 @@
-use crate::color::Color;
 use crate::fragment::Fragment;
-use crate::vertex::{self, Vertex};
+use crate::stats;
+use crate::vertex::{Vertex, MIN_CLIP_W};
 use nalgebra_glm::{dot, Vec2, Vec3};
 
 /*
@@ -16,7 +16,20 @@ pub fn _triangle(v1: &Vertex, v2: &Vertex, v3: &Vertex) -> Vec<Fragment> {
 }
 */
 
-pub fn triangle(v1: &Vertex, v2: &Vertex, v3: &Vertex) -> Vec<Fragment> {
+// Side in pixels of the square screen tiles the rasterizer walks
+pub const TILE_SIZE: i32 = 32;
+
+// Rasterizes the triangle into fragments inside a `width` x `height` screen. The bounding box
+// is clipped to the screen once, then walked in TILE_SIZE tiles: the edge functions are
+// linear, so testing a tile's four corner pixels tells whether the whole tile is outside the
+// triangle (skipped), fully inside (no per-pixel test) or crossed by an edge.
+pub fn triangle(
+    v1: &Vertex,
+    v2: &Vertex,
+    v3: &Vertex,
+    width: usize,
+    height: usize,
+) -> Vec<Fragment> {
     let mut fragments = Vec::new();
     let (a, b, c) = (
         v1.transformed_position,
@@ -25,58 +38,183 @@ pub fn triangle(v1: &Vertex, v2: &Vertex, v3: &Vertex) -> Vec<Fragment> {
     );
 
     let (min_x, min_y, max_x, max_y) = calculate_bounding_box(&a, &b, &c);
+    let (min_x, min_y) = (min_x.max(0), min_y.max(0));
+    let max_x = max_x.min(width as i32 - 1);
+    let max_y = max_y.min(height as i32 - 1);
 
     let triangle_area = edge_function(&a, &b, &c);
-    let light_dir = Vec3::new(0.0, 0.0, 1.0).normalize();
-
-    // Iterate over each pixel in the bounding box
-    for y in min_y..=max_y {
-        for x in min_x..=max_x {
-            let point = Vec3::new(x as f32 + 0.5, y as f32 + 0.5, 0.0);
-
-            // Calculate barycentric coordinates
-            let (w1, w2, w3) = barycentric_coordinates(&point, &a, &b, &c, triangle_area);
-
-            // Check if the point is inside the triangle
-            if w1 >= 0.0 && w1 <= 1.0 && w2 >= 0.0 && w2 <= 1.0 && w3 >= 0.0 && w3 <= 1.0 {
-                // Interpolate normal
-                let normal = (v1.transformed_normal * w1
-                    + v2.transformed_normal * w2
-                    + v3.transformed_normal * w3)
-                    .normalize();
-
-                // Calculate lighting intensity
-                let intensity = dot(&normal, &light_dir).max(0.0);
-
-                // Create a gray color (unchanged)
-                let color = Color::new(100, 100, 100); // Medium gray
-
-                // Interpolate depth
-                let depth = a.z * w1 + b.z * w2 + c.z * w3;
-
-                // Positions of the original vertex
-                let vertex_position = v1.position * w1 + v2.position * w2 + v3.position * w3;
-
-                fragments.push(Fragment::new(
-                    Vec2::new(x as f32, y as f32),
-                    color,
-                    depth,
-                    normal,
-                    intensity,
-                    vertex_position,
-                ));
+
+    // Triángulos degenerados no cubren ningún centro de píxel
+    if triangle_area.abs() < f32::EPSILON || min_x > max_x || min_y > max_y {
+        return fragments;
+    }
+    let light_dir = light_direction();
+
+    // 1 / w of each vertex, to undo the perspective divide when interpolating attributes
+    let inverse_w = [v1, v2, v3].map(|vertex| 1.0 / vertex.clip_position.w.max(MIN_CLIP_W));
+
+    let weights_at = |x: i32, y: i32| {
+        let point = Vec3::new(x as f32 + 0.5, y as f32 + 0.5, 0.0);
+        barycentric_coordinates(&point, &a, &b, &c, triangle_area)
+    };
+
+    for tile_y in (min_y..=max_y).step_by(TILE_SIZE as usize) {
+        let tile_max_y = (tile_y + TILE_SIZE - 1).min(max_y);
+        for tile_x in (min_x..=max_x).step_by(TILE_SIZE as usize) {
+            let tile_max_x = (tile_x + TILE_SIZE - 1).min(max_x);
+
+            let corners = [
+                weights_at(tile_x, tile_y),
+                weights_at(tile_max_x, tile_y),
+                weights_at(tile_x, tile_max_y),
+                weights_at(tile_max_x, tile_max_y),
+            ];
+            // All four corners behind the same edge: so is every pixel in between
+            if corners.iter().all(|w| w.0 < 0.0)
+                || corners.iter().all(|w| w.1 < 0.0)
+                || corners.iter().all(|w| w.2 < 0.0)
+            {
+                continue;
+            }
+            let fully_covered = corners
+                .iter()
+                .all(|w| w.0 >= 0.0 && w.1 >= 0.0 && w.2 >= 0.0);
+
+            for y in tile_y..=tile_max_y {
+                for x in tile_x..=tile_max_x {
+                    let (w1, w2, w3) = weights_at(x, y);
+
+                    // Check if the pixel center is inside the triangle
+                    if !fully_covered && (w1 < 0.0 || w2 < 0.0 || w3 < 0.0) {
+                        continue;
+                    }
+
+                    fragments.push(interpolate_fragment(
+                        [v1, v2, v3],
+                        (x, y),
+                        (w1, w2, w3),
+                        &inverse_w,
+                        &light_dir,
+                    ));
+                }
             }
         }
     }
 
+    stats::count_fragments(fragments.len());
     fragments
 }
 
+// Fragment at pixel (x, y) from its screen-space barycentric weights
+fn interpolate_fragment(
+    [v1, v2, v3]: [&Vertex; 3],
+    (x, y): (i32, i32),
+    (w1, w2, w3): (f32, f32, f32),
+    inverse_w: &[f32; 3],
+    light_dir: &Vec3,
+) -> Fragment {
+    let (a, b, c) = (
+        v1.transformed_position,
+        v2.transformed_position,
+        v3.transformed_position,
+    );
+
+    // Screen-space barycentrics are not linear in the triangle's own space:
+    // weighting them by 1 / w and renormalizing gives perspective-correct ones
+    let p1 = w1 * inverse_w[0];
+    let p2 = w2 * inverse_w[1];
+    let p3 = w3 * inverse_w[2];
+    let sum = p1 + p2 + p3;
+    let (p1, p2, p3) = (p1 / sum, p2 / sum, p3 / sum);
+
+    // Interpolate normal. Vertices without a normal (or with normals that cancel
+    // out) give a zero vector instead of NaN, so the fragment is simply unlit
+    let normal =
+        (v1.transformed_normal * p1 + v2.transformed_normal * p2 + v3.transformed_normal * p3)
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_else(Vec3::zeros);
+
+    // Calculate lighting intensity
+    let intensity = dot(&normal, light_dir).max(0.0);
+
+    // The face's material color (`Kd`); all three vertices of a face share it
+    let color = v1.color;
+
+    // Interpolate depth. The z-buffer stores NDC depth (clip z / clip w), which is
+    // affine in screen space, so the screen-space weights are already exact here:
+    // this equals interpolating clip z and 1 / w perspective-correctly and dividing
+    let depth = a.z * w1 + b.z * w2 + c.z * w3;
+
+    // Interpolated object-space position
+    let object_position = v1.position * p1 + v2.position * p2 + v3.position * p3;
+
+    // Interpolated UVs
+    let tex_coords = v1.tex_coords * p1 + v2.tex_coords * p2 + v3.tex_coords * p3;
+
+    // Interpolated tangent frame (re-orthonormalized by `Fragment::tangent_frame`)
+    let tangent =
+        v1.transformed_tangent * p1 + v2.transformed_tangent * p2 + v3.transformed_tangent * p3;
+    let bitangent = v1.transformed_bitangent * p1
+        + v2.transformed_bitangent * p2
+        + v3.transformed_bitangent * p3;
+
+    Fragment::new(
+        Vec2::new(x as f32, y as f32),
+        color,
+        depth,
+        normal,
+        intensity,
+        object_position,
+    )
+    .with_tex_coords(tex_coords)
+    .with_tangents(tangent, bitangent)
+    .with_material(v1.material)
+}
+
+// Vertex order of the faces that look at the camera, as seen on screen with +Y up (NDC),
+// like OpenGL's front face
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Winding {
+    Clockwise,
+    CounterClockwise,
+}
+
+impl Winding {
+    pub fn flipped(self) -> Self {
+        match self {
+            Winding::Clockwise => Winding::CounterClockwise,
+            Winding::CounterClockwise => Winding::Clockwise,
+        }
+    }
+
+    // Whether a triangle with screen-space vertices faces the camera. The viewport flips Y,
+    // so counter-clockwise in NDC gives a positive edge function in screen space
+    pub fn is_front_facing(self, v1: &Vertex, v2: &Vertex, v3: &Vertex) -> bool {
+        let area = edge_function(
+            &v1.transformed_position,
+            &v2.transformed_position,
+            &v3.transformed_position,
+        );
+        match self {
+            Winding::CounterClockwise => area > 0.0,
+            Winding::Clockwise => area < 0.0,
+        }
+    }
+}
+
+// Direction the scene light comes from, shared by the rasterizer's diffuse term and the
+// shaders that relight perturbed normals
+pub fn light_direction() -> Vec3 {
+    Vec3::new(0.0, 0.0, 1.0)
+}
+
+// Vertices stay in subpixel (float) space; only pixels whose center (x + 0.5)
+// can fall inside the triangle are visited.
 fn calculate_bounding_box(v1: &Vec3, v2: &Vec3, v3: &Vec3) -> (i32, i32, i32, i32) {
-    let min_x = v1.x.min(v2.x).min(v3.x).floor() as i32;
-    let min_y = v1.y.min(v2.y).min(v3.y).floor() as i32;
-    let max_x = v1.x.max(v2.x).max(v3.x).ceil() as i32;
-    let max_y = v1.y.max(v2.y).max(v3.y).ceil() as i32;
+    let min_x = (v1.x.min(v2.x).min(v3.x) - 0.5).ceil() as i32;
+    let min_y = (v1.y.min(v2.y).min(v3.y) - 0.5).ceil() as i32;
+    let max_x = (v1.x.max(v2.x).max(v3.x) - 0.5).floor() as i32;
+    let max_y = (v1.y.max(v2.y).max(v3.y) - 0.5).floor() as i32;
 
     (min_x, min_y, max_x, max_y)
 }
@@ -92,3 +230,189 @@ fn barycentric_coordinates(p: &Vec3, a: &Vec3, b: &Vec3, c: &Vec3, area: f32) ->
 fn edge_function(a: &Vec3, b: &Vec3, c: &Vec3) -> f32 {
     (c.x - a.x) * (b.y - a.y) - (c.y - a.y) * (b.x - a.x)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra_glm::Vec4;
+
+    // Screen vertex of a 200x200 viewport from clip coordinates (x, y, w), with its UVs
+    fn screen_vertex(x: f32, y: f32, w: f32, u: f32, v: f32) -> Vertex {
+        let mut vertex = Vertex::new(Vec3::zeros(), Vec3::new(0.0, 0.0, 1.0), Vec2::new(u, v));
+        vertex.clip_position = Vec4::new(x, y, 0.0, w);
+        vertex.transformed_position = Vec3::new(100.0 + 90.0 * x / w, 100.0 - 90.0 * y / w, 0.5);
+        vertex
+    }
+
+    // Rasterizer without tiles, for comparison: walks the whole bounding box and tests every
+    // pixel against the screen and the three edges
+    fn untiled_triangle(
+        v1: &Vertex,
+        v2: &Vertex,
+        v3: &Vertex,
+        width: usize,
+        height: usize,
+    ) -> Vec<Fragment> {
+        let (a, b, c) = (
+            v1.transformed_position,
+            v2.transformed_position,
+            v3.transformed_position,
+        );
+        let (min_x, min_y, max_x, max_y) = calculate_bounding_box(&a, &b, &c);
+        let triangle_area = edge_function(&a, &b, &c);
+        if triangle_area.abs() < f32::EPSILON {
+            return Vec::new();
+        }
+        let light_dir = light_direction();
+        let inverse_w = [v1, v2, v3].map(|vertex| 1.0 / vertex.clip_position.w.max(MIN_CLIP_W));
+
+        let mut fragments = Vec::new();
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                if x < 0 || y < 0 || x >= width as i32 || y >= height as i32 {
+                    continue;
+                }
+                let point = Vec3::new(x as f32 + 0.5, y as f32 + 0.5, 0.0);
+                let (w1, w2, w3) = barycentric_coordinates(&point, &a, &b, &c, triangle_area);
+                if w1 >= 0.0 && w2 >= 0.0 && w3 >= 0.0 {
+                    fragments.push(interpolate_fragment(
+                        [v1, v2, v3],
+                        (x, y),
+                        (w1, w2, w3),
+                        &inverse_w,
+                        &light_dir,
+                    ));
+                }
+            }
+        }
+        fragments
+    }
+
+    // Unit sphere seen head-on, scaled to `radius` pixels and centered on `center`
+    fn sphere_on_screen(center: (f32, f32), radius: f32) -> Vec<Vertex> {
+        let mut vertices = crate::mesh::uv_sphere(32, 64);
+        for vertex in &mut vertices {
+            let p = vertex.position;
+            vertex.transformed_position = Vec3::new(
+                center.0 + p.x * radius,
+                center.1 - p.y * radius,
+                0.5 - p.z * 0.1,
+            );
+        }
+        vertices
+    }
+
+    fn pixels(fragments: &[Fragment]) -> Vec<(f32, f32)> {
+        let mut pixels: Vec<_> = fragments
+            .iter()
+            .map(|fragment| (fragment.position.x, fragment.position.y))
+            .collect();
+        pixels.sort_by(|p1, p2| p1.partial_cmp(p2).unwrap());
+        pixels
+    }
+
+    #[test]
+    fn tiles_cover_the_same_pixels() {
+        // Partly off the left and bottom edges, so clipping the tiles to the screen matters
+        let sphere = sphere_on_screen((40.0, 170.0), 90.0);
+        for tri in sphere.chunks_exact(3) {
+            let tiled = triangle(&tri[0], &tri[1], &tri[2], 200, 200);
+            let untiled = untiled_triangle(&tri[0], &tri[1], &tri[2], 200, 200);
+            assert_eq!(pixels(&tiled), pixels(&untiled));
+        }
+    }
+
+    // Measurement: `cargo test --release -- --ignored --nocapture tiled_rasterization`
+    #[test]
+    #[ignore]
+    fn tiled_rasterization_speedup() {
+        let spheres: Vec<_> = [
+            (400.0, 400.0, 350.0),
+            (100.0, 650.0, 250.0),
+            (700.0, 150.0, 120.0),
+        ]
+        .iter()
+        .flat_map(|&(x, y, radius)| sphere_on_screen((x, y), radius))
+        .collect();
+        let time = |rasterize: fn(&Vertex, &Vertex, &Vertex, usize, usize) -> Vec<Fragment>| {
+            let start = std::time::Instant::now();
+            let mut count = 0;
+            for _ in 0..10 {
+                for tri in spheres.chunks_exact(3) {
+                    count += rasterize(&tri[0], &tri[1], &tri[2], 800, 800).len();
+                }
+            }
+            (start.elapsed(), count)
+        };
+
+        let (untiled, untiled_count) = time(untiled_triangle);
+        let (tiled, tiled_count) = time(triangle);
+        assert_eq!(tiled_count, untiled_count);
+        println!(
+            "Without tiles: {:?}, with tiles: {:?} (x{:.2})",
+            untiled,
+            tiled,
+            untiled.as_secs_f64() / tiled.as_secs_f64()
+        );
+    }
+
+    #[test]
+    fn identical_vertex_normals_are_kept_mid_triangle() {
+        let normal = Vec3::new(1.0, 2.0, 2.0).normalize();
+        let [v1, v2, v3] =
+            [(-1.0, -1.0, 1.0), (1.0, -1.0, 2.0), (0.0, 1.0, 4.0)].map(|(x, y, w)| {
+                let mut vertex = screen_vertex(x * w, y * w, w, 0.0, 0.0);
+                vertex.transformed_normal = normal;
+                vertex
+            });
+
+        let fragments = triangle(&v1, &v2, &v3, 200, 200);
+        let centroid =
+            (v1.transformed_position + v2.transformed_position + v3.transformed_position) / 3.0;
+        let middle = fragments
+            .iter()
+            .find(|fragment| {
+                fragment.position.x == centroid.x.floor()
+                    && fragment.position.y == centroid.y.floor()
+            })
+            .unwrap();
+
+        assert!((middle.normal - normal).magnitude() < 1e-5);
+    }
+
+    #[test]
+    fn checkerboard_diagonal_stays_straight() {
+        // A floor receding from the camera: the near edge has w = 1 and the far one w = 3
+        let a = screen_vertex(-1.0, -1.0, 1.0, 0.0, 0.0);
+        let b = screen_vertex(1.0, -1.0, 1.0, 1.0, 0.0);
+        let c = screen_vertex(1.0, -1.0, 3.0, 1.0, 1.0);
+        let d = screen_vertex(-1.0, -1.0, 3.0, 0.0, 1.0);
+
+        let mut fragments = triangle(&a, &b, &c, 200, 200);
+        fragments.extend(triangle(&a, &c, &d, 200, 200));
+
+        // The checkerboard's diagonal u + v = 1 joins B and D in the plane, so on screen it
+        // must follow the straight segment between them, across both triangles
+        let (from, to) = (b.transformed_position, d.transformed_position);
+        for y in (from.y.min(to.y).ceil() as i32)..(from.y.max(to.y) as i32) {
+            let on_diagonal = fragments
+                .iter()
+                .filter(|fragment| fragment.position.y as i32 == y)
+                .min_by(|f1, f2| {
+                    let distance = |f: &Fragment| (f.tex_coords.x + f.tex_coords.y - 1.0).abs();
+                    distance(f1).total_cmp(&distance(f2))
+                })
+                .unwrap();
+
+            let t = (y as f32 + 0.5 - from.y) / (to.y - from.y);
+            let expected_x = from.x + (to.x - from.x) * t - 0.5;
+            assert!(
+                (on_diagonal.position.x - expected_x).abs() <= 1.0,
+                "row {}: diagonal at x = {}, expected {}",
+                y,
+                on_diagonal.position.x,
+                expected_x
+            );
+        }
+    }
+}
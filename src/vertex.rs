@@ -1,5 +1,9 @@
 use crate::color::Color;
-use nalgebra_glm::{Vec2, Vec3};
+use nalgebra_glm::{Vec2, Vec3, Vec4};
+
+// `w` mínimo en espacio de clip para poder hacer la división de perspectiva; con w <= 0 el
+// vértice está sobre o detrás del plano de la cámara
+pub const MIN_CLIP_W: f32 = 1e-5;
 
 #[derive(Clone, Debug)]
 pub struct Vertex {
@@ -7,8 +11,16 @@ pub struct Vertex {
     pub normal: Vec3,
     pub tex_coords: Vec2,
     pub color: Color,
+    pub material: usize, // Índice en `Obj::materials` (0 = material por defecto)
+    // Direcciones de U y V sobre la superficie (espacio de objeto), para normal mapping en
+    // espacio tangente. Quedan en cero si la malla no tiene UV útiles
+    pub tangent: Vec3,
+    pub bitangent: Vec3,
     pub transformed_position: Vec3,
     pub transformed_normal: Vec3,
+    pub transformed_tangent: Vec3,
+    pub transformed_bitangent: Vec3,
+    pub clip_position: Vec4, // Posición antes de la división de perspectiva (para recortar)
 }
 
 impl Vertex {
@@ -18,8 +30,14 @@ impl Vertex {
             normal,
             tex_coords,
             color: Color::black(),
+            material: 0,
+            tangent: Vec3::zeros(),
+            bitangent: Vec3::zeros(),
             transformed_position: position,
             transformed_normal: normal,
+            transformed_tangent: Vec3::zeros(),
+            transformed_bitangent: Vec3::zeros(),
+            clip_position: Vec4::new(position.x, position.y, position.z, 1.0),
         }
     }
 
@@ -29,11 +47,23 @@ impl Vertex {
             normal: Vec3::new(0.0, 0.0, 0.0),
             tex_coords: Vec2::new(0.0, 0.0),
             color,
+            material: 0,
+            tangent: Vec3::zeros(),
+            bitangent: Vec3::zeros(),
             transformed_position: Vec3::new(0.0, 0.0, 0.0),
             transformed_normal: Vec3::new(0.0, 0.0, 0.0),
+            transformed_tangent: Vec3::zeros(),
+            transformed_bitangent: Vec3::zeros(),
+            clip_position: Vec4::new(position.x, position.y, position.z, 1.0),
         }
     }
 
+    // Falso si el vértice está sobre o detrás de la cámara: su posición en pantalla no es
+    // válida y el triángulo debe recortarse o descartarse
+    pub fn is_projectable(&self) -> bool {
+        self.clip_position.w > MIN_CLIP_W
+    }
+
     pub fn set_transformed(&mut self, position: Vec3, normal: Vec3) {
         self.transformed_position = position;
         self.transformed_normal = normal;
@@ -47,8 +77,14 @@ impl Default for Vertex {
             normal: Vec3::new(0.0, 1.0, 0.0),
             tex_coords: Vec2::new(0.0, 0.0),
             color: Color::black(),
+            material: 0,
+            tangent: Vec3::zeros(),
+            bitangent: Vec3::zeros(),
             transformed_position: Vec3::new(0.0, 0.0, 0.0),
             transformed_normal: Vec3::new(0.0, 1.0, 0.0),
+            transformed_tangent: Vec3::zeros(),
+            transformed_bitangent: Vec3::zeros(),
+            clip_position: Vec4::new(0.0, 0.0, 0.0, 1.0),
         }
     }
 }
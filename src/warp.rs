@@ -0,0 +1,69 @@
+use crate::color::Color;
+use crate::framebuffer::Framebuffer;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::f32::consts::PI;
+
+// Estrella de la animación de arranque, en coordenadas polares relativas al centro de la
+// pantalla (`distance` = 1 es la esquina)
+struct WarpStar {
+    angle: f32,
+    distance: f32,
+    brightness: f32,
+}
+
+// Efecto de "salto al sistema" al iniciar: estrellas estiradas radialmente que se acortan y
+// se apagan hasta desaparecer sobre el fondo normal
+pub struct WarpIntro {
+    stars: Vec<WarpStar>,
+    pub duration: f32, // Segundos que dura el efecto
+}
+
+impl WarpIntro {
+    pub fn new(star_count: usize, duration: f32, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let stars = (0..star_count)
+            .map(|_| WarpStar {
+                angle: rng.gen_range(0.0..2.0 * PI),
+                distance: rng.gen_range(0.05..1.0),
+                brightness: rng.gen_range(0.4..1.0),
+            })
+            .collect();
+
+        WarpIntro { stars, duration }
+    }
+
+    pub fn is_active(&self, time: f32) -> bool {
+        time < self.duration
+    }
+
+    // Se suma sobre el fondo ya dibujado; no escribe el z-buffer
+    pub fn render(&self, framebuffer: &mut Framebuffer, time: f32) {
+        if !self.is_active(time) {
+            return;
+        }
+
+        let progress = time / self.duration;
+        let stretch = 4.0 * (-5.0 * progress).exp(); // Largo de la estela, decae con el tiempo
+        let fade = 1.0 - progress * progress;
+
+        let center_x = framebuffer.width as f32 * 0.5;
+        let center_y = framebuffer.height as f32 * 0.5;
+        let half_diagonal = (center_x * center_x + center_y * center_y).sqrt();
+
+        for star in &self.stars {
+            let (sin, cos) = star.angle.sin_cos();
+            let start = star.distance * half_diagonal;
+            let end = start * (1.0 + stretch);
+            let color = Color::new(200, 220, 255) * (star.brightness * fade);
+
+            framebuffer.draw_line_additive(
+                (center_x + cos * start) as isize,
+                (center_y + sin * start) as isize,
+                (center_x + cos * end) as isize,
+                (center_y + sin * end) as isize,
+                color,
+            );
+        }
+    }
+}